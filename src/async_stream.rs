@@ -0,0 +1,231 @@
+// futures::Stream and futures::Sink adapters for RX and TX
+// Copyright Adam Greig <adam@adamgreig.com> 2014
+// Licensed under MIT license
+
+//! Async adapters, enabled by the `async` feature.
+//!
+//! `HackRFDevice::rx_async` and `tx_async` start a stream and hand back its
+//! guard together with an `AsyncRx` or `AsyncTx` that is `Send + 'static`,
+//! so it can be moved into a task on any executor while the guard stays with
+//! the code that owns the device. Dropping the guard stops the hardware;
+//! dropping the adapter makes the transfer callback ask libhackrf to stop.
+//!
+//! Backpressure:
+//!
+//! * RX never blocks libhackrf's transfer thread. Transfers are copied into
+//!   a fixed pool of buffers; when the consumer falls behind and all buffers
+//!   are queued or held, further transfers are dropped and counted, exactly
+//!   as for `HackRFDevice::rx_buffers`.
+//! * TX applies backpressure to the producer instead. `AsyncTx` accepts at
+//!   most `capacity` queued buffers, after which `poll_ready` is pending
+//!   until the transfer thread has taken one. If the queue runs dry the
+//!   transfer is padded with zero samples and counted as an underrun. Flushing
+//!   completes once the queue has room again, not when the data has been
+//!   transmitted. Closing the sink lets the queue drain and then ends the
+//!   stream.
+//!
+//! `rx_channel` and `tx_channel` expose the two halves directly, for use
+//! with `HackRFDevice::rx` and `tx` or for driving an adapter without
+//! hardware.
+
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+
+use futures::channel::mpsc;
+use futures::{Sink, Stream};
+
+use crate::buffer::{BufferPool, SampleBuffer};
+use crate::{HackRFDevice, HackRFError, RxStream, TxStream, IQ8};
+
+/// The transfer-thread half of an RX channel.
+pub struct RxSender {
+    pool: Arc<BufferPool>
+}
+
+/// A `Stream` of received sample buffers.
+pub struct AsyncRx {
+    pool: Arc<BufferPool>
+}
+
+/// Create an RX channel backed by a pool of `count` reusable buffers.
+pub fn rx_channel(count: usize) -> (RxSender, AsyncRx) {
+    let pool = Arc::new(BufferPool::new(count));
+    (RxSender { pool: pool.clone() }, AsyncRx { pool })
+}
+
+impl RxSender {
+    /// Copy one transfer into the pool, dropping it if no buffer is free.
+    /// Returns false once the `AsyncRx` has been dropped.
+    pub fn push(&mut self, samples: &[IQ8]) -> bool {
+        self.pool.push(IQ8::as_bytes(samples))
+    }
+}
+
+impl Drop for RxSender {
+    fn drop(&mut self) {
+        self.pool.finish();
+    }
+}
+
+impl AsyncRx {
+    /// Total number of transfers dropped so far because the consumer did
+    /// not keep up.
+    pub fn dropped(&self) -> u64 {
+        self.pool.dropped()
+    }
+}
+
+impl Stream for AsyncRx {
+    type Item = SampleBuffer;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>)
+                 -> Poll<Option<SampleBuffer>> {
+        BufferPool::poll_pop(&self.pool, cx)
+    }
+}
+
+impl Drop for AsyncRx {
+    fn drop(&mut self) {
+        self.pool.finish();
+    }
+}
+
+/// The transfer-thread half of a TX channel.
+pub struct TxSource {
+    queue: Arc<Mutex<Option<mpsc::Receiver<Vec<IQ8>>>>>,
+    pending: Vec<IQ8>,
+    offset: usize,
+    underruns: Arc<AtomicU64>
+}
+
+/// A `Sink` of sample buffers to transmit.
+pub struct AsyncTx {
+    queue: mpsc::Sender<Vec<IQ8>>,
+    underruns: Arc<AtomicU64>
+}
+
+/// Create a TX channel which queues up to `capacity` buffers.
+pub fn tx_channel(capacity: usize) -> (AsyncTx, TxSource) {
+    // The mpsc channel allows one extra message per sender.
+    let (sender, receiver) = mpsc::channel(capacity.saturating_sub(1));
+    let underruns = Arc::new(AtomicU64::new(0));
+    (AsyncTx { queue: sender, underruns: underruns.clone() },
+     TxSource { queue: Arc::new(Mutex::new(Some(receiver))),
+                pending: Vec::new(), offset: 0, underruns })
+}
+
+impl TxSource {
+    /// Fill one transfer from the queue. Pads with zeros and counts an
+    /// underrun if the queue is empty. Returns false once the `AsyncTx` has
+    /// been closed or dropped and everything queued has been sent.
+    pub fn fill(&mut self, buffer: &mut [IQ8]) -> bool {
+        let mut queue = self.queue.lock().unwrap();
+        let mut filled = 0;
+        while filled < buffer.len() {
+            if self.offset == self.pending.len() {
+                let next = match *queue {
+                    Some(ref mut queue) => queue.try_recv(),
+                    None => Err(mpsc::TryRecvError::Closed)
+                };
+                match next {
+                    Ok(next) => {
+                        self.pending = next;
+                        self.offset = 0;
+                        continue;
+                    },
+                    Err(mpsc::TryRecvError::Closed) => {
+                        buffer[filled..].fill(IQ8::default());
+                        return filled > 0;
+                    },
+                    Err(mpsc::TryRecvError::Empty) => {
+                        buffer[filled..].fill(IQ8::default());
+                        self.underruns.fetch_add(1, Ordering::Relaxed);
+                        return true;
+                    }
+                }
+            }
+            let n = (self.pending.len() - self.offset)
+                        .min(buffer.len() - filled);
+            buffer[filled..filled + n].copy_from_slice(
+                &self.pending[self.offset..self.offset + n]);
+            self.offset += n;
+            filled += n;
+        }
+        true
+    }
+
+    /// A closure which closes the queue, as dropping this would, so the
+    /// `AsyncTx` sees the stream end.
+    fn closer(&self) -> impl FnOnce() + Send + 'static {
+        let queue = self.queue.clone();
+        move || drop(queue.lock().unwrap().take())
+    }
+}
+
+impl AsyncTx {
+    /// Number of transfers which had to be padded with zeros because the
+    /// queue was empty.
+    pub fn underruns(&self) -> u64 {
+        self.underruns.load(Ordering::Relaxed)
+    }
+}
+
+fn send_error(_: mpsc::SendError) -> HackRFError {
+    HackRFError::StreamingStopped
+}
+
+impl Sink<Vec<IQ8>> for AsyncTx {
+    type Error = HackRFError;
+
+    fn poll_ready(mut self: Pin<&mut Self>, cx: &mut Context<'_>)
+                  -> Poll<Result<(), HackRFError>> {
+        self.queue.poll_ready(cx).map_err(send_error)
+    }
+
+    fn start_send(mut self: Pin<&mut Self>, item: Vec<IQ8>)
+                  -> Result<(), HackRFError> {
+        self.queue.start_send(item).map_err(send_error)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>)
+                  -> Poll<Result<(), HackRFError>> {
+        Pin::new(&mut self.queue).poll_flush(cx).map_err(send_error)
+    }
+
+    fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context<'_>)
+                  -> Poll<Result<(), HackRFError>> {
+        Pin::new(&mut self.queue).poll_close(cx).map_err(send_error)
+    }
+}
+
+impl HackRFDevice {
+    /// Begin receiving into a pool of `count` buffers, returning the stream
+    /// guard and a `futures::Stream` of the received buffers.
+    pub fn rx_async(&mut self, count: usize)
+                    -> Result<(RxStream<'_>, AsyncRx), HackRFError> {
+        if count == 0 {
+            return Err(HackRFError::InvalidParam);
+        }
+        let (mut sender, receiver) = rx_channel(count);
+        let pool = receiver.pool.clone();
+        let stream = self.rx(move |buffer: &[IQ8]| sender.push(buffer))?
+            .on_stop(move || pool.finish());
+        Ok((stream, receiver))
+    }
+
+    /// Begin transmitting from a queue of up to `capacity` buffers,
+    /// returning the stream guard and a `futures::Sink` to send them to.
+    pub fn tx_async(&mut self, capacity: usize)
+                    -> Result<(TxStream<'_>, AsyncTx), HackRFError> {
+        if capacity == 0 {
+            return Err(HackRFError::InvalidParam);
+        }
+        let (sender, mut source) = tx_channel(capacity);
+        let close = source.closer();
+        let stream = self.tx(move |buffer: &mut [IQ8]| source.fill(buffer))?
+            .on_stop(close);
+        Ok((stream, sender))
+    }
+}
@@ -0,0 +1,727 @@
+// Device backends: the libhackrf implementation behind HackRFDevice
+// Copyright Adam Greig <adam@adamgreig.com> 2014
+// Licensed under MIT license
+
+use std::ffi::CStr;
+use std::panic::{self, AssertUnwindSafe};
+
+use crate::operacake::{OperaCakeDwell, OperaCakeMode, OperaCakePort,
+                       OperaCakeRange};
+use crate::sweep::{SweepConfig, SweepStyle};
+use crate::{ffi, HackRFError, RFPathFilter, IQ8};
+
+/// Closure called with each received transfer. Returns false to stop.
+pub type RxCallback = Box<dyn FnMut(&[IQ8]) -> bool + Send>;
+/// Closure called to fill each transfer to send. Returns false to stop.
+pub type TxCallback = Box<dyn FnMut(&mut [IQ8]) -> bool + Send>;
+
+/// The calls `HackRFDevice` makes on a radio, one per libhackrf device
+/// function. `LibHackRF` drives a real board and `SimulatedHackRF` runs
+/// without hardware; any other implementation can be wrapped with
+/// `HackRFDevice::from_backend`.
+///
+/// Errors should be those libhackrf would report for the same call.
+/// The backend owns the closure given to `start_rx` or `start_tx` and must
+/// not drop it until its transfer thread has stopped calling it. Starting a
+/// stream while one is already set up returns `HackRFError::Busy`.
+/// Functions missing from older libhackrf versions have default
+/// implementations returning `HackRFError::Unsupported`.
+pub trait Backend {
+    /// Close the device, stopping any stream. Later calls may fail.
+    fn close(&mut self) -> Result<(), HackRFError>;
+
+    fn start_rx(&mut self, callback: RxCallback) -> Result<(), HackRFError>;
+    fn stop_rx(&mut self) -> Result<(), HackRFError>;
+    fn start_tx(&mut self, callback: TxCallback) -> Result<(), HackRFError>;
+    fn stop_tx(&mut self) -> Result<(), HackRFError>;
+
+    /// True while streaming, false once the stream has stopped, and an
+    /// error if it failed or was never started.
+    fn is_streaming(&mut self) -> Result<bool, HackRFError>;
+
+    fn set_baseband_filter_bandwidth(&mut self, bandwidth_hz: u32)
+                                     -> Result<(), HackRFError>;
+
+    /// The numeric board ID, one of the `BOARD_ID_*` constants.
+    fn board_id_read(&mut self) -> Result<u8, HackRFError>;
+    fn version_string_read(&mut self) -> Result<String, HackRFError>;
+    fn board_partid_serialno_read(&mut self)
+                                  -> Result<([u32; 2], [u32; 4]),
+                                            HackRFError>;
+
+    fn set_freq(&mut self, freq_hz: u64) -> Result<(), HackRFError>;
+    fn set_freq_explicit(&mut self, if_freq_hz: u64, lo_freq_hz: u64,
+                         path: RFPathFilter) -> Result<(), HackRFError>;
+    fn set_sample_rate_manual(&mut self, freq_hz: u32, divider: u32)
+                              -> Result<(), HackRFError>;
+    fn set_sample_rate(&mut self, freq_hz: f64) -> Result<(), HackRFError>;
+    fn set_amp_enable(&mut self, on: bool) -> Result<(), HackRFError>;
+    fn set_lna_gain(&mut self, gain: u32) -> Result<(), HackRFError>;
+    fn set_vga_gain(&mut self, gain: u32) -> Result<(), HackRFError>;
+    fn set_txvga_gain(&mut self, gain: u32) -> Result<(), HackRFError>;
+    fn set_antenna_enable(&mut self, on: bool) -> Result<(), HackRFError>;
+
+    /// The USB API version, as binary-coded decimal such as 0x0108.
+    fn usb_api_version_read(&mut self) -> Result<u16, HackRFError> {
+        Err(HackRFError::Unsupported)
+    }
+
+    /// The numeric hardware revision, as `hackrf_board_rev_read` reports.
+    fn board_rev_read(&mut self) -> Result<u8, HackRFError> {
+        Err(HackRFError::Unsupported)
+    }
+
+    fn set_clkout_enable(&mut self, _on: bool) -> Result<(), HackRFError> {
+        Err(HackRFError::Unsupported)
+    }
+
+    /// True if a clock is detected on CLKIN.
+    fn clkin_status(&mut self) -> Result<bool, HackRFError> {
+        Err(HackRFError::Unsupported)
+    }
+
+    fn set_hw_sync_mode(&mut self, _on: bool) -> Result<(), HackRFError> {
+        Err(HackRFError::Unsupported)
+    }
+
+    /// The I2C addresses of the attached Opera Cakes.
+    fn operacake_boards(&mut self) -> Result<Vec<u8>, HackRFError> {
+        Err(HackRFError::Unsupported)
+    }
+
+    /// Switch an Opera Cake's ports, already checked to be on opposite
+    /// sides.
+    fn set_operacake_ports(&mut self, _address: u8, _port_a: OperaCakePort,
+                           _port_b: OperaCakePort) -> Result<(), HackRFError> {
+        Err(HackRFError::Unsupported)
+    }
+
+    fn operacake_mode(&mut self, _address: u8)
+                      -> Result<OperaCakeMode, HackRFError> {
+        Err(HackRFError::Unsupported)
+    }
+
+    fn set_operacake_mode(&mut self, _address: u8, _mode: OperaCakeMode)
+                          -> Result<(), HackRFError> {
+        Err(HackRFError::Unsupported)
+    }
+
+    /// Set the Opera Cake frequency ranges, already validated.
+    fn set_operacake_freq_ranges(&mut self, _ranges: &[OperaCakeRange])
+                                 -> Result<(), HackRFError> {
+        Err(HackRFError::Unsupported)
+    }
+
+    /// Set the Opera Cake dwell times, already validated.
+    fn set_operacake_dwell_times(&mut self, _dwells: &[OperaCakeDwell])
+                                 -> Result<(), HackRFError> {
+        Err(HackRFError::Unsupported)
+    }
+
+    /// Read a MAX2837 register, already checked to be in range.
+    fn max2837_read(&mut self, _register: u8) -> Result<u16, HackRFError> {
+        Err(HackRFError::Unsupported)
+    }
+
+    /// Write a MAX2837 register. `register` and `value` are already
+    /// checked to be in range.
+    fn max2837_write(&mut self, _register: u8, _value: u16)
+                     -> Result<(), HackRFError> {
+        Err(HackRFError::Unsupported)
+    }
+
+    fn si5351c_read(&mut self, _register: u8) -> Result<u8, HackRFError> {
+        Err(HackRFError::Unsupported)
+    }
+
+    fn si5351c_write(&mut self, _register: u8, _value: u8)
+                     -> Result<(), HackRFError> {
+        Err(HackRFError::Unsupported)
+    }
+
+    /// Read an RFFC5071 register, already checked to be in range.
+    fn rffc5071_read(&mut self, _register: u8) -> Result<u16, HackRFError> {
+        Err(HackRFError::Unsupported)
+    }
+
+    /// Write an RFFC5071 register, already checked to be in range.
+    fn rffc5071_write(&mut self, _register: u8, _value: u16)
+                      -> Result<(), HackRFError> {
+        Err(HackRFError::Unsupported)
+    }
+
+    /// Erase the whole SPI flash.
+    fn spiflash_erase(&mut self) -> Result<(), HackRFError> {
+        Err(HackRFError::Unsupported)
+    }
+
+    /// Write `data`, at most one page, to SPI flash at `address`. The range
+    /// is already checked to be within the flash.
+    fn spiflash_write(&mut self, _address: u32, _data: &[u8])
+                      -> Result<(), HackRFError> {
+        Err(HackRFError::Unsupported)
+    }
+
+    /// Fill `buffer`, at most one page, from SPI flash at `address`. The
+    /// range is already checked to be within the flash.
+    fn spiflash_read(&mut self, _address: u32, _buffer: &mut [u8])
+                     -> Result<(), HackRFError> {
+        Err(HackRFError::Unsupported)
+    }
+
+    /// Program the CPLD with `xsvf`, which has already been parsed, calling
+    /// `progress` with the bytes sent so far whenever it can. libhackrf
+    /// only allows this once, after the whole file is sent.
+    fn cpld_write(&mut self, _xsvf: &[u8], _progress: &mut dyn FnMut(usize))
+                  -> Result<(), HackRFError> {
+        Err(HackRFError::Unsupported)
+    }
+
+    /// Configure sweep mode. `config` has already been validated.
+    fn init_sweep(&mut self, _config: &SweepConfig)
+                  -> Result<(), HackRFError> {
+        Err(HackRFError::Unsupported)
+    }
+
+    /// Begin receiving in sweep mode, as set up by `init_sweep`. Each
+    /// transfer passed to `callback` is a whole number of sweep blocks.
+    fn start_rx_sweep(&mut self, _callback: RxCallback)
+                      -> Result<(), HackRFError> {
+        Err(HackRFError::Unsupported)
+    }
+}
+
+/// The closure for the current or last RX or TX stream. libhackrf's transfer
+/// thread holds a raw pointer to it, so it is kept as a raw pointer here too.
+/// Older libhackrf versions return from `hackrf_stop_*` before the transfer
+/// thread has finished with it, so it is only freed when the device is
+/// closed or the next stream replaces it.
+enum StreamCallback {
+    Rx(*mut RxCallback),
+    Tx(*mut TxCallback)
+}
+
+impl StreamCallback {
+    fn rx(callback: RxCallback) -> StreamCallback {
+        StreamCallback::Rx(Box::into_raw(Box::new(callback)))
+    }
+
+    fn tx(callback: TxCallback) -> StreamCallback {
+        StreamCallback::Tx(Box::into_raw(Box::new(callback)))
+    }
+
+    /// The context pointer `rx_cb` or `tx_cb` expects.
+    fn ctx(&self) -> *mut libc::c_void {
+        match *self {
+            StreamCallback::Rx(ptr) => ptr as *mut libc::c_void,
+            StreamCallback::Tx(ptr) => ptr as *mut libc::c_void
+        }
+    }
+}
+
+impl Drop for StreamCallback {
+    fn drop(&mut self) {
+        unsafe {
+            match *self {
+                StreamCallback::Rx(ptr) => drop(Box::from_raw(ptr)),
+                StreamCallback::Tx(ptr) => drop(Box::from_raw(ptr))
+            }
+        }
+    }
+}
+
+/// The library defines the C callback, which will itself call a closure
+/// inside Rust after resolving memory stuff, so that users don't need to
+/// write unsafe code. A panicking closure stops the stream rather than
+/// unwinding into libhackrf.
+extern "C" fn rx_cb(transfer: *mut ffi::hackrf_transfer) -> libc::c_int {
+    let data = unsafe { &*transfer };
+    let valid_length = data.valid_length as usize;
+    let buffer: &[IQ8] = IQ8::from_bytes(unsafe {
+        std::slice::from_raw_parts(data.buffer, valid_length)
+    });
+    let cb: &mut RxCallback = unsafe { &mut *(data.rx_ctx as *mut RxCallback) };
+    match panic::catch_unwind(AssertUnwindSafe(|| cb(buffer))) {
+        Ok(true) => 0,
+        _ => 1
+    }
+}
+
+/// The library defines the C callback, which will itself call a closure
+/// inside Rust after resolving memory stuff, so that users don't need to
+/// write unsafe code. A panicking closure stops the stream rather than
+/// unwinding into libhackrf.
+extern "C" fn tx_cb(transfer: *mut ffi::hackrf_transfer) -> libc::c_int {
+    let data = unsafe { &*transfer };
+    let buffer_length = data.buffer_length as usize;
+    let buffer: &mut [IQ8] = IQ8::from_bytes_mut(unsafe {
+        std::slice::from_raw_parts_mut(data.buffer, buffer_length)
+    });
+    let cb: &mut TxCallback = unsafe { &mut *(data.tx_ctx as *mut TxCallback) };
+    match panic::catch_unwind(AssertUnwindSafe(|| cb(buffer))) {
+        Ok(true) => 0,
+        _ => 1
+    }
+}
+
+/// A HackRF opened through libhackrf.
+pub struct LibHackRF {
+    ptr: *mut ffi::hackrf_device,
+    callback: Option<StreamCallback>,
+    /// True from starting a stream until it is stopped.
+    streaming: bool
+}
+
+impl Drop for LibHackRF {
+    #[inline(never)]
+    fn drop(&mut self) {
+        // Closing the device stops the transfer thread, after which it is
+        // safe for `callback` to be dropped along with the rest of `self`.
+        if !self.ptr.is_null() {
+            unsafe {
+                ffi::hackrf_close(self.ptr);
+            }
+        }
+    }
+}
+
+impl LibHackRF {
+    pub(crate) fn from_ptr(ptr: *mut ffi::hackrf_device) -> LibHackRF {
+        LibHackRF { ptr, callback: None, streaming: false }
+    }
+
+    /// Hand `callback` to libhackrf through `start`, which is given the
+    /// context pointer to register alongside `rx_cb` or `tx_cb`. If the
+    /// stream does not start, the callback is freed straight away.
+    fn start_with<F>(&mut self, callback: StreamCallback, start: F)
+                     -> Result<(), HackRFError>
+        where F: FnOnce(*mut libc::c_void) -> libc::c_int
+    {
+        if self.streaming {
+            return Err(HackRFError::Busy);
+        }
+        match start(callback.ctx()) {
+            ffi::HACKRF_SUCCESS => {
+                self.callback = Some(callback);
+                self.streaming = true;
+                Ok(())
+            },
+            err => Err(HackRFError::from_code(err))
+        }
+    }
+}
+
+impl Backend for LibHackRF {
+    fn close(&mut self) -> Result<(), HackRFError> {
+        if self.ptr.is_null() {
+            return Ok(());
+        }
+        let result = unsafe { ffi::hackrf_close(self.ptr) };
+        self.ptr = std::ptr::null_mut();
+        self.callback = None;
+        self.streaming = false;
+        match result {
+            ffi::HACKRF_SUCCESS => Ok(()),
+            err => Err(HackRFError::from_code(err))
+        }
+    }
+
+    fn start_rx(&mut self, callback: RxCallback) -> Result<(), HackRFError> {
+        let device = self.ptr;
+        self.start_with(StreamCallback::rx(callback), |ctx| unsafe {
+            ffi::hackrf_start_rx(device, rx_cb, ctx)
+        })
+    }
+
+    fn stop_rx(&mut self) -> Result<(), HackRFError> {
+        match unsafe { ffi::hackrf_stop_rx(self.ptr) } {
+            ffi::HACKRF_SUCCESS => {
+                self.streaming = false;
+                Ok(())
+            },
+            err => Err(HackRFError::from_code(err))
+        }
+    }
+
+    fn start_tx(&mut self, callback: TxCallback) -> Result<(), HackRFError> {
+        let device = self.ptr;
+        self.start_with(StreamCallback::tx(callback), |ctx| unsafe {
+            ffi::hackrf_start_tx(device, tx_cb, ctx)
+        })
+    }
+
+    fn stop_tx(&mut self) -> Result<(), HackRFError> {
+        match unsafe { ffi::hackrf_stop_tx(self.ptr) } {
+            ffi::HACKRF_SUCCESS => {
+                self.streaming = false;
+                Ok(())
+            },
+            err => Err(HackRFError::from_code(err))
+        }
+    }
+
+    fn is_streaming(&mut self) -> Result<bool, HackRFError> {
+        match unsafe { ffi::hackrf_is_streaming(self.ptr) } {
+            ffi::HACKRF_TRUE => Ok(true),
+            ffi::HACKRF_ERROR_STREAMING_STOPPED |
+            ffi::HACKRF_ERROR_STREAMING_EXIT_CALLED => Ok(false),
+            err => Err(HackRFError::from_code(err))
+        }
+    }
+
+    fn set_baseband_filter_bandwidth(&mut self, bandwidth_hz: u32)
+                                     -> Result<(), HackRFError> {
+        match unsafe { ffi::hackrf_set_baseband_filter_bandwidth(
+                                self.ptr, bandwidth_hz) } {
+            ffi::HACKRF_SUCCESS => Ok(()),
+            err => Err(HackRFError::from_code(err))
+        }
+    }
+
+    fn board_id_read(&mut self) -> Result<u8, HackRFError> {
+        let mut id: u8 = ffi::BOARD_ID_INVALID;
+        match unsafe { ffi::hackrf_board_id_read(self.ptr, &mut id) } {
+            ffi::HACKRF_SUCCESS => Ok(id),
+            err => Err(HackRFError::from_code(err))
+        }
+    }
+
+    fn version_string_read(&mut self) -> Result<String, HackRFError> {
+        let mut buf = [0 as libc::c_char; 128];
+        match unsafe { ffi::hackrf_version_string_read(self.ptr,
+                                                       buf.as_mut_ptr(),
+                                                       127) } {
+            ffi::HACKRF_SUCCESS => {
+                let s = unsafe { CStr::from_ptr(buf.as_ptr()) };
+                Ok(s.to_string_lossy().into_owned())
+            },
+            err => Err(HackRFError::from_code(err))
+        }
+    }
+
+    fn board_partid_serialno_read(&mut self)
+                                  -> Result<([u32; 2], [u32; 4]),
+                                            HackRFError> {
+        let mut serial = ffi::read_partid_serialno_t {
+            part_id: [0; 2], serial_no: [0; 4] };
+        match unsafe { ffi::hackrf_board_partid_serialno_read(self.ptr,
+                                                              &mut serial) } {
+            ffi::HACKRF_SUCCESS => Ok((serial.part_id, serial.serial_no)),
+            err => Err(HackRFError::from_code(err))
+        }
+    }
+
+    fn usb_api_version_read(&mut self) -> Result<u16, HackRFError> {
+        let mut version: u16 = 0;
+        match unsafe { ffi::hackrf_usb_api_version_read(self.ptr,
+                                                        &mut version) } {
+            ffi::HACKRF_SUCCESS => Ok(version),
+            err => Err(HackRFError::from_code(err))
+        }
+    }
+
+    fn board_rev_read(&mut self) -> Result<u8, HackRFError> {
+        let mut rev: u8 = ffi::BOARD_REV_UNDETECTED;
+        match unsafe { ffi::hackrf_board_rev_read(self.ptr, &mut rev) } {
+            ffi::HACKRF_SUCCESS => Ok(rev),
+            err => Err(HackRFError::from_code(err))
+        }
+    }
+
+    fn set_clkout_enable(&mut self, on: bool) -> Result<(), HackRFError> {
+        match unsafe { ffi::hackrf_set_clkout_enable(self.ptr, on as u8) } {
+            ffi::HACKRF_SUCCESS => Ok(()),
+            err => Err(HackRFError::from_code(err))
+        }
+    }
+
+    fn clkin_status(&mut self) -> Result<bool, HackRFError> {
+        let mut status: u8 = 0;
+        match unsafe { ffi::hackrf_get_clkin_status(self.ptr, &mut status) } {
+            ffi::HACKRF_SUCCESS => Ok(status != 0),
+            err => Err(HackRFError::from_code(err))
+        }
+    }
+
+    fn set_hw_sync_mode(&mut self, on: bool) -> Result<(), HackRFError> {
+        match unsafe { ffi::hackrf_set_hw_sync_mode(self.ptr, on as u8) } {
+            ffi::HACKRF_SUCCESS => Ok(()),
+            err => Err(HackRFError::from_code(err))
+        }
+    }
+
+    fn operacake_boards(&mut self) -> Result<Vec<u8>, HackRFError> {
+        let mut boards = [ffi::HACKRF_OPERACAKE_ADDRESS_INVALID;
+                          ffi::HACKRF_OPERACAKE_MAX_BOARDS];
+        let ptr = boards.as_mut_ptr();
+        match unsafe { ffi::hackrf_get_operacake_boards(self.ptr, ptr) } {
+            ffi::HACKRF_SUCCESS => Ok(boards.iter()
+                .take_while(|&&b| b != ffi::HACKRF_OPERACAKE_ADDRESS_INVALID)
+                .copied().collect()),
+            err => Err(HackRFError::from_code(err))
+        }
+    }
+
+    fn set_operacake_ports(&mut self, address: u8, port_a: OperaCakePort,
+                           port_b: OperaCakePort) -> Result<(), HackRFError> {
+        match unsafe { ffi::hackrf_set_operacake_ports(self.ptr, address,
+                                                       port_a.into(),
+                                                       port_b.into()) } {
+            ffi::HACKRF_SUCCESS => Ok(()),
+            err => Err(HackRFError::from_code(err))
+        }
+    }
+
+    fn operacake_mode(&mut self, address: u8)
+                      -> Result<OperaCakeMode, HackRFError> {
+        let mut mode = ffi::OPERACAKE_MODE_MANUAL;
+        match unsafe { ffi::hackrf_get_operacake_mode(self.ptr, address,
+                                                      &mut mode) } {
+            ffi::HACKRF_SUCCESS => match mode {
+                ffi::OPERACAKE_MODE_MANUAL => Ok(OperaCakeMode::Manual),
+                ffi::OPERACAKE_MODE_FREQUENCY => Ok(OperaCakeMode::Frequency),
+                ffi::OPERACAKE_MODE_TIME => Ok(OperaCakeMode::Time),
+                _ => Err(HackRFError::Other)
+            },
+            err => Err(HackRFError::from_code(err))
+        }
+    }
+
+    fn set_operacake_mode(&mut self, address: u8, mode: OperaCakeMode)
+                          -> Result<(), HackRFError> {
+        let c_mode = match mode {
+            OperaCakeMode::Manual => ffi::OPERACAKE_MODE_MANUAL,
+            OperaCakeMode::Frequency => ffi::OPERACAKE_MODE_FREQUENCY,
+            OperaCakeMode::Time => ffi::OPERACAKE_MODE_TIME
+        };
+        match unsafe { ffi::hackrf_set_operacake_mode(self.ptr, address,
+                                                      c_mode) } {
+            ffi::HACKRF_SUCCESS => Ok(()),
+            err => Err(HackRFError::from_code(err))
+        }
+    }
+
+    fn set_operacake_freq_ranges(&mut self, ranges: &[OperaCakeRange])
+                                 -> Result<(), HackRFError> {
+        let mut c_ranges: Vec<_> = ranges.iter()
+            .map(|r| ffi::hackrf_operacake_freq_range {
+                freq_min: r.start_mhz,
+                freq_max: r.stop_mhz,
+                port: r.port.into()
+            }).collect();
+        match unsafe { ffi::hackrf_set_operacake_freq_ranges(
+                           self.ptr, c_ranges.as_mut_ptr(),
+                           c_ranges.len() as u8) } {
+            ffi::HACKRF_SUCCESS => Ok(()),
+            err => Err(HackRFError::from_code(err))
+        }
+    }
+
+    fn set_operacake_dwell_times(&mut self, dwells: &[OperaCakeDwell])
+                                 -> Result<(), HackRFError> {
+        let mut c_dwells: Vec<_> = dwells.iter()
+            .map(|d| ffi::hackrf_operacake_dwell_time {
+                dwell: d.samples,
+                port: d.port.into()
+            }).collect();
+        match unsafe { ffi::hackrf_set_operacake_dwell_times(
+                           self.ptr, c_dwells.as_mut_ptr(),
+                           c_dwells.len() as u8) } {
+            ffi::HACKRF_SUCCESS => Ok(()),
+            err => Err(HackRFError::from_code(err))
+        }
+    }
+
+    fn set_freq(&mut self, freq_hz: u64) -> Result<(), HackRFError> {
+        match unsafe { ffi::hackrf_set_freq(self.ptr, freq_hz) } {
+            ffi::HACKRF_SUCCESS => Ok(()),
+            err => Err(HackRFError::from_code(err))
+        }
+    }
+
+    fn set_freq_explicit(&mut self, if_freq_hz: u64, lo_freq_hz: u64,
+                         path: RFPathFilter) -> Result<(), HackRFError> {
+        let c_path = match path {
+            RFPathFilter::Bypass => ffi::RF_PATH_FILTER_BYPASS,
+            RFPathFilter::LowPass => ffi::RF_PATH_FILTER_LOW_PASS,
+            RFPathFilter::HighPass => ffi::RF_PATH_FILTER_HIGH_PASS
+        };
+        match unsafe { ffi::hackrf_set_freq_explicit(self.ptr, if_freq_hz,
+                                                     lo_freq_hz, c_path) } {
+            ffi::HACKRF_SUCCESS => Ok(()),
+            err => Err(HackRFError::from_code(err))
+        }
+    }
+
+    fn set_sample_rate_manual(&mut self, freq_hz: u32, divider: u32)
+                              -> Result<(), HackRFError> {
+        match unsafe { ffi::hackrf_set_sample_rate_manual(self.ptr, freq_hz,
+                                                          divider) } {
+            ffi::HACKRF_SUCCESS => Ok(()),
+            err => Err(HackRFError::from_code(err))
+        }
+    }
+
+    fn set_sample_rate(&mut self, freq_hz: f64) -> Result<(), HackRFError> {
+        match unsafe { ffi::hackrf_set_sample_rate(self.ptr, freq_hz) } {
+            ffi::HACKRF_SUCCESS => Ok(()),
+            err => Err(HackRFError::from_code(err))
+        }
+    }
+
+    fn set_amp_enable(&mut self, on: bool) -> Result<(), HackRFError> {
+        let value = match on { false => 0u8, true => 1 };
+        match unsafe { ffi::hackrf_set_amp_enable(self.ptr, value) } {
+            ffi::HACKRF_SUCCESS => Ok(()),
+            err => Err(HackRFError::from_code(err))
+        }
+    }
+
+    fn set_lna_gain(&mut self, gain: u32) -> Result<(), HackRFError> {
+        match unsafe { ffi::hackrf_set_lna_gain(self.ptr, gain) } {
+            ffi::HACKRF_SUCCESS => Ok(()),
+            err => Err(HackRFError::from_code(err))
+        }
+    }
+
+    fn set_vga_gain(&mut self, gain: u32) -> Result<(), HackRFError> {
+        match unsafe { ffi::hackrf_set_vga_gain(self.ptr, gain) } {
+            ffi::HACKRF_SUCCESS => Ok(()),
+            err => Err(HackRFError::from_code(err))
+        }
+    }
+
+    fn set_txvga_gain(&mut self, gain: u32) -> Result<(), HackRFError> {
+        match unsafe { ffi::hackrf_set_txvga_gain(self.ptr, gain) } {
+            ffi::HACKRF_SUCCESS => Ok(()),
+            err => Err(HackRFError::from_code(err))
+        }
+    }
+
+    fn set_antenna_enable(&mut self, on: bool) -> Result<(), HackRFError> {
+        let value = match on { false => 0u8, true => 1 };
+        match unsafe { ffi::hackrf_set_antenna_enable(self.ptr, value) } {
+            ffi::HACKRF_SUCCESS => Ok(()),
+            err => Err(HackRFError::from_code(err))
+        }
+    }
+
+    fn max2837_read(&mut self, register: u8) -> Result<u16, HackRFError> {
+        let mut value: u16 = 0;
+        match unsafe { ffi::hackrf_max2837_read(self.ptr, register,
+                                                &mut value) } {
+            ffi::HACKRF_SUCCESS => Ok(value),
+            err => Err(HackRFError::from_code(err))
+        }
+    }
+
+    fn max2837_write(&mut self, register: u8, value: u16)
+                     -> Result<(), HackRFError> {
+        match unsafe { ffi::hackrf_max2837_write(self.ptr, register,
+                                                 value) } {
+            ffi::HACKRF_SUCCESS => Ok(()),
+            err => Err(HackRFError::from_code(err))
+        }
+    }
+
+    fn si5351c_read(&mut self, register: u8) -> Result<u8, HackRFError> {
+        let mut value: u16 = 0;
+        match unsafe { ffi::hackrf_si5351c_read(self.ptr, register as u16,
+                                                &mut value) } {
+            ffi::HACKRF_SUCCESS => Ok(value as u8),
+            err => Err(HackRFError::from_code(err))
+        }
+    }
+
+    fn si5351c_write(&mut self, register: u8, value: u8)
+                     -> Result<(), HackRFError> {
+        match unsafe { ffi::hackrf_si5351c_write(self.ptr, register as u16,
+                                                 value as u16) } {
+            ffi::HACKRF_SUCCESS => Ok(()),
+            err => Err(HackRFError::from_code(err))
+        }
+    }
+
+    fn rffc5071_read(&mut self, register: u8) -> Result<u16, HackRFError> {
+        let mut value: u16 = 0;
+        match unsafe { ffi::hackrf_rffc5071_read(self.ptr, register,
+                                                 &mut value) } {
+            ffi::HACKRF_SUCCESS => Ok(value),
+            err => Err(HackRFError::from_code(err))
+        }
+    }
+
+    fn rffc5071_write(&mut self, register: u8, value: u16)
+                      -> Result<(), HackRFError> {
+        match unsafe { ffi::hackrf_rffc5071_write(self.ptr, register,
+                                                  value) } {
+            ffi::HACKRF_SUCCESS => Ok(()),
+            err => Err(HackRFError::from_code(err))
+        }
+    }
+
+    fn spiflash_erase(&mut self) -> Result<(), HackRFError> {
+        match unsafe { ffi::hackrf_spiflash_erase(self.ptr) } {
+            ffi::HACKRF_SUCCESS => Ok(()),
+            err => Err(HackRFError::from_code(err))
+        }
+    }
+
+    fn spiflash_write(&mut self, address: u32, data: &[u8])
+                      -> Result<(), HackRFError> {
+        match unsafe { ffi::hackrf_spiflash_write(self.ptr, address,
+                                                  data.len() as u16,
+                                                  data.as_ptr()) } {
+            ffi::HACKRF_SUCCESS => Ok(()),
+            err => Err(HackRFError::from_code(err))
+        }
+    }
+
+    fn spiflash_read(&mut self, address: u32, buffer: &mut [u8])
+                     -> Result<(), HackRFError> {
+        match unsafe { ffi::hackrf_spiflash_read(self.ptr, address,
+                                                 buffer.len() as u16,
+                                                 buffer.as_mut_ptr()) } {
+            ffi::HACKRF_SUCCESS => Ok(()),
+            err => Err(HackRFError::from_code(err))
+        }
+    }
+
+    fn cpld_write(&mut self, xsvf: &[u8], progress: &mut dyn FnMut(usize))
+                  -> Result<(), HackRFError> {
+        match unsafe { ffi::hackrf_cpld_write(self.ptr, xsvf.as_ptr(),
+                                              xsvf.len() as libc::c_uint) } {
+            ffi::HACKRF_SUCCESS => {
+                progress(xsvf.len());
+                Ok(())
+            },
+            err => Err(HackRFError::from_code(err))
+        }
+    }
+
+    fn init_sweep(&mut self, config: &SweepConfig)
+                  -> Result<(), HackRFError> {
+        let frequencies: Vec<u16> = config.ranges.iter()
+            .flat_map(|&(start, stop)| [start, stop]).collect();
+        let style = match config.style {
+            SweepStyle::Linear => ffi::LINEAR,
+            SweepStyle::Interleaved => ffi::INTERLEAVED
+        };
+        match unsafe { ffi::hackrf_init_sweep(self.ptr, frequencies.as_ptr(),
+                                              config.ranges.len() as i32,
+                                              config.dwell_samples * 2,
+                                              config.step_width,
+                                              config.offset, style) } {
+            ffi::HACKRF_SUCCESS => Ok(()),
+            err => Err(HackRFError::from_code(err))
+        }
+    }
+
+    fn start_rx_sweep(&mut self, callback: RxCallback)
+                      -> Result<(), HackRFError> {
+        let device = self.ptr;
+        self.start_with(StreamCallback::rx(callback), |ctx| unsafe {
+            ffi::hackrf_start_rx_sweep(device, rx_cb, ctx)
+        })
+    }
+}
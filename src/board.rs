@@ -0,0 +1,157 @@
+// Board identification: board ID, part ID and serial number
+// Copyright Adam Greig <adam@adamgreig.com> 2014
+// Licensed under MIT license
+
+use std::fmt;
+use std::str::FromStr;
+
+use crate::list::UsbBoardId;
+use crate::{ffi, HackRFError};
+
+/// The board ID the firmware reports with `board_id_read`, one of the
+/// `BOARD_ID_*` constants in libhackrf.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum BoardId {
+    Jellybean,
+    Jawbreaker,
+    /// A HackRF One before r9.
+    HackRFOne,
+    Rad1o,
+    /// A HackRF One r9 or later.
+    HackRFOneR9,
+    Unknown(u8)
+}
+
+impl BoardId {
+    /// The USB product ID this board enumerates with, if it has its own.
+    pub fn usb_board_id(self) -> Option<UsbBoardId> {
+        match self {
+            BoardId::Jawbreaker => Some(UsbBoardId::Jawbreaker),
+            BoardId::HackRFOne | BoardId::HackRFOneR9 =>
+                Some(UsbBoardId::HackRFOne),
+            BoardId::Rad1o => Some(UsbBoardId::Rad1o),
+            BoardId::Jellybean | BoardId::Unknown(_) => None
+        }
+    }
+}
+
+impl From<u8> for BoardId {
+    fn from(id: u8) -> BoardId {
+        match id {
+            ffi::BOARD_ID_JELLYBEAN => BoardId::Jellybean,
+            ffi::BOARD_ID_JAWBREAKER => BoardId::Jawbreaker,
+            ffi::BOARD_ID_HACKRF_ONE => BoardId::HackRFOne,
+            ffi::BOARD_ID_RAD1O => BoardId::Rad1o,
+            ffi::BOARD_ID_HACKRF1_R9 => BoardId::HackRFOneR9,
+            id => BoardId::Unknown(id)
+        }
+    }
+}
+
+impl From<BoardId> for u8 {
+    fn from(id: BoardId) -> u8 {
+        match id {
+            BoardId::Jellybean => ffi::BOARD_ID_JELLYBEAN,
+            BoardId::Jawbreaker => ffi::BOARD_ID_JAWBREAKER,
+            BoardId::HackRFOne => ffi::BOARD_ID_HACKRF_ONE,
+            BoardId::Rad1o => ffi::BOARD_ID_RAD1O,
+            BoardId::HackRFOneR9 => ffi::BOARD_ID_HACKRF1_R9,
+            BoardId::Unknown(id) => id
+        }
+    }
+}
+
+/// Displays the names `hackrf_board_id_name` gives, so both HackRF One
+/// variants display as `HackRF One`.
+impl fmt::Display for BoardId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match *self {
+            BoardId::Jellybean => "Jellybean",
+            BoardId::Jawbreaker => "Jawbreaker",
+            BoardId::HackRFOne | BoardId::HackRFOneR9 => "HackRF One",
+            BoardId::Rad1o => "rad1o",
+            BoardId::Unknown(ffi::BOARD_ID_UNRECOGNIZED) => "unrecognized",
+            BoardId::Unknown(ffi::BOARD_ID_UNDETECTED) => "undetected",
+            BoardId::Unknown(_) => "unknown"
+        };
+        f.write_str(name)
+    }
+}
+
+/// Parse `s` as a hex word, with or without a `0x` prefix.
+fn parse_word(s: &str) -> Result<u32, HackRFError> {
+    let digits = s.strip_prefix("0x").unwrap_or(s);
+    if digits.is_empty() || digits.len() > 8 ||
+       !digits.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return Err(HackRFError::InvalidParam);
+    }
+    u32::from_str_radix(digits, 16).map_err(|_| HackRFError::InvalidParam)
+}
+
+/// The LPC43xx part ID, as two words. Displays and parses as `hackrf_info`
+/// prints it, e.g. `0xa000cb3c 0x00574f4b`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct PartId(pub [u32; 2]);
+
+impl fmt::Display for PartId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "0x{:08x} 0x{:08x}", self.0[0], self.0[1])
+    }
+}
+
+impl FromStr for PartId {
+    type Err = HackRFError;
+
+    /// Parse two whitespace-separated hex words.
+    /// Returns `HackRFError::InvalidParam` if `s` is not in that form.
+    fn from_str(s: &str) -> Result<PartId, HackRFError> {
+        let words = s.split_whitespace().map(parse_word)
+            .collect::<Result<Vec<u32>, _>>()?;
+        match words[..] {
+            [a, b] => Ok(PartId([a, b])),
+            _ => Err(HackRFError::InvalidParam)
+        }
+    }
+}
+
+/// The board's serial number, as four words. Displays as the 32 hex digits
+/// of the USB serial number string, which `DeviceList` reports and
+/// `HackRFDevice::open_by_serial` accepts.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct SerialNumber(pub [u32; 4]);
+
+impl fmt::Display for SerialNumber {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for word in &self.0 {
+            write!(f, "{:08x}", word)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for SerialNumber {
+    type Err = HackRFError;
+
+    /// Parse 32 hex digits, optionally prefixed with `0x`, or four
+    /// whitespace-separated hex words as older `hackrf_info` prints.
+    /// Returns `HackRFError::InvalidParam` otherwise.
+    fn from_str(s: &str) -> Result<SerialNumber, HackRFError> {
+        let s = s.trim();
+        let words = if s.contains(char::is_whitespace) {
+            s.split_whitespace().map(parse_word)
+                .collect::<Result<Vec<u32>, _>>()?
+        } else {
+            let digits = s.strip_prefix("0x").unwrap_or(s);
+            if digits.len() != 32 ||
+               !digits.bytes().all(|b| b.is_ascii_hexdigit()) {
+                return Err(HackRFError::InvalidParam);
+            }
+            (0..4).map(|i| parse_word(&digits[8 * i..8 * i + 8]))
+                .collect::<Result<Vec<u32>, _>>()?
+        };
+        match words[..] {
+            [a, b, c, d] => Ok(SerialNumber([a, b, c, d])),
+            _ => Err(HackRFError::InvalidParam)
+        }
+    }
+}
@@ -0,0 +1,205 @@
+// Bounded pool of reusable sample buffers for pull-style RX
+// Copyright Adam Greig <adam@adamgreig.com> 2014
+// Licensed under MIT license
+
+use std::collections::VecDeque;
+use std::ops::Deref;
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::task::Waker;
+#[cfg(feature = "async")]
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use crate::IQ8;
+
+/// One USB transfer worth of received samples, taken from a bounded pool.
+/// Dereferences to the samples as `[IQ8]`.
+/// The underlying allocation goes back to the pool when this is dropped, so
+/// holding on to buffers reduces how many the receiver can queue.
+pub struct SampleBuffer {
+    data: Vec<u8>,
+    sequence: u64,
+    dropped_before: u64,
+    pool: Arc<BufferPool>
+}
+
+impl SampleBuffer {
+    /// The raw received bytes.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// Index of this transfer since the stream started, counting transfers
+    /// that were dropped.
+    pub fn sequence(&self) -> u64 {
+        self.sequence
+    }
+
+    /// Number of transfers dropped immediately before this one because no
+    /// free buffer was available. Non-zero means the consumer fell behind
+    /// and there is a gap in the samples.
+    pub fn dropped_before(&self) -> u64 {
+        self.dropped_before
+    }
+}
+
+impl Deref for SampleBuffer {
+    type Target = [IQ8];
+    fn deref(&self) -> &[IQ8] {
+        IQ8::from_bytes(&self.data)
+    }
+}
+
+impl Drop for SampleBuffer {
+    fn drop(&mut self) {
+        let data = std::mem::take(&mut self.data);
+        self.pool.lock().free.push(data);
+    }
+}
+
+struct Filled {
+    data: Vec<u8>,
+    sequence: u64,
+    dropped_before: u64
+}
+
+struct PoolState {
+    free: Vec<Vec<u8>>,
+    filled: VecDeque<Filled>,
+    sequence: u64,
+    dropped: u64,
+    dropped_pending: u64,
+    finished: bool,
+    waker: Option<Waker>
+}
+
+impl PoolState {
+    fn wake(&mut self) {
+        if let Some(waker) = self.waker.take() {
+            waker.wake();
+        }
+    }
+}
+
+/// Shared between the transfer thread, which fills buffers, and the
+/// consumer, which receives them.
+pub(crate) struct BufferPool {
+    state: Mutex<PoolState>,
+    ready: Condvar
+}
+
+impl BufferPool {
+    pub(crate) fn new(count: usize) -> BufferPool {
+        BufferPool {
+            state: Mutex::new(PoolState {
+                free: (0..count).map(|_| Vec::new()).collect(),
+                filled: VecDeque::with_capacity(count),
+                sequence: 0,
+                dropped: 0,
+                dropped_pending: 0,
+                finished: false,
+                waker: None
+            }),
+            ready: Condvar::new()
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, PoolState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Called from the transfer thread for each transfer. Never blocks on
+    /// the consumer: if every buffer is in use the transfer is dropped and
+    /// counted. Returns false once the pool has been finished.
+    pub(crate) fn push(&self, samples: &[u8]) -> bool {
+        let mut state = self.lock();
+        if state.finished {
+            return false;
+        }
+        let sequence = state.sequence;
+        state.sequence += 1;
+        match state.free.pop() {
+            Some(mut data) => {
+                data.clear();
+                data.extend_from_slice(samples);
+                let dropped_before = state.dropped_pending;
+                state.dropped_pending = 0;
+                state.filled.push_back(Filled { data, sequence,
+                                                dropped_before });
+                self.ready.notify_one();
+                state.wake();
+            },
+            None => {
+                state.dropped += 1;
+                state.dropped_pending += 1;
+            }
+        }
+        true
+    }
+
+    /// Stop accepting transfers and wake any waiting consumer.
+    pub(crate) fn finish(&self) {
+        let mut state = self.lock();
+        state.finished = true;
+        self.ready.notify_all();
+        state.wake();
+    }
+
+    /// Total number of transfers dropped so far.
+    pub(crate) fn dropped(&self) -> u64 {
+        self.lock().dropped
+    }
+
+    /// Take the oldest filled buffer, waiting up to `timeout` for one.
+    /// Returns `None` on timeout or once finished with nothing queued.
+    pub(crate) fn pop(pool: &Arc<BufferPool>, timeout: Option<Duration>)
+                      -> Option<SampleBuffer> {
+        let mut state = pool.lock();
+        loop {
+            if let Some(buffer) = BufferPool::take(pool, &mut state) {
+                return Some(buffer);
+            }
+            if state.finished {
+                return None;
+            }
+            match timeout {
+                Some(timeout) => {
+                    let (s, result) = pool.ready.wait_timeout(state, timeout)
+                        .unwrap_or_else(|e| e.into_inner());
+                    state = s;
+                    if result.timed_out() && state.filled.is_empty() {
+                        return None;
+                    }
+                },
+                None => {
+                    state = pool.ready.wait(state)
+                                .unwrap_or_else(|e| e.into_inner());
+                }
+            }
+        }
+    }
+
+    /// Poll for the oldest filled buffer, registering the task to be woken
+    /// when one arrives. Ready(None) once finished with nothing queued.
+    #[cfg(feature = "async")]
+    pub(crate) fn poll_pop(pool: &Arc<BufferPool>, cx: &mut Context<'_>)
+                           -> Poll<Option<SampleBuffer>> {
+        let mut state = pool.lock();
+        if let Some(buffer) = BufferPool::take(pool, &mut state) {
+            return Poll::Ready(Some(buffer));
+        }
+        if state.finished {
+            return Poll::Ready(None);
+        }
+        state.waker = Some(cx.waker().clone());
+        Poll::Pending
+    }
+
+    fn take(pool: &Arc<BufferPool>, state: &mut PoolState)
+            -> Option<SampleBuffer> {
+        state.filled.pop_front().map(|f| SampleBuffer {
+            data: f.data, sequence: f.sequence,
+            dropped_before: f.dropped_before, pool: pool.clone()
+        })
+    }
+}
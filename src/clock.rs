@@ -0,0 +1,191 @@
+// Clock output, external clock detection and hardware trigger sync
+// Copyright Adam Greig <adam@adamgreig.com> 2014
+// Licensed under MIT license
+
+//! Several HackRFs sample coherently when one, the leader, drives its
+//! CLKOUT port into each follower's CLKIN port and its trigger output
+//! (pin 15 of P28 on a HackRF One) into each follower's trigger input
+//! (pin 16). The followers then share the leader's sample clock, and in
+//! hardware sync mode hold off streaming until the leader starts.
+
+use std::error::Error;
+use std::fmt;
+
+use crate::stream::RxStream;
+use crate::{HackRFDevice, HackRFError};
+
+/// How a board shares its clock and starts streaming.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct ClockConfig {
+    /// Drive the sample clock reference out of the CLKOUT port.
+    pub clkout: bool,
+    /// Wait for a rising edge on the trigger input before streaming.
+    pub hw_sync: bool
+}
+
+/// Why `start_synchronised_rx` did not start.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SyncError {
+    /// The follower at this index saw no clock on CLKIN, so would not
+    /// sample coherently.
+    NoClockIn(usize),
+    /// libhackrf failed while setting up or starting a board.
+    Device(HackRFError)
+}
+
+impl From<HackRFError> for SyncError {
+    fn from(err: HackRFError) -> SyncError {
+        SyncError::Device(err)
+    }
+}
+
+impl fmt::Display for SyncError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            SyncError::NoClockIn(index) =>
+                write!(f, "follower {} has no clock on CLKIN", index),
+            SyncError::Device(ref err) => err.fmt(f)
+        }
+    }
+}
+
+impl Error for SyncError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match *self {
+            SyncError::Device(ref err) => Some(err),
+            _ => None
+        }
+    }
+}
+
+impl ClockConfig {
+    /// The setup for a leader: share the clock and start immediately.
+    pub fn leader() -> ClockConfig {
+        ClockConfig { clkout: true, hw_sync: false }
+    }
+
+    /// The setup for a follower: wait for the leader's trigger.
+    pub fn follower() -> ClockConfig {
+        ClockConfig { clkout: false, hw_sync: true }
+    }
+}
+
+impl HackRFDevice {
+    /// Enable or disable the CLKOUT port.
+    /// Returns `HackRFError::Unsupported` if libhackrf is too old.
+    pub fn set_clkout_enable(&mut self, on: bool) -> Result<(), HackRFError> {
+        self.backend.set_clkout_enable(on)
+    }
+
+    /// True if a clock is detected on CLKIN, in which case the firmware
+    /// locks the Si5351C to it instead of the on-board crystal.
+    /// Returns `HackRFError::Unsupported` if libhackrf is too old.
+    pub fn clkin_status(&mut self) -> Result<bool, HackRFError> {
+        self.backend.clkin_status()
+    }
+
+    /// In hardware sync mode, the next stream started waits for a rising
+    /// edge on the trigger input before it delivers samples.
+    /// Returns `HackRFError::Unsupported` if libhackrf is too old.
+    pub fn set_hw_sync_mode(&mut self, on: bool) -> Result<(), HackRFError> {
+        self.backend.set_hw_sync_mode(on)
+    }
+
+    /// Apply `config` with `set_clkout_enable` and `set_hw_sync_mode`.
+    pub fn set_clock_config(&mut self, config: ClockConfig)
+                            -> Result<(), HackRFError> {
+        self.set_clkout_enable(config.clkout)?;
+        self.set_hw_sync_mode(config.hw_sync)
+    }
+}
+
+/// Start receiving on `leader` and every one of `followers` so they begin
+/// sampling on the same trigger edge, each into a pool of `count` buffers
+/// as with `HackRFDevice::rx_buffers`. The streams are returned leader
+/// first, then the followers in order.
+///
+/// The leader is set up with `ClockConfig::leader` and each follower with
+/// `ClockConfig::follower`. Followers are started first, so they are
+/// waiting when the leader starts and raises its trigger output.
+/// The boards stay in those modes after their streams stop, until reset
+/// with `set_clock_config(ClockConfig::default())`; `MultiRxStream` does
+/// this when it stops.
+///
+/// Returns `SyncError::NoClockIn` without starting anything if a follower
+/// sees no clock on CLKIN. If starting any board fails, the streams already
+/// started are stopped. On any error every board is left with the default
+/// `ClockConfig`.
+pub fn start_synchronised_rx<'a, I>(leader: &'a mut HackRFDevice,
+                                    followers: I, count: usize)
+                                    -> Result<Vec<RxStream<'a>>, SyncError>
+    where I: IntoIterator<Item = &'a mut HackRFDevice>
+{
+    let mut followers: Vec<_> = followers.into_iter().collect();
+    if let Err(err) = share_clock(leader, &mut followers) {
+        let _ = reset_clock(leader);
+        return Err(err);
+    }
+    let mut streams = Vec::with_capacity(followers.len() + 1);
+    for follower in followers {
+        match start_follower(follower, count) {
+            Ok(stream) => streams.push(stream),
+            Err(err) => {
+                abandon(streams);
+                let _ = reset_clock(leader);
+                return Err(err.into());
+            }
+        }
+    }
+    match leader.try_rx_buffers(count) {
+        Ok(stream) => {
+            streams.insert(0, stream);
+            Ok(streams)
+        },
+        Err((err, leader)) => {
+            abandon(streams);
+            let _ = reset_clock(leader);
+            Err(err.into())
+        }
+    }
+}
+
+/// Put `device` back to the default `ClockConfig`, neither sharing its
+/// clock nor waiting for a trigger.
+pub(crate) fn reset_clock(device: &mut HackRFDevice)
+                          -> Result<(), HackRFError> {
+    device.set_clock_config(ClockConfig::default())
+}
+
+/// Drive the leader's clock out and check every follower receives it.
+fn share_clock(leader: &mut HackRFDevice,
+               followers: &mut [&mut HackRFDevice])
+               -> Result<(), SyncError> {
+    leader.set_clock_config(ClockConfig::leader())?;
+    for (index, follower) in followers.iter_mut().enumerate() {
+        if !follower.clkin_status()? {
+            return Err(SyncError::NoClockIn(index));
+        }
+    }
+    Ok(())
+}
+
+/// Start `device` waiting for the leader's trigger, resetting its clock
+/// config if that fails.
+fn start_follower(device: &mut HackRFDevice, count: usize)
+                  -> Result<RxStream<'_>, HackRFError> {
+    let started = match device.set_clock_config(ClockConfig::follower()) {
+        Ok(()) => device.try_rx_buffers(count),
+        Err(err) => Err((err, device))
+    };
+    started.map_err(|(err, device)| {
+        let _ = reset_clock(device);
+        err
+    })
+}
+
+/// Stop the followers already started and reset their clock configs.
+fn abandon(streams: Vec<RxStream<'_>>) {
+    for stream in streams {
+        let _ = stream.stop_with(reset_clock);
+    }
+}
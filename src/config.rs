@@ -0,0 +1,135 @@
+// Radio settings applied to a device as one configuration
+// Copyright Adam Greig <adam@adamgreig.com> 2014
+// Licensed under MIT license
+
+use crate::max2837::MAX2837_FT_BANDWIDTHS_HZ;
+use crate::{HackRFDevice, HackRFError};
+
+/// Highest frequency the firmware will tune to.
+const MAX_FREQ_HZ: u64 = 7_250_000_000;
+
+/// Sample rates the HackRF's ADC and firmware support.
+const MIN_SAMPLE_RATE: f64 = 2e6;
+const MAX_SAMPLE_RATE: f64 = 20e6;
+
+/// Every setting that puts a HackRF's radio into a known state.
+///
+/// With the `serde` feature it can be stored as TOML or JSON, for example
+/// as named profiles in a map. The amplifier, antenna power and gains may
+/// be left out and default to off and zero; unknown fields are rejected.
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(deny_unknown_fields))]
+pub struct RadioConfig {
+    /// Centre frequency in Hz.
+    pub freq_hz: u64,
+    /// Sample rate in Hz.
+    pub sample_rate: f64,
+    /// Baseband filter bandwidth in Hz. The narrowest filter at least this
+    /// wide is selected.
+    pub baseband_filter_bandwidth: u32,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub amp_enable: bool,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub antenna_enable: bool,
+    /// LNA gain, 0-40dB in 8dB steps.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub lna_gain: u32,
+    /// VGA gain, 0-62dB in 2dB steps.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub vga_gain: u32,
+    /// TX VGA gain, 0-47dB in 1dB steps.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub txvga_gain: u32
+}
+
+impl RadioConfig {
+    /// Tune to `freq_hz` at `sample_rate` with the baseband filter
+    /// libhackrf sets along with that rate, every gain at zero and the
+    /// amplifier and antenna power off.
+    pub fn new(freq_hz: u64, sample_rate: f64) -> RadioConfig {
+        RadioConfig {
+            freq_hz,
+            sample_rate,
+            baseband_filter_bandwidth: default_filter(sample_rate),
+            amp_enable: false,
+            antenna_enable: false,
+            lna_gain: 0,
+            vga_gain: 0,
+            txvga_gain: 0
+        }
+    }
+
+    /// Check every setting is one the radio accepts: a frequency up to
+    /// 7.25GHz, a sample rate of 2-20Msps, a filter bandwidth between the
+    /// MAX2837's narrowest and widest filters, as any such bandwidth is
+    /// rounded to one of them, and gains within their ranges.
+    /// Returns `HackRFError::InvalidParam` if any is out of range.
+    pub fn validate(&self) -> Result<(), HackRFError> {
+        let filters = MAX2837_FT_BANDWIDTHS_HZ[0]..=
+            MAX2837_FT_BANDWIDTHS_HZ[MAX2837_FT_BANDWIDTHS_HZ.len() - 1];
+        let ok = self.freq_hz <= MAX_FREQ_HZ &&
+            (MIN_SAMPLE_RATE..=MAX_SAMPLE_RATE).contains(&self.sample_rate) &&
+            filters.contains(&self.baseband_filter_bandwidth) &&
+            self.lna_gain <= 40 && self.vga_gain <= 62 &&
+            self.txvga_gain <= 47;
+        if !ok {
+            return Err(HackRFError::InvalidParam);
+        }
+        Ok(())
+    }
+
+    /// Apply every setting to `device`, after checking them with
+    /// `validate` so an invalid configuration changes nothing.
+    ///
+    /// The amplifier and antenna power are turned off first if they are to
+    /// be off, and on last if they are to be on, so neither is powered
+    /// while the rest of the radio is part way between configurations.
+    /// In between come the sample rate, then the filter, which libhackrf
+    /// changes along with the sample rate, then the frequency and gains.
+    pub fn apply(&self, device: &mut HackRFDevice) -> Result<(), HackRFError> {
+        self.validate()?;
+        if !self.amp_enable {
+            device.set_amp_enable(false)?;
+        }
+        if !self.antenna_enable {
+            device.set_antenna_enable(false)?;
+        }
+        device.set_sample_rate(self.sample_rate)?;
+        device.set_baseband_filter_bandwidth(self.baseband_filter_bandwidth)?;
+        device.set_freq(self.freq_hz)?;
+        device.set_lna_gain(self.lna_gain)?;
+        device.set_vga_gain(self.vga_gain)?;
+        device.set_txvga_gain(self.txvga_gain)?;
+        if self.amp_enable {
+            device.set_amp_enable(true)?;
+        }
+        if self.antenna_enable {
+            device.set_antenna_enable(true)?;
+        }
+        device.config = Some(*self);
+        Ok(())
+    }
+}
+
+/// The widest filter no wider than three quarters of `sample_rate`, as
+/// `hackrf_compute_baseband_filter_bw` chooses, or the narrowest filter.
+pub(crate) fn default_filter(sample_rate: f64) -> u32 {
+    filter_at_most((sample_rate * 0.75) as u32)
+}
+
+/// The widest filter no wider than `bandwidth_hz`, or the narrowest filter.
+pub(crate) fn filter_at_most(bandwidth_hz: u32) -> u32 {
+    MAX2837_FT_BANDWIDTHS_HZ.iter().rev()
+        .find(|&&bw| bw <= bandwidth_hz)
+        .copied()
+        .unwrap_or(MAX2837_FT_BANDWIDTHS_HZ[0])
+}
+
+/// The widest filter narrower than `bandwidth_hz`, or the narrowest filter.
+pub(crate) fn filter_below(bandwidth_hz: u32) -> u32 {
+    MAX2837_FT_BANDWIDTHS_HZ.iter().rev()
+        .find(|&&bw| bw < bandwidth_hz)
+        .copied()
+        .unwrap_or(MAX2837_FT_BANDWIDTHS_HZ[0])
+}
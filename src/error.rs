@@ -0,0 +1,133 @@
+// Error type for the hackrf crate
+// Copyright Adam Greig <adam@adamgreig.com> 2014
+// Licensed under MIT license
+
+use std::fmt;
+
+use crate::ffi;
+
+/// An error returned by libhackrf, one variant per `HACKRF_ERROR_*` code,
+/// or `LibraryNotFound` if libhackrf itself could not be loaded.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum HackRFError {
+    /// Invalid parameter(s) passed to a function.
+    InvalidParam,
+    /// No HackRF could be found.
+    NotFound,
+    /// The HackRF is in use by something else.
+    Busy,
+    /// libhackrf could not allocate memory.
+    NoMem,
+    /// A libusb call failed, typically because the device was unplugged.
+    Libusb,
+    /// libhackrf could not create its transfer thread.
+    Thread,
+    /// The streaming thread encountered an error.
+    StreamingThreadErr,
+    /// Streaming has stopped.
+    StreamingStopped,
+    /// Streaming was terminated by `exit` being called.
+    StreamingExitCalled,
+    /// The installed libhackrf or HackRF firmware is too old to support the
+    /// requested function.
+    Unsupported,
+    /// An unspecified error from libhackrf.
+    Other,
+    /// libhackrf could not be loaded at runtime, with the loader's reason.
+    /// Only returned with the `dynamic-loading` feature, and reported with
+    /// code `HACKRF_ERROR_OTHER`.
+    LibraryNotFound(String),
+    /// An error code this crate does not know about.
+    Unknown(i32),
+}
+
+impl HackRFError {
+    /// Convert a libhackrf return code into an error.
+    pub fn from_code(code: i32) -> HackRFError {
+        match code {
+            ffi::HACKRF_ERROR_INVALID_PARAM => HackRFError::InvalidParam,
+            ffi::HACKRF_ERROR_NOT_FOUND => HackRFError::NotFound,
+            ffi::HACKRF_ERROR_BUSY => HackRFError::Busy,
+            ffi::HACKRF_ERROR_NO_MEM => HackRFError::NoMem,
+            ffi::HACKRF_ERROR_LIBUSB => HackRFError::Libusb,
+            ffi::HACKRF_ERROR_THREAD => HackRFError::Thread,
+            ffi::HACKRF_ERROR_STREAMING_THREAD_ERR =>
+                HackRFError::StreamingThreadErr,
+            ffi::HACKRF_ERROR_STREAMING_STOPPED =>
+                HackRFError::StreamingStopped,
+            ffi::HACKRF_ERROR_STREAMING_EXIT_CALLED =>
+                HackRFError::StreamingExitCalled,
+            ffi::HACKRF_ERROR_USB_API_VERSION => HackRFError::Unsupported,
+            ffi::HACKRF_ERROR_OTHER => HackRFError::Other,
+            code => HackRFError::Unknown(code),
+        }
+    }
+
+    /// The libhackrf error code corresponding to this error.
+    pub fn code(&self) -> i32 {
+        match *self {
+            HackRFError::InvalidParam => ffi::HACKRF_ERROR_INVALID_PARAM,
+            HackRFError::NotFound => ffi::HACKRF_ERROR_NOT_FOUND,
+            HackRFError::Busy => ffi::HACKRF_ERROR_BUSY,
+            HackRFError::NoMem => ffi::HACKRF_ERROR_NO_MEM,
+            HackRFError::Libusb => ffi::HACKRF_ERROR_LIBUSB,
+            HackRFError::Thread => ffi::HACKRF_ERROR_THREAD,
+            HackRFError::StreamingThreadErr =>
+                ffi::HACKRF_ERROR_STREAMING_THREAD_ERR,
+            HackRFError::StreamingStopped =>
+                ffi::HACKRF_ERROR_STREAMING_STOPPED,
+            HackRFError::StreamingExitCalled =>
+                ffi::HACKRF_ERROR_STREAMING_EXIT_CALLED,
+            HackRFError::Unsupported => ffi::HACKRF_ERROR_USB_API_VERSION,
+            HackRFError::Other | HackRFError::LibraryNotFound(_) =>
+                ffi::HACKRF_ERROR_OTHER,
+            HackRFError::Unknown(code) => code,
+        }
+    }
+
+    /// True if the same operation might succeed if tried again, possibly
+    /// after re-opening the device.
+    pub fn is_retryable(&self) -> bool {
+        matches!(*self, HackRFError::Busy | HackRFError::NoMem |
+                        HackRFError::Libusb | HackRFError::Thread |
+                        HackRFError::StreamingThreadErr)
+    }
+
+    /// True if the error suggests the device is missing or was unplugged.
+    /// libhackrf reports most USB failures on a removed device as `Libusb`.
+    pub fn is_disconnected(&self) -> bool {
+        matches!(*self, HackRFError::NotFound | HackRFError::Libusb)
+    }
+
+    /// True if the error was caused by a bad argument rather than by the
+    /// device or the USB stack.
+    pub fn is_invalid_param(&self) -> bool {
+        matches!(*self, HackRFError::InvalidParam)
+    }
+}
+
+impl fmt::Display for HackRFError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let desc = match *self {
+            HackRFError::InvalidParam => "invalid parameter(s)",
+            HackRFError::NotFound => "HackRF not found",
+            HackRFError::Busy => "HackRF busy",
+            HackRFError::NoMem => "insufficient memory",
+            HackRFError::Libusb => "USB error",
+            HackRFError::Thread => "transfer thread error",
+            HackRFError::StreamingThreadErr =>
+                "streaming thread encountered an error",
+            HackRFError::StreamingStopped => "streaming stopped",
+            HackRFError::StreamingExitCalled => "streaming terminated",
+            HackRFError::Unsupported =>
+                "not supported by the installed libhackrf or firmware",
+            HackRFError::Other => "unspecified error",
+            HackRFError::LibraryNotFound(ref reason) =>
+                return write!(f, "libhackrf could not be loaded: {}", reason),
+            HackRFError::Unknown(_) => "unknown error",
+        };
+        write!(f, "{} ({})", desc, self.code())
+    }
+}
+
+impl std::error::Error for HackRFError {}
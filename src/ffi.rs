@@ -2,8 +2,11 @@
 // Copyright Adam Greig <adam@adamgreig.com> 2014
 // Licensed under MIT license
 
-use libc::{c_void, c_uint, c_int, c_char, c_double,
-           uint8_t, uint32_t, uint64_t};
+use libc::{c_void, c_uint, c_int, c_char, c_double};
+#[cfg(feature = "dynamic-loading")]
+use std::sync::OnceLock;
+
+use crate::HackRFError;
 
 pub const HACKRF_SUCCESS: c_int = 0;
 pub const HACKRF_TRUE: c_int = 1;
@@ -16,115 +19,471 @@ pub const HACKRF_ERROR_THREAD: c_int = -1001;
 pub const HACKRF_ERROR_STREAMING_THREAD_ERR: c_int = -1002;
 pub const HACKRF_ERROR_STREAMING_STOPPED: c_int = -1003;
 pub const HACKRF_ERROR_STREAMING_EXIT_CALLED: c_int = -1004;
+pub const HACKRF_ERROR_USB_API_VERSION: c_int = -1005;
 pub const HACKRF_ERROR_OTHER: c_int = -9999;
 
-pub const BOARD_ID_JELLYBEAN: uint8_t = 0;
-pub const BOARD_ID_JAWBREAKER: uint8_t = 1;
-pub const BOARD_ID_HACKRF_ONE: uint8_t = 2;
-pub const BOARD_ID_INVALID: uint8_t = 0xFF;
+pub const BOARD_ID_JELLYBEAN: u8 = 0;
+pub const BOARD_ID_JAWBREAKER: u8 = 1;
+pub const BOARD_ID_HACKRF_ONE: u8 = 2;
+pub const BOARD_ID_RAD1O: u8 = 3;
+pub const BOARD_ID_HACKRF1_R9: u8 = 4;
+pub const BOARD_ID_UNRECOGNIZED: u8 = 0xFE;
+pub const BOARD_ID_UNDETECTED: u8 = 0xFF;
+pub const BOARD_ID_INVALID: u8 = 0xFF;
+
+pub const BOARD_REV_HACKRF1_OLD: u8 = 0;
+pub const BOARD_REV_HACKRF1_R6: u8 = 1;
+pub const BOARD_REV_HACKRF1_R10: u8 = 5;
+pub const BOARD_REV_GSG: u8 = 0x80;
+pub const BOARD_REV_UNRECOGNIZED: u8 = 0xFE;
+pub const BOARD_REV_UNDETECTED: u8 = 0xFF;
+
+pub const USB_BOARD_ID_JAWBREAKER: c_int = 0x604B;
+pub const USB_BOARD_ID_HACKRF_ONE: c_int = 0x6089;
+pub const USB_BOARD_ID_RAD1O: c_int = 0xCC15;
+pub const USB_BOARD_ID_INVALID: c_int = 0xFFFF;
 
 pub const RF_PATH_FILTER_BYPASS: c_uint = 0;
 pub const RF_PATH_FILTER_LOW_PASS: c_uint = 1;
 pub const RF_PATH_FILTER_HIGH_PASS: c_uint = 2;
 
+#[allow(non_camel_case_types)]
+pub type sweep_style = c_uint;
+pub const LINEAR: sweep_style = 0;
+pub const INTERLEAVED: sweep_style = 1;
+
+pub const HACKRF_OPERACAKE_ADDRESS_INVALID: u8 = 0xFF;
+pub const HACKRF_OPERACAKE_MAX_BOARDS: usize = 8;
+pub const HACKRF_OPERACAKE_MAX_DWELL_TIMES: usize = 16;
+pub const HACKRF_OPERACAKE_MAX_FREQ_RANGES: usize = 8;
+
+pub const OPERACAKE_PA1: u8 = 0;
+pub const OPERACAKE_PA2: u8 = 1;
+pub const OPERACAKE_PA3: u8 = 2;
+pub const OPERACAKE_PA4: u8 = 3;
+pub const OPERACAKE_PB1: u8 = 4;
+pub const OPERACAKE_PB2: u8 = 5;
+pub const OPERACAKE_PB3: u8 = 6;
+pub const OPERACAKE_PB4: u8 = 7;
+
+#[allow(non_camel_case_types)]
+pub type operacake_switching_mode = c_uint;
+pub const OPERACAKE_MODE_MANUAL: operacake_switching_mode = 0;
+pub const OPERACAKE_MODE_FREQUENCY: operacake_switching_mode = 1;
+pub const OPERACAKE_MODE_TIME: operacake_switching_mode = 2;
+
 #[allow(non_camel_case_types)]
 #[repr(C)]
-pub struct hackrf_device;
+pub struct hackrf_device {
+    _private: [u8; 0]
+}
 
+#[allow(non_camel_case_types)]
 #[repr(C)]
 pub struct hackrf_transfer {
     pub device: *mut hackrf_device,
-    pub buffer: *mut uint8_t,
+    pub buffer: *mut u8,
     pub buffer_length: c_int,
     pub valid_length: c_int,
     pub rx_ctx: *mut c_void,
     pub tx_ctx: *mut c_void
 }
 
+#[allow(non_camel_case_types)]
 #[repr(C)]
 pub struct read_partid_serialno_t {
-    pub part_id: [uint32_t, ..2],
-    pub serial_no: [uint32_t, ..4]
+    pub part_id: [u32; 2],
+    pub serial_no: [u32; 4]
+}
+
+#[allow(non_camel_case_types)]
+#[repr(C)]
+pub struct hackrf_operacake_dwell_time {
+    pub dwell: u32,
+    pub port: u8
+}
+
+#[allow(non_camel_case_types)]
+#[repr(C)]
+pub struct hackrf_operacake_freq_range {
+    pub freq_min: u16,
+    pub freq_max: u16,
+    pub port: u8
+}
+
+#[allow(non_camel_case_types)]
+#[repr(C)]
+pub struct hackrf_device_list_t {
+    pub serial_numbers: *mut *mut c_char,
+    pub usb_board_ids: *mut c_int,
+    pub usb_device_index: *mut c_int,
+    pub devicecount: c_int,
+    pub usb_devices: *mut *mut c_void,
+    pub usb_devicecount: c_int
+}
+
+#[allow(non_camel_case_types)]
+pub type hackrf_sample_block_cb_fn =
+    extern "C" fn(transfer: *mut hackrf_transfer) -> c_int;
+
+/// Declares the functions of one C library. Without the `dynamic-loading`
+/// feature this is a plain `#[link]` extern block. With it, the library is
+/// opened at runtime by `load` and each function becomes an `unsafe fn`
+/// wrapper with the same signature, so callers are unaffected.
+///
+/// Functions listed as optional are missing from older libhackrf versions.
+/// When dynamically loaded they return `HACKRF_ERROR_USB_API_VERSION` if
+/// absent, and `provides` reports whether each one was found.
+macro_rules! library {
+    (
+        link = $link:literal;
+        $library:ident in $slot:ident from $filenames:expr;
+        required {
+            $( pub fn $name:ident ( $( $arg:ident : $ty:ty ),* $(,)? )
+                   $( -> $ret:ty )?; )*
+        }
+        optional {
+            $( pub fn $oname:ident ( $( $oarg:ident : $oty:ty ),* $(,)? )
+                   -> c_int; )*
+        }
+    ) => {
+        #[cfg(not(feature = "dynamic-loading"))]
+        #[link(name=$link)]
+        extern "C" {
+            $( pub fn $name($( $arg: $ty ),*) $( -> $ret )?; )*
+            $( pub fn $oname($( $oarg: $oty ),*) -> c_int; )*
+        }
+
+        #[cfg(feature = "dynamic-loading")]
+        struct $library {
+            _library: libloading::Library,
+            $( $name: unsafe extern "C" fn($( $ty ),*) $( -> $ret )?, )*
+            $( $oname: Option<unsafe extern "C" fn($( $oty ),*) -> c_int>, )*
+        }
+
+        #[cfg(feature = "dynamic-loading")]
+        static $slot: OnceLock<Result<$library, String>> = OnceLock::new();
+
+        #[cfg(feature = "dynamic-loading")]
+        impl $library {
+            fn open() -> Result<$library, String> {
+                let mut errors = Vec::new();
+                for filename in $filenames {
+                    match unsafe { libloading::Library::new(filename) } {
+                        Ok(library) => return $library::resolve(library),
+                        Err(e) => errors.push(e.to_string())
+                    }
+                }
+                Err(errors.join("; "))
+            }
+
+            fn resolve(library: libloading::Library)
+                       -> Result<$library, String> {
+                unsafe {
+                    Ok($library {
+                        $( $name: *library.get(
+                            concat!(stringify!($name), "\0").as_bytes())
+                            .map_err(|e| e.to_string())?, )*
+                        $( $oname: library.get(
+                            concat!(stringify!($oname), "\0").as_bytes())
+                            .ok().map(|f| *f), )*
+                        _library: library
+                    })
+                }
+            }
+
+            /// True if the optional function `name` was found.
+            #[allow(unused_variables)]
+            fn provides(&self, name: &str) -> bool {
+                $( if name == stringify!($oname) {
+                    return self.$oname.is_some();
+                } )*
+                false
+            }
+
+            fn get() -> &'static $library {
+                match $slot.get_or_init($library::open) {
+                    Ok(library) => library,
+                    Err(e) => panic!("{} could not be loaded: {}",
+                                     stringify!($link), e)
+                }
+            }
+        }
+
+        $(
+            #[cfg(feature = "dynamic-loading")]
+            pub unsafe fn $name($( $arg: $ty ),*) $( -> $ret )? {
+                ($library::get().$name)($( $arg ),*)
+            }
+        )*
+
+        $(
+            #[cfg(feature = "dynamic-loading")]
+            pub unsafe fn $oname($( $oarg: $oty ),*) -> c_int {
+                match $library::get().$oname {
+                    Some(f) => f($( $oarg ),*),
+                    None => HACKRF_ERROR_USB_API_VERSION
+                }
+            }
+        )*
+    };
 }
 
-#[link(name="hackrf")]
-extern "C" {
-    pub fn hackrf_init() -> c_int;
-    pub fn hackrf_exit() -> c_int;
-
-    pub fn hackrf_open(device: *mut *mut hackrf_device) -> c_int;
-    pub fn hackrf_close(device: *mut hackrf_device) -> c_int;
-
-    pub fn hackrf_start_rx(device: *mut hackrf_device,
-                           callback: extern fn(*mut hackrf_transfer) -> c_int,
-                           rx_ctx: *mut c_void)
-                           -> c_int;
-    pub fn hackrf_stop_rx(device: *mut hackrf_device) -> c_int;
-    pub fn hackrf_start_tx(device: *mut hackrf_device,
-                           callback: extern fn(*mut hackrf_transfer) -> c_int,
-                           tx_ctx: *mut c_void)
-                           -> c_int;
-    pub fn hackrf_stop_tx(device: *mut hackrf_device) -> c_int;
-
-    // Returns HACKRF_TRUE=1 if success
-    pub fn hackrf_is_streaming(device: *mut hackrf_device) -> c_int;
-
-    pub fn hackrf_set_baseband_filter_bandwidth(device: *mut hackrf_device,
-                                            bandwidth_hz: uint32_t) -> c_int;
-
-    pub fn hackrf_board_id_read(device: *mut hackrf_device,
-                            value: *mut uint8_t) -> c_int;
-    pub fn hackrf_version_string_read(device: *mut hackrf_device,
-                                      version: *mut c_char,
-                                      length: uint8_t) -> c_int;
-    pub fn hackrf_board_partid_serialno_read(
-        device: *mut hackrf_device,
-        read_partid_serialno: *mut read_partid_serialno_t) -> c_int;
-
-    pub fn hackrf_set_freq(device: *mut hackrf_device,
-                       freq_hz: uint64_t) -> c_int;
-    pub fn hackrf_set_freq_explicit(device: *mut hackrf_device,
-                                if_freq_hz: uint64_t,
-                                lo_freq_hz: uint64_t,
-                                path: c_uint) -> c_int;
-    
-    // Currently 8-20MHz, either as a fraction:
-    // freq=20_000_000 divider=2 giving 10MHz
-    // or as a double: freq=10_000_000
-    // Preferred rates are 8, 10, 12.5, 16 and 20MHz due to less jitter.
-    pub fn hackrf_set_sample_rate_manual(device: *mut hackrf_device,
-                                     freq_hz: uint32_t,
-                                     divider: uint32_t) -> c_int;
-    pub fn hackrf_set_sample_rate(device: *mut hackrf_device,
-                              freq_hz: c_double) -> c_int;
-
-    // External amp, bool on/off
-    pub fn hackrf_set_amp_enable(device: *mut hackrf_device,
-                             value: uint8_t) -> c_int;
-
-    // Range 0-40 step 8dB
-    pub fn hackrf_set_lna_gain(device: *mut hackrf_device,
-                           value: uint32_t) -> c_int;
-    // Range 0-62 step 2dB
-    pub fn hackrf_set_vga_gain(device: *mut hackrf_device,
-                           value: uint32_t) -> c_int;
-    // Range 0-47 step 1dB
-    pub fn hackrf_set_txvga_gain(device: *mut hackrf_device,
-                             value: uint32_t) -> c_int;
-
-    // Antenna port power control
-    pub fn hackrf_set_antenna_enable(device: *mut hackrf_device,
-                                 value: uint8_t) -> c_int;
-
-    pub fn hackrf_error_name(errcode: c_int) -> *const c_char;
-    pub fn hackrf_board_id_name(hackrf_board_id: uint8_t) -> *const c_char;
-    pub fn hackrf_filter_path_name(path: c_uint) -> *const c_char;
-
-    // Compute nearest freq for bw filter (manual filter)
-    pub fn hackrf_compute_baseband_filter_bw_round_down_lt(
-        bandwidth_hz: uint32_t) -> uint32_t;
-    // Compute best default value depending on sample rate (auto filter)
-    pub fn hackrf_compute_baseband_filter_bw(
-        bandwidth_hz: uint32_t) -> uint32_t;
+library! {
+    link = "hackrf";
+    HackRFLibrary in HACKRF from HACKRF_FILENAMES;
+    required {
+        pub fn hackrf_init() -> c_int;
+        pub fn hackrf_exit() -> c_int;
+
+        pub fn hackrf_device_list() -> *mut hackrf_device_list_t;
+        pub fn hackrf_device_list_open(list: *mut hackrf_device_list_t,
+                                       idx: c_int,
+                                       device: *mut *mut hackrf_device)
+                                       -> c_int;
+        pub fn hackrf_device_list_free(list: *mut hackrf_device_list_t);
+
+        pub fn hackrf_open(device: *mut *mut hackrf_device) -> c_int;
+        pub fn hackrf_open_by_serial(desired_serial_number: *const c_char,
+                                     device: *mut *mut hackrf_device)
+                                     -> c_int;
+        pub fn hackrf_close(device: *mut hackrf_device) -> c_int;
+
+        pub fn hackrf_start_rx(device: *mut hackrf_device,
+                               callback: hackrf_sample_block_cb_fn,
+                               rx_ctx: *mut c_void)
+                               -> c_int;
+        pub fn hackrf_stop_rx(device: *mut hackrf_device) -> c_int;
+        pub fn hackrf_start_tx(device: *mut hackrf_device,
+                               callback: hackrf_sample_block_cb_fn,
+                               tx_ctx: *mut c_void)
+                               -> c_int;
+        pub fn hackrf_stop_tx(device: *mut hackrf_device) -> c_int;
+
+        // Returns HACKRF_TRUE=1 if success
+        pub fn hackrf_is_streaming(device: *mut hackrf_device) -> c_int;
+
+        pub fn hackrf_set_baseband_filter_bandwidth(
+            device: *mut hackrf_device, bandwidth_hz: u32) -> c_int;
+
+        // MAX2837 transceiver registers, 0-31, each 10 bits
+        pub fn hackrf_max2837_read(device: *mut hackrf_device,
+                                   register_number: u8,
+                                   value: *mut u16) -> c_int;
+        pub fn hackrf_max2837_write(device: *mut hackrf_device,
+                                    register_number: u8,
+                                    value: u16) -> c_int;
+
+        // Si5351C clock generator registers, 0-255, each 8 bits
+        pub fn hackrf_si5351c_read(device: *mut hackrf_device,
+                                   register_number: u16,
+                                   value: *mut u16) -> c_int;
+        pub fn hackrf_si5351c_write(device: *mut hackrf_device,
+                                    register_number: u16,
+                                    value: u16) -> c_int;
+
+        // RFFC5071 mixer/synthesizer registers, 0-30, each 16 bits
+        pub fn hackrf_rffc5071_read(device: *mut hackrf_device,
+                                    register_number: u8,
+                                    value: *mut u16) -> c_int;
+        pub fn hackrf_rffc5071_write(device: *mut hackrf_device,
+                                     register_number: u8,
+                                     value: u16) -> c_int;
+
+        // SPI flash holding the firmware, read and written up to 256 bytes
+        // at a time
+        pub fn hackrf_spiflash_erase(device: *mut hackrf_device) -> c_int;
+        pub fn hackrf_spiflash_write(device: *mut hackrf_device,
+                                     address: u32,
+                                     length: u16,
+                                     data: *const u8) -> c_int;
+        pub fn hackrf_spiflash_read(device: *mut hackrf_device,
+                                    address: u32,
+                                    length: u16,
+                                    data: *mut u8) -> c_int;
+
+        // Program the CPLD from an XSVF file
+        pub fn hackrf_cpld_write(device: *mut hackrf_device,
+                                 data: *const u8,
+                                 total_length: c_uint) -> c_int;
+
+        pub fn hackrf_board_id_read(device: *mut hackrf_device,
+                                    value: *mut u8) -> c_int;
+        pub fn hackrf_version_string_read(device: *mut hackrf_device,
+                                          version: *mut c_char,
+                                          length: u8) -> c_int;
+        pub fn hackrf_board_partid_serialno_read(
+            device: *mut hackrf_device,
+            read_partid_serialno: *mut read_partid_serialno_t) -> c_int;
+
+        pub fn hackrf_set_freq(device: *mut hackrf_device,
+                               freq_hz: u64) -> c_int;
+        pub fn hackrf_set_freq_explicit(device: *mut hackrf_device,
+                                        if_freq_hz: u64,
+                                        lo_freq_hz: u64,
+                                        path: c_uint) -> c_int;
+
+        // Currently 8-20MHz, either as a fraction:
+        // freq=20_000_000 divider=2 giving 10MHz
+        // or as a double: freq=10_000_000
+        // Preferred rates are 8, 10, 12.5, 16 and 20MHz due to less jitter.
+        pub fn hackrf_set_sample_rate_manual(device: *mut hackrf_device,
+                                             freq_hz: u32,
+                                             divider: u32) -> c_int;
+        pub fn hackrf_set_sample_rate(device: *mut hackrf_device,
+                                      freq_hz: c_double) -> c_int;
+
+        // External amp, bool on/off
+        pub fn hackrf_set_amp_enable(device: *mut hackrf_device,
+                                     value: u8) -> c_int;
+
+        // Range 0-40 step 8dB
+        pub fn hackrf_set_lna_gain(device: *mut hackrf_device,
+                                   value: u32) -> c_int;
+        // Range 0-62 step 2dB
+        pub fn hackrf_set_vga_gain(device: *mut hackrf_device,
+                                   value: u32) -> c_int;
+        // Range 0-47 step 1dB
+        pub fn hackrf_set_txvga_gain(device: *mut hackrf_device,
+                                     value: u32) -> c_int;
+
+        // Antenna port power control
+        pub fn hackrf_set_antenna_enable(device: *mut hackrf_device,
+                                         value: u8) -> c_int;
+
+        pub fn hackrf_error_name(errcode: c_int) -> *const c_char;
+        pub fn hackrf_board_id_name(hackrf_board_id: u8) -> *const c_char;
+        pub fn hackrf_filter_path_name(path: c_uint) -> *const c_char;
+
+        // Compute nearest freq for bw filter (manual filter)
+        pub fn hackrf_compute_baseband_filter_bw_round_down_lt(
+            bandwidth_hz: u32) -> u32;
+        // Compute best default value depending on sample rate (auto filter)
+        pub fn hackrf_compute_baseband_filter_bw(
+            bandwidth_hz: u32) -> u32;
+    }
+    optional {
+        // Sweep mode, libhackrf 2017.02.1 onwards
+        pub fn hackrf_init_sweep(device: *mut hackrf_device,
+                                 frequency_list: *const u16,
+                                 num_ranges: c_int,
+                                 num_bytes: u32,
+                                 step_width: u32,
+                                 offset: u32,
+                                 style: sweep_style) -> c_int;
+        pub fn hackrf_start_rx_sweep(device: *mut hackrf_device,
+                                     callback: hackrf_sample_block_cb_fn,
+                                     rx_ctx: *mut c_void) -> c_int;
+
+        // Opera Cake antenna switch, libhackrf 2017.02.1 onwards
+        pub fn hackrf_get_operacake_boards(device: *mut hackrf_device,
+                                           boards: *mut u8) -> c_int;
+        pub fn hackrf_set_operacake_ports(device: *mut hackrf_device,
+                                          address: u8,
+                                          port_a: u8,
+                                          port_b: u8) -> c_int;
+
+        // Opera Cake automatic switching, libhackrf 2022.09.1 onwards.
+        // hackrf_set_operacake_freq_ranges replaces the byte-packed
+        // hackrf_set_operacake_ranges, sending the same request.
+        pub fn hackrf_set_operacake_mode(
+            device: *mut hackrf_device, address: u8,
+            mode: operacake_switching_mode) -> c_int;
+        pub fn hackrf_get_operacake_mode(
+            device: *mut hackrf_device, address: u8,
+            mode: *mut operacake_switching_mode) -> c_int;
+        pub fn hackrf_set_operacake_freq_ranges(
+            device: *mut hackrf_device,
+            freq_ranges: *mut hackrf_operacake_freq_range,
+            count: u8) -> c_int;
+        pub fn hackrf_set_operacake_dwell_times(
+            device: *mut hackrf_device,
+            dwell_times: *mut hackrf_operacake_dwell_time,
+            count: u8) -> c_int;
+
+        // CLKOUT port control, libhackrf 2018.01.1 onwards
+        pub fn hackrf_set_clkout_enable(device: *mut hackrf_device,
+                                        value: u8) -> c_int;
+
+        // Hardware triggered streaming, libhackrf 2017.02.1 onwards
+        pub fn hackrf_set_hw_sync_mode(device: *mut hackrf_device,
+                                       value: u8) -> c_int;
+
+        // CLKIN detection, libhackrf 2023.01.1 onwards
+        pub fn hackrf_get_clkin_status(device: *mut hackrf_device,
+                                       status: *mut u8) -> c_int;
+
+        // USB API version from the device descriptor, libhackrf 2017.02.1
+        // onwards
+        pub fn hackrf_usb_api_version_read(device: *mut hackrf_device,
+                                           version: *mut u16) -> c_int;
+
+        // Hardware revision, libhackrf 2023.01.1 onwards
+        pub fn hackrf_board_rev_read(device: *mut hackrf_device,
+                                     value: *mut u8) -> c_int;
+    }
+}
+
+// Only used to find where each HackRF in a device list sits on the USB bus.
+// `dev` is one of the opaque libusb_device pointers in hackrf_device_list_t.
+library! {
+    link = "usb-1.0";
+    LibusbLibrary in LIBUSB from LIBUSB_FILENAMES;
+    required {
+        pub fn libusb_get_bus_number(dev: *mut c_void) -> u8;
+        pub fn libusb_get_device_address(dev: *mut c_void) -> u8;
+        pub fn libusb_get_port_numbers(dev: *mut c_void,
+                                       port_numbers: *mut u8,
+                                       port_numbers_len: c_int) -> c_int;
+    }
+    optional {}
+}
+
+#[cfg(all(feature = "dynamic-loading", target_os = "windows"))]
+const HACKRF_FILENAMES: &[&str] = &["hackrf.dll", "libhackrf.dll"];
+#[cfg(all(feature = "dynamic-loading", target_os = "windows"))]
+const LIBUSB_FILENAMES: &[&str] = &["libusb-1.0.dll"];
+#[cfg(all(feature = "dynamic-loading", target_os = "macos"))]
+const HACKRF_FILENAMES: &[&str] = &["libhackrf.0.dylib", "libhackrf.dylib"];
+#[cfg(all(feature = "dynamic-loading", target_os = "macos"))]
+const LIBUSB_FILENAMES: &[&str] = &["libusb-1.0.0.dylib",
+                                    "libusb-1.0.dylib"];
+#[cfg(all(feature = "dynamic-loading",
+          not(any(target_os = "windows", target_os = "macos"))))]
+const HACKRF_FILENAMES: &[&str] = &["libhackrf.so.0", "libhackrf.so"];
+#[cfg(all(feature = "dynamic-loading",
+          not(any(target_os = "windows", target_os = "macos"))))]
+const LIBUSB_FILENAMES: &[&str] = &["libusb-1.0.so.0", "libusb-1.0.so"];
+
+/// Open libhackrf and libusb, reporting `LibraryNotFound` if either is
+/// missing or lacks a required function. Does nothing when linked.
+#[cfg(feature = "dynamic-loading")]
+pub fn load() -> Result<(), HackRFError> {
+    if let Err(e) = HACKRF.get_or_init(HackRFLibrary::open) {
+        return Err(HackRFError::LibraryNotFound(e.clone()));
+    }
+    if let Err(e) = LIBUSB.get_or_init(LibusbLibrary::open) {
+        return Err(HackRFError::LibraryNotFound(e.clone()));
+    }
+    Ok(())
+}
+
+#[cfg(not(feature = "dynamic-loading"))]
+pub fn load() -> Result<(), HackRFError> {
+    Ok(())
+}
+
+/// True if the optional libhackrf function `name` is available. Always true
+/// when linked, since linking would have failed otherwise.
+#[cfg(feature = "dynamic-loading")]
+pub fn provides(name: &str) -> bool {
+    match HACKRF.get_or_init(HackRFLibrary::open) {
+        Ok(library) => library.provides(name),
+        Err(_) => false
+    }
+}
 
+#[cfg(not(feature = "dynamic-loading"))]
+pub fn provides(_name: &str) -> bool {
+    true
 }
@@ -0,0 +1,246 @@
+// Inspection of HackRF firmware images
+// Copyright Adam Greig <adam@adamgreig.com> 2014
+// Licensed under MIT license
+
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::list::UsbBoardId;
+use crate::spiflash::FirmwareError;
+use crate::{HackRFDevice, HackRFError};
+
+/// The USB vendor ID HackRF firmware enumerates with.
+pub const HACKRF_USB_VID: u16 = 0x1d50;
+
+/// Length of the DFU suffix at the end of a `.dfu` file.
+pub const DFU_SUFFIX_LEN: usize = 16;
+
+/// Length of the LPC43xx boot ROM header at the start of a `.dfu` file.
+pub const LPC_DFU_HEADER_LEN: usize = 16;
+
+/// First byte of an LPC43xx DFU header for an unencrypted image.
+const LPC_DFU_PLAIN: u8 = 0xda;
+
+/// The LPC43xx boot ROM counts image length in blocks of this many bytes.
+const LPC_DFU_BLOCK: usize = 512;
+
+/// Whether an image is a raw binary for the SPI flash or a DFU file for the
+/// LPC43xx's USB bootloader.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum FirmwareFormat {
+    /// A `.bin` file, as written by `flash_firmware` or `hackrf_spiflash`.
+    Bin,
+    /// A `.dfu` file, as loaded into RAM by `dfu-util`.
+    Dfu
+}
+
+/// The DFU suffix at the end of a `.dfu` file.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct DfuSuffix {
+    pub bcd_device: u16,
+    pub product_id: u16,
+    pub vendor_id: u16,
+    pub bcd_dfu: u16,
+    /// The CRC stored in the file.
+    pub crc: u32,
+    /// The CRC computed over the file, which should equal `crc`.
+    pub computed_crc: u32
+}
+
+impl DfuSuffix {
+    /// True if the stored CRC matches the file's contents.
+    pub fn crc_ok(&self) -> bool {
+        self.crc == self.computed_crc
+    }
+}
+
+/// What can be learnt about a firmware image without running it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FirmwareImage {
+    pub format: FirmwareFormat,
+    /// The version string `version_string_read` reports once this firmware
+    /// runs, such as `2024.02.1` or `git-a1b2c3d`, if one was found.
+    pub version: Option<String>,
+    /// Boards the image enumerates as, from its USB device descriptors.
+    pub usb_board_ids: Vec<UsbBoardId>,
+    /// CRC-32 (as used by zlib) of the firmware binary, excluding any DFU
+    /// header and suffix.
+    pub crc32: u32,
+    /// The DFU suffix, for `.dfu` files.
+    pub dfu: Option<DfuSuffix>,
+    binary: Vec<u8>
+}
+
+impl FirmwareImage {
+    /// Inspect the contents of a `.bin` or `.dfu` file. Files ending in a
+    /// DFU suffix are treated as DFU files.
+    /// Returns `FirmwareError::Invalid` if a DFU file is malformed.
+    /// A DFU CRC mismatch is reported through `DfuSuffix::crc_ok` rather
+    /// than as an error.
+    pub fn parse(data: &[u8]) -> Result<FirmwareImage, FirmwareError> {
+        let reject = |reason: &str| Err(FirmwareError::Invalid(
+            reason.to_owned()));
+        let (format, binary, dfu) = match parse_dfu_suffix(data) {
+            None => (FirmwareFormat::Bin, data, None),
+            Some(suffix) => {
+                let body = &data[..data.len() - DFU_SUFFIX_LEN];
+                if body.len() < LPC_DFU_HEADER_LEN ||
+                   body[0] != LPC_DFU_PLAIN {
+                    return reject("DFU file lacks an LPC43xx header");
+                }
+                let blocks = u16::from_le_bytes([body[2], body[3]]) as usize;
+                let binary = &body[LPC_DFU_HEADER_LEN..];
+                if blocks == 0 ||
+                   blocks * LPC_DFU_BLOCK < binary.len() ||
+                   (blocks - 1) * LPC_DFU_BLOCK >= binary.len() {
+                    return reject("DFU header length does not match image");
+                }
+                (FirmwareFormat::Dfu, binary, Some(suffix))
+            }
+        };
+        if binary.is_empty() {
+            return reject("image is empty");
+        }
+        Ok(FirmwareImage {
+            format,
+            version: find_version(binary),
+            usb_board_ids: find_usb_board_ids(binary),
+            crc32: !crc32_update(0xffff_ffff, binary),
+            dfu,
+            binary: binary.to_vec()
+        })
+    }
+
+    /// Read and inspect a `.bin` or `.dfu` file. Malformed files give an
+    /// `io::ErrorKind::InvalidData` error.
+    pub fn from_file<P: AsRef<Path>>(path: P) -> io::Result<FirmwareImage> {
+        let data = fs::read(path)?;
+        FirmwareImage::parse(&data).map_err(|e| {
+            io::Error::new(io::ErrorKind::InvalidData, e.to_string())
+        })
+    }
+
+    /// The firmware binary, without any DFU header or suffix.
+    pub fn binary(&self) -> &[u8] {
+        &self.binary
+    }
+
+    /// True if the image lists `board` among the boards it supports.
+    pub fn supports(&self, board: UsbBoardId) -> bool {
+        self.usb_board_ids.contains(&board)
+    }
+}
+
+/// How a firmware image compares with what a device is running.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FirmwareComparison {
+    /// The version string the device reports.
+    pub device_version: String,
+    /// True if the image's version string is the one the device reports.
+    pub same_version: bool,
+    /// Whether the image supports the device's board, or `None` if either
+    /// is unknown.
+    pub board_supported: Option<bool>
+}
+
+impl HackRFDevice {
+    /// Compare `image` with the firmware this device is running.
+    pub fn compare_firmware(&mut self, image: &FirmwareImage)
+                            -> Result<FirmwareComparison, HackRFError> {
+        let device_version = self.version_string_read()?;
+        let board_supported = match self.board_id_read()?.usb_board_id() {
+            Some(board) if !image.usb_board_ids.is_empty() =>
+                Some(image.supports(board)),
+            _ => None
+        };
+        Ok(FirmwareComparison {
+            same_version: image.version.as_deref() == Some(&device_version),
+            device_version,
+            board_supported
+        })
+    }
+}
+
+/// Parse the DFU suffix at the end of `data`, if it has one.
+fn parse_dfu_suffix(data: &[u8]) -> Option<DfuSuffix> {
+    if data.len() < DFU_SUFFIX_LEN {
+        return None;
+    }
+    let suffix = &data[data.len() - DFU_SUFFIX_LEN..];
+    if &suffix[8..11] != b"UFD" || suffix[11] as usize != DFU_SUFFIX_LEN {
+        return None;
+    }
+    let u16_at = |i: usize| u16::from_le_bytes([suffix[i], suffix[i + 1]]);
+    Some(DfuSuffix {
+        bcd_device: u16_at(0),
+        product_id: u16_at(2),
+        vendor_id: u16_at(4),
+        bcd_dfu: u16_at(6),
+        crc: u32::from_le_bytes([suffix[12], suffix[13], suffix[14],
+                                 suffix[15]]),
+        // dfu-util stores the CRC without the final inversion.
+        computed_crc: crc32_update(0xffff_ffff, &data[..data.len() - 4])
+    })
+}
+
+/// Update a CRC-32 (polynomial 0xedb88320, reflected) with `data`.
+fn crc32_update(mut crc: u32, data: &[u8]) -> u32 {
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = match crc & 1 {
+                0 => crc >> 1,
+                _ => (crc >> 1) ^ 0xedb8_8320
+            };
+        }
+    }
+    crc
+}
+
+/// Find the firmware's version string: a NUL-terminated release version
+/// such as `2024.02.1`, or a build tag such as `git-a1b2c3d`.
+fn find_version(binary: &[u8]) -> Option<String> {
+    binary.split(|&b| b == 0)
+        .filter_map(|s| std::str::from_utf8(s).ok())
+        .find(|s| is_version(s))
+        .map(str::to_owned)
+}
+
+fn is_version(s: &str) -> bool {
+    if s.len() > 64 || !s.bytes().all(|b| b.is_ascii_graphic()) {
+        return false;
+    }
+    let parts: Vec<&str> = s.splitn(3, '.').collect();
+    let digits = |p: &str, n: Option<usize>| {
+        !p.is_empty() && p.bytes().all(|b| b.is_ascii_digit()) &&
+            n.is_none_or(|n| p.len() == n)
+    };
+    let release = parts.len() == 3 && digits(parts[0], Some(4)) &&
+        digits(parts[1], Some(2)) &&
+        parts[2].starts_with(|c: char| c.is_ascii_digit());
+    let tag = ["git-", "local-"].iter().any(|prefix| {
+        s.strip_prefix(prefix).is_some_and(|rest| {
+            let hash = rest.split('-').next().unwrap_or("");
+            hash.len() >= 7 && hash.bytes().all(|b| b.is_ascii_hexdigit())
+        })
+    });
+    release || tag
+}
+
+/// Find the product IDs of the HackRF USB device descriptors in `binary`.
+fn find_usb_board_ids(binary: &[u8]) -> Vec<UsbBoardId> {
+    let vid = HACKRF_USB_VID.to_le_bytes();
+    let mut ids = Vec::new();
+    for descriptor in binary.windows(18) {
+        if descriptor[0] == 18 && descriptor[1] == 1 &&
+           descriptor[8..10] == vid {
+            let pid = u16::from_le_bytes([descriptor[10], descriptor[11]]);
+            let id = UsbBoardId::from_raw(pid as libc::c_int);
+            if !ids.contains(&id) {
+                ids.push(id);
+            }
+        }
+    }
+    ids
+}
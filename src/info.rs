@@ -0,0 +1,100 @@
+// Inventory of every attached HackRF, like libhackrf's hackrf_info
+// Copyright Adam Greig <adam@adamgreig.com> 2014
+// Licensed under MIT license
+
+use std::env;
+use std::process;
+
+use hackrf::{BoardReport, DeviceList, HackRFError};
+
+const USAGE: &str = "Usage: hackrf-info [--json]
+
+Report the board ID, firmware version, USB API version, part ID, serial
+number and hardware revision of every attached HackRF.
+
+    --json    print a JSON array with one object per board";
+
+/// Print `report` in the layout `hackrf_info` uses.
+fn print(report: &BoardReport) {
+    let unknown = || String::from("unknown");
+    println!("Index: {}", report.info.index);
+    println!("USB descriptor serial number: {}",
+             report.info.serial_number.clone().unwrap_or_else(unknown));
+    println!("USB board ID: {}", report.info.usb_board_id);
+    println!("USB bus path: {}", report.info.bus_path());
+    if let Some(ref e) = report.open_error {
+        println!("Could not open: {}", e);
+        return;
+    }
+    println!("Board ID: {}", match report.board_id {
+        Some(id) => format!("{} ({})", u8::from(id), id),
+        None => unknown()
+    });
+    println!("Firmware version: {}",
+             report.version.clone().unwrap_or_else(unknown));
+    println!("USB API version: {}",
+             report.usb_api_version_string().unwrap_or_else(unknown));
+    let (part_id, serial) = match report.part_serial {
+        Some((part_id, serial)) => (part_id.to_string(), serial.to_string()),
+        None => (unknown(), unknown())
+    };
+    println!("Part ID: {}", part_id);
+    println!("Serial number: {}", serial);
+    println!("Hardware revision: {}", match report.board_rev {
+        Some((_, ref name)) => name.clone(),
+        None => unknown()
+    });
+}
+
+fn fail(message: &str, e: HackRFError) -> ! {
+    eprintln!("hackrf-info: {}: {}", message, e);
+    process::exit(1);
+}
+
+fn main() {
+    let mut json = false;
+    for arg in env::args().skip(1) {
+        match arg.as_str() {
+            "--json" => json = true,
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                return;
+            },
+            _ => {
+                eprintln!("{}", USAGE);
+                process::exit(2);
+            }
+        }
+    }
+
+    if let Err(e) = hackrf::init() {
+        fail("could not initialise libhackrf", e);
+    }
+    let list = match DeviceList::new() {
+        Ok(list) => list,
+        Err(e) => fail("could not list devices", e)
+    };
+    let boards: Vec<BoardReport> = list.iter()
+        .map(|info| BoardReport::read(&list, info))
+        .collect();
+    drop(list);
+    let _ = hackrf::exit();
+
+    if json {
+        let objects: Vec<String> = boards.iter()
+            .map(BoardReport::to_json).collect();
+        println!("[{}]", objects.join(",\n "));
+    } else if boards.is_empty() {
+        println!("No HackRF boards found.");
+    } else {
+        println!("Found {} HackRF board(s).", boards.len());
+        for board in &boards {
+            println!();
+            print(board);
+        }
+    }
+
+    if boards.iter().any(|b| b.open_error.is_some()) {
+        process::exit(1);
+    }
+}
@@ -0,0 +1,117 @@
+// What can be read from each attached HackRF, as hackrf-info reports it
+// Copyright Adam Greig <adam@adamgreig.com> 2014
+// Licensed under MIT license
+
+use std::fmt::Write;
+
+use crate::board::{BoardId, PartId, SerialNumber};
+use crate::list::{DeviceInfo, DeviceList};
+use crate::{HackRFDevice, HackRFError};
+
+/// What could be read from one board found by `DeviceList`. Reads which
+/// fail, for example because libhackrf is too old, are left as `None`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BoardReport {
+    pub info: DeviceInfo,
+    /// Why the board could not be opened, in which case nothing else was
+    /// read.
+    pub open_error: Option<HackRFError>,
+    pub board_id: Option<BoardId>,
+    pub version: Option<String>,
+    pub usb_api_version: Option<u16>,
+    pub part_serial: Option<(PartId, SerialNumber)>,
+    pub board_rev: Option<(u8, String)>
+}
+
+impl BoardReport {
+    /// Open the board `info` describes and read everything it reports.
+    pub fn read(list: &DeviceList, info: DeviceInfo) -> BoardReport {
+        match list.open(info.index) {
+            Ok(mut device) => {
+                let report = BoardReport::from_device(info, &mut device);
+                let _ = device.close();
+                report
+            },
+            Err(e) => BoardReport {
+                open_error: Some(e),
+                ..BoardReport::unread(info)
+            }
+        }
+    }
+
+    /// Read everything `device`, listed as `info`, reports.
+    pub fn from_device(info: DeviceInfo, device: &mut HackRFDevice)
+                       -> BoardReport {
+        BoardReport {
+            board_id: device.board_id_read().ok(),
+            version: device.version_string_read().ok(),
+            usb_api_version: device.usb_api_version_read().ok(),
+            part_serial: device.board_partid_serialno_read().ok(),
+            board_rev: device.board_rev_read().ok(),
+            ..BoardReport::unread(info)
+        }
+    }
+
+    fn unread(info: DeviceInfo) -> BoardReport {
+        BoardReport { info, open_error: None, board_id: None, version: None,
+                      usb_api_version: None, part_serial: None,
+                      board_rev: None }
+    }
+
+    /// The USB API version as `hackrf_info` prints it, such as `1.08`.
+    pub fn usb_api_version_string(&self) -> Option<String> {
+        self.usb_api_version.map(|v| format!("{:x}.{:02x}", v >> 8, v & 0xff))
+    }
+
+    /// One JSON object with a field for everything reported, `null` where
+    /// it could not be read.
+    pub fn to_json(&self) -> String {
+        let string = |s: Option<&str>| s.map_or("null".into(), json_string);
+        let number = |n: Option<u8>| {
+            n.map_or("null".into(), |n| n.to_string())
+        };
+        let board_name = self.board_id.map(|id| id.to_string());
+        let part_id = self.part_serial.map(|(p, _)| p.to_string());
+        let serial = self.part_serial.map(|(_, s)| s.to_string());
+        let api_version = self.usb_api_version_string();
+        let error = self.open_error.as_ref().map(|e| e.to_string());
+        let fields = [
+            ("index", self.info.index.to_string()),
+            ("usb_serial_number", string(self.info.serial_number.as_deref())),
+            ("usb_board_id", json_string(&self.info.usb_board_id.to_string())),
+            ("bus_path", json_string(&self.info.bus_path())),
+            ("board_id", number(self.board_id.map(u8::from))),
+            ("board_name", string(board_name.as_deref())),
+            ("firmware_version", string(self.version.as_deref())),
+            ("usb_api_version", string(api_version.as_deref())),
+            ("part_id", string(part_id.as_deref())),
+            ("serial_number", string(serial.as_deref())),
+            ("board_rev", number(self.board_rev.as_ref().map(|r| r.0))),
+            ("board_rev_name",
+             string(self.board_rev.as_ref().map(|r| r.1.as_str()))),
+            ("error", string(error.as_deref()))
+        ];
+        let fields: Vec<String> = fields.iter()
+            .map(|(name, value)| format!("\"{}\": {}", name, value))
+            .collect();
+        format!("{{{}}}", fields.join(", "))
+    }
+}
+
+/// `s` as a JSON string, escaping quotes, backslashes and control
+/// characters.
+fn json_string(s: &str) -> String {
+    let mut out = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            c if (c as u32) < 0x20 => {
+                let _ = write!(out, "\\u{:04x}", c as u32);
+            },
+            c => out.push(c)
+        }
+    }
+    out.push('"');
+    out
+}
@@ -0,0 +1,152 @@
+// Interleaved 8-bit IQ samples and conversions
+// Copyright Adam Greig <adam@adamgreig.com> 2014
+// Licensed under MIT license
+
+//! The HackRF streams signed 8-bit I and Q values, interleaved as
+//! `I0 Q0 I1 Q1 ...`. `IQ8` has exactly that layout, so transfer buffers
+//! are viewed as `&[IQ8]` without copying, and the bulk conversions here
+//! are plain element-wise loops the compiler can vectorise.
+
+use std::slice;
+
+pub use num_complex::Complex;
+
+/// One complex sample as sent over USB: signed 8-bit in-phase and
+/// quadrature components.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+#[repr(C)]
+pub struct IQ8 {
+    pub i: i8,
+    pub q: i8
+}
+
+impl IQ8 {
+    pub const fn new(i: i8, q: i8) -> IQ8 {
+        IQ8 { i, q }
+    }
+
+    /// View raw transfer bytes as samples. A trailing odd byte is ignored.
+    pub fn from_bytes(bytes: &[u8]) -> &[IQ8] {
+        unsafe {
+            slice::from_raw_parts(bytes.as_ptr() as *const IQ8,
+                                  bytes.len() / 2)
+        }
+    }
+
+    /// View raw transfer bytes as mutable samples. A trailing odd byte is
+    /// ignored.
+    pub fn from_bytes_mut(bytes: &mut [u8]) -> &mut [IQ8] {
+        unsafe {
+            slice::from_raw_parts_mut(bytes.as_mut_ptr() as *mut IQ8,
+                                      bytes.len() / 2)
+        }
+    }
+
+    /// View samples as the interleaved bytes sent over USB.
+    pub fn as_bytes(samples: &[IQ8]) -> &[u8] {
+        unsafe {
+            slice::from_raw_parts(samples.as_ptr() as *const u8,
+                                  samples.len() * 2)
+        }
+    }
+
+    /// Convert to floating point, scaled so full scale is -1.0 to +1.0.
+    pub fn to_complex_f32(self) -> Complex<f32> {
+        Complex::new(self.i as f32 / 128.0, self.q as f32 / 128.0)
+    }
+
+    /// Widen to 16 bits without scaling.
+    pub fn to_complex_i16(self) -> Complex<i16> {
+        Complex::new(self.i as i16, self.q as i16)
+    }
+
+    /// Convert from floating point with full scale -1.0 to +1.0, rounding
+    /// to the nearest value and saturating out-of-range inputs.
+    pub fn from_complex_f32(c: Complex<f32>) -> IQ8 {
+        IQ8 { i: f32_to_i8(c.re), q: f32_to_i8(c.im) }
+    }
+
+    /// Narrow from 16 bits without scaling, saturating out-of-range inputs.
+    pub fn from_complex_i16(c: Complex<i16>) -> IQ8 {
+        IQ8 { i: i16_to_i8(c.re), q: i16_to_i8(c.im) }
+    }
+}
+
+#[inline(always)]
+fn f32_to_i8(x: f32) -> i8 {
+    // Float to int casts saturate, so only the rounding needs doing here.
+    (x * 128.0).round() as i8
+}
+
+#[inline(always)]
+fn i16_to_i8(x: i16) -> i8 {
+    x.clamp(i8::MIN as i16, i8::MAX as i16) as i8
+}
+
+impl From<IQ8> for Complex<f32> {
+    fn from(s: IQ8) -> Complex<f32> {
+        s.to_complex_f32()
+    }
+}
+
+impl From<IQ8> for Complex<i16> {
+    fn from(s: IQ8) -> Complex<i16> {
+        s.to_complex_i16()
+    }
+}
+
+impl From<Complex<f32>> for IQ8 {
+    fn from(c: Complex<f32>) -> IQ8 {
+        IQ8::from_complex_f32(c)
+    }
+}
+
+impl From<Complex<i16>> for IQ8 {
+    fn from(c: Complex<i16>) -> IQ8 {
+        IQ8::from_complex_i16(c)
+    }
+}
+
+/// Convert `input` into `output` using `IQ8::to_complex_f32`.
+/// Converts as many samples as fit in the shorter slice and returns that
+/// count.
+pub fn to_complex_f32(input: &[IQ8], output: &mut [Complex<f32>]) -> usize {
+    let n = input.len().min(output.len());
+    for (o, s) in output[..n].iter_mut().zip(&input[..n]) {
+        *o = s.to_complex_f32();
+    }
+    n
+}
+
+/// Convert `input` into `output` using `IQ8::to_complex_i16`.
+/// Converts as many samples as fit in the shorter slice and returns that
+/// count.
+pub fn to_complex_i16(input: &[IQ8], output: &mut [Complex<i16>]) -> usize {
+    let n = input.len().min(output.len());
+    for (o, s) in output[..n].iter_mut().zip(&input[..n]) {
+        *o = s.to_complex_i16();
+    }
+    n
+}
+
+/// Convert `input` into `output` using `IQ8::from_complex_f32`, e.g. to
+/// fill a TX buffer. Converts as many samples as fit in the shorter slice
+/// and returns that count.
+pub fn from_complex_f32(input: &[Complex<f32>], output: &mut [IQ8]) -> usize {
+    let n = input.len().min(output.len());
+    for (o, c) in output[..n].iter_mut().zip(&input[..n]) {
+        *o = IQ8::from_complex_f32(*c);
+    }
+    n
+}
+
+/// Convert `input` into `output` using `IQ8::from_complex_i16`, e.g. to
+/// fill a TX buffer. Converts as many samples as fit in the shorter slice
+/// and returns that count.
+pub fn from_complex_i16(input: &[Complex<i16>], output: &mut [IQ8]) -> usize {
+    let n = input.len().min(output.len());
+    for (o, c) in output[..n].iter_mut().zip(&input[..n]) {
+        *o = IQ8::from_complex_i16(*c);
+    }
+    n
+}
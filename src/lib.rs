@@ -4,340 +4,412 @@
 
 #![allow(dead_code)]
 
-extern crate libc;
-
 mod ffi;
-
+#[cfg(feature = "async")]
+mod async_stream;
+mod backend;
+mod board;
+mod buffer;
+mod clock;
+mod config;
+mod error;
+mod firmware;
+mod inventory;
+pub mod iq;
+mod list;
+mod max2837;
+mod multi;
+mod operacake;
+mod rffc5071;
+mod si5351c;
+mod sim;
+#[cfg(feature = "spectrum")]
+mod spectrum;
+mod spiflash;
+mod stream;
+mod sweep;
+mod transceiver;
+mod xsvf;
+
+#[cfg(feature = "async")]
+pub use async_stream::{rx_channel, tx_channel, AsyncRx, AsyncTx, RxSender,
+                       TxSource};
+pub use backend::{Backend, LibHackRF, RxCallback, TxCallback};
+pub use board::{BoardId, PartId, SerialNumber};
+pub use buffer::SampleBuffer;
+pub use clock::{start_synchronised_rx, ClockConfig, SyncError};
+pub use config::RadioConfig;
+pub use error::HackRFError;
+pub use firmware::{DfuSuffix, FirmwareComparison, FirmwareFormat,
+                   FirmwareImage, DFU_SUFFIX_LEN, HACKRF_USB_VID,
+                   LPC_DFU_HEADER_LEN};
+pub use inventory::BoardReport;
+pub use iq::IQ8;
+pub use list::{DeviceList, DeviceInfo, UsbBoardId};
+pub use max2837::{Max2837Registers, MAX2837_FT_BANDWIDTHS_HZ,
+                  MAX2837_MAX_VALUE, MAX2837_NUM_REGS};
+pub use multi::{MultiDeviceRx, MultiRxStream, TaggedBuffer};
+pub use operacake::{OperaCake, OperaCakeDwell, OperaCakeMode, OperaCakePort,
+                    OperaCakeRange, OPERACAKE_MAX_BOARDS,
+                    OPERACAKE_MAX_DWELL_TIMES, OPERACAKE_MAX_FREQ_RANGES};
+pub use rffc5071::{tuning_plan, Rffc5071Path, Rffc5071Registers, TuningPlan,
+                   RFFC5071_NUM_REGS, RFFC5071_REF_HZ};
+pub use si5351c::{Si5351cPll, Si5351cRegisters, Si5351cSource,
+                  SI5351C_CLKIN_HZ, SI5351C_NUM_REGS, SI5351C_XTAL_HZ};
+pub use sim::{Signal, SimHandle, SimTrigger, SimulatedHackRF};
+#[cfg(feature = "spectrum")]
+pub use spectrum::{PowerSpectrum, SpectrumRow, SWEEP_BASEBAND_FILTER_HZ,
+                   SWEEP_SAMPLE_RATE_HZ};
+pub use spiflash::{check_firmware_image, FirmwareError, FlashProgress,
+                   FlashStage, SPIFLASH_PAGE_SIZE, SPIFLASH_SIZE};
+pub use stream::{RxStream, TxStream};
+pub use sweep::{SweepBlock, SweepConfig, SweepStyle, MAX_SWEEP_DWELL_SAMPLES,
+                MAX_SWEEP_RANGES, SWEEP_BLOCK_BYTES, SWEEP_BLOCK_SAMPLES,
+                SWEEP_HEADER_BYTES};
+pub use transceiver::{StreamDirection, Transceiver};
+pub use xsvf::{Xsvf, XsvfCommand, XsvfError};
+
+/// An open HackRF, real or simulated.
 pub struct HackRFDevice {
-    ptr: *mut ffi::hackrf_device
-}
-
-impl Drop for HackRFDevice {
-    #[inline(never)]
-    fn drop(&mut self) {
-        unsafe {
-            ffi::hackrf_close(self.ptr);
-        }
-    }
-}
-
-pub struct HackRFError {
-    errno: int,
-    errstr: String
-}
-
-impl std::fmt::Show for HackRFError {
-    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        write!(f, "HackRF error: {} ({})", self.errstr, self.errno)
-    }
-}
-
-fn hackrf_error(err: libc::c_int) -> HackRFError {
-    let s = unsafe {
-        let ptr = ffi::hackrf_error_name(err);
-        std::c_str::CString::new(ptr, false)
-    };
-    HackRFError {errno: err as int, errstr: s.as_str().unwrap().to_string()}
+    backend: Box<dyn Backend>,
+    /// The last `RadioConfig` applied, kept up to date by the `set_*`
+    /// calls since.
+    config: Option<RadioConfig>
 }
 
 /// Initialise the HackRF library. Call this once at application startup.
+/// With the `dynamic-loading` feature this first loads libhackrf, returning
+/// `HackRFError::LibraryNotFound` if it is not installed.
 pub fn init() -> Result<(), HackRFError> {
+    ffi::load()?;
     match unsafe { ffi::hackrf_init() } {
         ffi::HACKRF_SUCCESS => Ok(()),
-        err => Err(hackrf_error(err))
+        err => Err(HackRFError::from_code(err))
     }
 }
 
 /// De-initialise the HackRF library. Call this once at application
 /// termination.
 pub fn exit() -> Result<(), HackRFError> {
+    ffi::load()?;
     match unsafe { ffi::hackrf_exit() } {
         ffi::HACKRF_SUCCESS => Ok(()),
-        err => Err(hackrf_error(err))
+        err => Err(HackRFError::from_code(err))
     }
 }
 
-/// Attempt to open a connected HackRF device.
-pub fn open() -> Result<HackRFDevice, HackRFError> {
-    let mut device: HackRFDevice = unsafe { std::mem::zeroed() };
-    match unsafe { ffi::hackrf_open(&mut device.ptr) } {
-        ffi::HACKRF_SUCCESS => Ok(device),
-        err => Err(hackrf_error(err))
+impl HackRFDevice {
+    fn from_ptr(ptr: *mut ffi::hackrf_device) -> HackRFDevice {
+        HackRFDevice::from_backend(LibHackRF::from_ptr(ptr))
+    }
+
+    /// Wrap any `Backend`, such as a `SimulatedHackRF`, as a device.
+    pub fn from_backend<B>(backend: B) -> HackRFDevice
+        where B: Backend + 'static
+    {
+        HackRFDevice { backend: Box::new(backend), config: None }
+    }
+
+    /// The `RadioConfig` last applied to this device, updated by any
+    /// `set_freq`, `set_sample_rate`, gain, filter, amplifier or antenna
+    /// power calls since, or `None` if none has been applied.
+    pub fn radio_config(&self) -> Option<RadioConfig> {
+        self.config
     }
-}
 
-/// Close a connected HackRF device.
-pub fn close(device: HackRFDevice) -> Result<(), HackRFError> {
-        match unsafe { ffi::hackrf_close(device.ptr) } {
-            ffi::HACKRF_SUCCESS => Ok(()),
-            err => Err(hackrf_error(err))
+    /// Update the snapshot returned by `radio_config`, if there is one.
+    fn remember<F: FnOnce(&mut RadioConfig)>(&mut self, update: F) {
+        if let Some(ref mut config) = self.config {
+            update(config);
         }
-}
+    }
 
-/// The library defines the C callback, which will itself call a closure
-/// inside Rust after resolving memory stuff, so that users don't need to
-/// write unsafe code.
-extern "C" fn rx_cb(transfer: *mut ffi::hackrf_transfer) -> libc::c_int {
-    let data = unsafe { &*transfer };
-    let valid_length = data.valid_length as uint;
-    let buffer: &[u8] = unsafe {
-        std::slice::from_raw_mut_buf(&data.buffer, valid_length)
-    };
-    let cb_ptr = data.rx_ctx as *mut |&[u8]| -> bool;
-    let cb: &mut |&[u8]| -> bool = unsafe { &mut *cb_ptr };
-    match (*cb)(buffer) {
-        true => 0 as libc::c_int,
-        false => 1 as libc::c_int
+    /// Attempt to open a connected HackRF device.
+    pub fn open() -> Result<HackRFDevice, HackRFError> {
+        ffi::load()?;
+        let mut ptr = std::ptr::null_mut();
+        match unsafe { ffi::hackrf_open(&mut ptr) } {
+            ffi::HACKRF_SUCCESS => Ok(HackRFDevice::from_ptr(ptr)),
+            err => Err(HackRFError::from_code(err))
+        }
     }
-}
 
-/// The library defines the C callback, which will itself call a closure
-/// inside Rust after resolving memory stuff, so that users don't need to
-/// write unsafe code.
-extern "C" fn tx_cb(transfer: *mut ffi::hackrf_transfer) -> libc::c_int {
-    let data = unsafe { &*transfer };
-    let buffer_length = data.buffer_length as uint;
-    let buffer: &mut[u8] = unsafe {
-        std::slice::from_raw_mut_buf(&data.buffer, buffer_length)
-    };
-    let cb_ptr = data.tx_ctx as *mut |&mut[u8]| -> bool;
-    let cb: &mut |&mut[u8]| -> bool = unsafe { &mut *cb_ptr };
-    match (*cb)(buffer) {
-        true => 0 as libc::c_int,
-        false => 1 as libc::c_int
+    /// Close a connected HackRF device.
+    pub fn close(mut self) -> Result<(), HackRFError> {
+        self.backend.close()
     }
-}
 
+    /// Begin RX stream.
+    /// `callback` is a closure like:
+    ///     callback(buffer: &[IQ8]) -> bool
+    /// which is given `buffer`, the RX samples, and returns `true` if it
+    /// should continue receiving data or `false` to stop. It may be called
+    /// a few times after returning `false` while the system catches up.
+    /// The closure runs on libhackrf's transfer thread and is owned by the
+    /// device until the next stream replaces it or the device is closed.
+    /// Returns `HackRFError::Busy` if a stream is already set up.
+    /// Use `rx` to get a guard which calls `stop_rx` when dropped.
+    fn start_rx<F>(&mut self, callback: F) -> Result<(), HackRFError>
+        where F: FnMut(&[IQ8]) -> bool + Send + 'static
+    {
+        self.backend.start_rx(Box::new(callback))
+    }
 
-/// Begin RX stream.
-/// `callback` is a borrowed reference to a closure like:
-///     callback(buffer: &[u8]) -> bool
-/// which is given `buffer`, the RX buffer, and returns `true` if it should
-/// continue receiving data or `false` to stop. It may be called a few times
-/// after returning `false` while the system catches up.
-pub fn start_rx(device: &mut HackRFDevice, callback: &mut |&[u8]| -> bool)
-                -> Result<(), HackRFError> {
-    let ctx = (callback as *mut |&[u8]| -> bool) as *mut libc::c_void;
-    match unsafe { ffi::hackrf_start_rx(device.ptr, rx_cb, ctx) } {
-        ffi::HACKRF_SUCCESS => Ok(()),
-        err => Err(hackrf_error(err))
+    /// Stop RX stream. The closure given to `start_rx` is kept until the
+    /// next stream starts, as older libhackrf may still be calling it.
+    fn stop_rx(&mut self) -> Result<(), HackRFError> {
+        self.backend.stop_rx()
     }
-}
 
-/// Stop RX stream
-pub fn stop_rx(device: &mut HackRFDevice) -> Result<(), HackRFError> {
-    match unsafe { ffi::hackrf_stop_rx(device.ptr) } {
-        ffi::HACKRF_SUCCESS => Ok(()),
-        err => Err(hackrf_error(err))
+    /// Begin TX stream
+    /// `callback` is a closure like:
+    ///     callback(buffer: &mut [IQ8]) -> bool
+    /// which is given `buffer`, the TX samples, and returns `true` if it
+    /// should continue sending data or `false` to stop. It may be called a
+    /// few times after returning `false` while the system catches up.
+    /// Modify the TX slice at leisure and it will be transmitted over the
+    /// radio. The closure runs on libhackrf's transfer thread and is owned
+    /// by the device until the next stream replaces it or the device is
+    /// closed.
+    /// Returns `HackRFError::Busy` if a stream is already set up.
+    /// Use `tx` to get a guard which calls `stop_tx` when dropped.
+    fn start_tx<F>(&mut self, callback: F) -> Result<(), HackRFError>
+        where F: FnMut(&mut [IQ8]) -> bool + Send + 'static
+    {
+        self.backend.start_tx(Box::new(callback))
     }
-}
 
-/// Begin TX stream
-/// `callback` is a borrowed reference to a closure like:
-///     callback(buffer: &mut[u8]) -> bool
-/// which is given `buffer`, the TX buffer, and returns `true` if it should
-/// continue sending data or `false` to stop. It may be called a few times
-/// after returning `false` while the system catches up.
-/// Modify the TX slice at leisure and it will be transmitted over the radio.
-pub fn start_tx(device: &mut HackRFDevice, callback: &mut |&mut[u8]| -> bool)
-                -> Result<(), HackRFError> {
-    let ctx = (callback as *mut |&mut[u8]| -> bool) as *mut libc::c_void;
-    match unsafe { ffi::hackrf_start_tx(device.ptr, tx_cb, ctx) } {
-        ffi::HACKRF_SUCCESS => Ok(()),
-        err => Err(hackrf_error(err))
+    /// Stop TX stream. The closure given to `start_tx` is kept until the
+    /// next stream starts, as older libhackrf may still be calling it.
+    fn stop_tx(&mut self) -> Result<(), HackRFError> {
+        self.backend.stop_tx()
     }
-}
 
-/// Stop TX stream
-pub fn stop_tx(device: &mut HackRFDevice) -> Result<(), HackRFError> {
-    match unsafe { ffi::hackrf_stop_tx(device.ptr) } {
-        ffi::HACKRF_SUCCESS => Ok(()),
-        err => Err(hackrf_error(err))
+    /// Check if a HackRF device is currently streaming data.
+    /// Returns true if so, false if stopped due to streaming finishing
+    /// or exit being called, and an error if not streaming due to error.
+    pub fn is_streaming(&mut self) -> Result<bool, HackRFError> {
+        self.backend.is_streaming()
     }
-}
 
-/// Check if a HackRF device is currently streaming data.
-/// Returns true if so, false if stopped due to streaming finishing
-/// or exit being called, and an error if not streaming due to error.
-pub fn is_streaming(device: &mut HackRFDevice) -> Result<bool, HackRFError> {
-    match unsafe { ffi::hackrf_is_streaming(device.ptr) } {
-        ffi::HACKRF_TRUE => Ok(true),
-        ffi::HACKRF_ERROR_STREAMING_STOPPED |
-        ffi::HACKRF_ERROR_STREAMING_EXIT_CALLED => Ok(false),
-        err => Err(hackrf_error(err))
+    /// Set the HackRF baseband filter bandwidth, in Hz.
+    /// See also `compute_baseband_filter_bw` and
+    /// `compute_baseband_filter_bw_round_down_lt`.
+    pub fn set_baseband_filter_bandwidth(&mut self, bandwidth_hz: u32)
+                                         -> Result<(), HackRFError> {
+        self.backend.set_baseband_filter_bandwidth(bandwidth_hz)?;
+        self.remember(|c| c.baseband_filter_bandwidth = bandwidth_hz);
+        Ok(())
     }
-}
 
-/// Set the HackRF baseband filter bandwidth, in Hz.
-/// See also `compute_baseband_filter_bw` and
-/// `compute_baseband_filter_bw_round_down_lt`.
-pub fn set_baseband_filter_bandwidth(device: &mut HackRFDevice,
-                                     bandwidth_hz: uint)
-                                     -> Result<(), HackRFError> {
-    match unsafe { ffi::hackrf_set_baseband_filter_bandwidth(
-                            device.ptr, bandwidth_hz as libc::uint32_t) } {
-        ffi::HACKRF_SUCCESS => Ok(()),
-        err => Err(hackrf_error(err))
+    /// Read the board ID. This is the product identifier, not a serial
+    /// number; its `Display` gives the board's name.
+    pub fn board_id_read(&mut self) -> Result<BoardId, HackRFError> {
+        self.backend.board_id_read().map(BoardId::from)
     }
-}
 
-/// Read the board ID. Returns a tuple of the numeric ID and a corresponding
-/// String. This is the product identifier, not a serial number.
-pub fn board_id_read(device: &mut HackRFDevice) 
-                     -> Result<(int, String), HackRFError> {
-    let mut id: libc::uint8_t = ffi::BOARD_ID_INVALID;
-    match unsafe { ffi::hackrf_board_id_read(device.ptr, &mut id) } {
-        ffi::HACKRF_SUCCESS => {
-            let s = unsafe {
-                let ptr = ffi::hackrf_board_id_name(id as libc::uint8_t);
-                std::c_str::CString::new(ptr, false)
-            };
-            Ok((id as int, s.as_str().unwrap().to_string()))
-        },
-        err => Err(hackrf_error(err))
+    /// Read the board's firmware version string.
+    pub fn version_string_read(&mut self) -> Result<String, HackRFError> {
+        self.backend.version_string_read()
     }
-}
 
+    /// Read the part ID and serial number
+    pub fn board_partid_serialno_read(&mut self)
+                                      -> Result<(PartId, SerialNumber),
+                                                HackRFError> {
+        let (part_id, serial_no) = self.backend.board_partid_serialno_read()?;
+        Ok((PartId(part_id), SerialNumber(serial_no)))
+    }
 
-/// Read the board's firmware version string.
-pub fn version_string_read(device: &mut HackRFDevice)
-                           -> Result<String, HackRFError> {
-    let mut buf = [0i8, ..127];
-    match unsafe { ffi::hackrf_version_string_read(device.ptr,
-                                                         buf.as_mut_ptr(),
-                                                         127) } {
-        ffi::HACKRF_SUCCESS => {
-            let s = unsafe {
-                std::str::from_utf8(std::mem::transmute(buf.as_slice()))};
-            Ok(String::from_str(s.unwrap()))
-        },
-        err => Err(hackrf_error(err))
+    /// Read the USB API version the firmware implements, as binary-coded
+    /// decimal: 0x0108 is version 1.08.
+    /// Returns `HackRFError::Unsupported` if libhackrf is too old.
+    pub fn usb_api_version_read(&mut self) -> Result<u16, HackRFError> {
+        self.backend.usb_api_version_read()
     }
-}
 
-/// Read the part ID and serial number
-pub fn board_partid_serialno_read(device: &mut HackRFDevice)
-                                  -> Result<([u32, ..2], [u32, ..4]),
-                                              HackRFError> {
-    let mut serial: ffi::read_partid_serialno_t = unsafe {
-        std::mem::zeroed() };
-    match unsafe { ffi::hackrf_board_partid_serialno_read(device.ptr,
-                                                          &mut serial) } {
-        ffi::HACKRF_SUCCESS => Ok((serial.part_id, serial.serial_no)),
-        err => Err(hackrf_error(err))
+    /// Read the hardware revision of a HackRF One. Returns a tuple of the
+    /// numeric revision and its name, such as "r9"; boards made by Great
+    /// Scott Gadgets have bit 7 set.
+    /// Returns `HackRFError::Unsupported` if libhackrf is too old, and an
+    /// error from the firmware if it cannot detect the revision.
+    pub fn board_rev_read(&mut self) -> Result<(u8, String), HackRFError> {
+        let rev = self.backend.board_rev_read()?;
+        Ok((rev, board_rev_name(rev)))
     }
-}
 
-/// Set HackRF frequency
-pub fn set_freq(device: &mut HackRFDevice, freq_hz: u64)
-                -> Result<(), HackRFError> {
-    match unsafe { ffi::hackrf_set_freq(device.ptr, freq_hz) } {
-        ffi::HACKRF_SUCCESS => Ok(()),
-        err => Err(hackrf_error(err))
+    /// Set HackRF frequency
+    pub fn set_freq(&mut self, freq_hz: u64) -> Result<(), HackRFError> {
+        self.backend.set_freq(freq_hz)?;
+        self.remember(|c| c.freq_hz = freq_hz);
+        Ok(())
     }
-}
 
-#[deriving(Copy, Clone)]
-pub enum RFPathFilter {Bypass, LowPass, HighPass}
+    /// Set HackRF frequency, specifying IF and LO and filters separately.
+    /// `path` may be `RFPathFilter::Bypass`, `LowPass` or `HighPass`.
+    pub fn set_freq_explicit(&mut self, if_freq_hz: u64, lo_freq_hz: u64,
+                             path: RFPathFilter) -> Result<(), HackRFError> {
+        self.backend.set_freq_explicit(if_freq_hz, lo_freq_hz, path)?;
+        let freq_hz = path.rf_freq_hz(if_freq_hz, lo_freq_hz);
+        self.remember(|c| c.freq_hz = freq_hz);
+        Ok(())
+    }
 
-/// Set HackRF frequency, specifying IF and LO and filters separately.
-/// `path` may be `RFPathFilter::Bypass`, `LowPass` or `HighPass`.
-pub fn set_freq_explicit(device: &mut HackRFDevice, if_freq_hz: u64,
-                         lo_freq_hz: u64, path: RFPathFilter)
-                         -> Result<(), HackRFError> {
-    let c_path = match path {
-        RFPathFilter::Bypass => ffi::RF_PATH_FILTER_BYPASS,
-        RFPathFilter::LowPass => ffi::RF_PATH_FILTER_LOW_PASS,
-        RFPathFilter::HighPass => ffi::RF_PATH_FILTER_HIGH_PASS
-    };
-    match unsafe { ffi::hackrf_set_freq_explicit(device.ptr, if_freq_hz,
-                                                 lo_freq_hz, c_path) } {
-        ffi::HACKRF_SUCCESS => Ok(()),
-        err => Err(hackrf_error(err))
+    /// Set HackRF sample rate, specifying integer frequency and divider
+    /// Preferred rates are 8, 10, 12.5, 16 and 20MHz
+    /// libhackrf also sets the baseband filter to suit the new rate.
+    pub fn set_sample_rate_manual(&mut self, freq_hz: u32, divider: u32)
+                                  -> Result<(), HackRFError> {
+        self.backend.set_sample_rate_manual(freq_hz, divider)?;
+        let rate = freq_hz as f64 / divider as f64;
+        self.remember(|c| {
+            c.sample_rate = rate;
+            c.baseband_filter_bandwidth = config::default_filter(rate);
+        });
+        Ok(())
     }
-}
 
-/// Set HackRF sample rate, specifying integer frequency and divider
-/// Preferred rates are 8, 10, 12.5, 16 and 20MHz
-pub fn set_sample_rate_manual(device: &mut HackRFDevice, freq_hz: u32,
-                              divider: u32) -> Result<(), HackRFError> {
-    match unsafe { ffi::hackrf_set_sample_rate_manual(device.ptr, freq_hz,
-                                                      divider) } {
-        ffi::HACKRF_SUCCESS => Ok(()),
-        err => Err(hackrf_error(err))
+    /// Set HackRF sample rate, specifying frequency as a double float
+    /// Preferred rates are 8, 10, 12.5, 16 and 20MHz
+    /// libhackrf also sets the baseband filter to suit the new rate.
+    pub fn set_sample_rate(&mut self, freq_hz: f64)
+                           -> Result<(), HackRFError> {
+        self.backend.set_sample_rate(freq_hz)?;
+        self.remember(|c| {
+            c.sample_rate = freq_hz;
+            c.baseband_filter_bandwidth = config::default_filter(freq_hz);
+        });
+        Ok(())
     }
-}
 
-/// Set HackRF sample rate, specifying frequency as a double float
-/// Preferred rates are 8, 10, 12.5, 16 and 20MHz
-pub fn set_sample_rate(device: &mut HackRFDevice, freq_hz: f64)
-                       -> Result<(), HackRFError> {
-    match unsafe { ffi::hackrf_set_sample_rate(device.ptr, freq_hz) } {
-        ffi::HACKRF_SUCCESS => Ok(()),
-        err => Err(hackrf_error(err))
+    /// Set HackRF external amplifier on or off
+    pub fn set_amp_enable(&mut self, on: bool) -> Result<(), HackRFError> {
+        self.backend.set_amp_enable(on)?;
+        self.remember(|c| c.amp_enable = on);
+        Ok(())
     }
-}
 
-/// Set HackRF external amplifier on or off
-pub fn set_amp_enable(device: &mut HackRFDevice, on: bool)
-                      -> Result<(), HackRFError> {
-    let value = match on { false => 0u8, true => 1 };
-    match unsafe { ffi::hackrf_set_amp_enable(device.ptr, value) } {
-        ffi::HACKRF_SUCCESS => Ok(()),
-        err => Err(hackrf_error(err))
+    /// Set LNA gain, 0-40 in steps of 8dB.
+    /// Returns `HackRFError::InvalidParam` if `gain` is out of range.
+    pub fn set_lna_gain(&mut self, gain: u32) -> Result<(), HackRFError> {
+        if gain > 40 {
+            return Err(HackRFError::InvalidParam);
+        }
+        self.backend.set_lna_gain(gain)?;
+        self.remember(|c| c.lna_gain = gain);
+        Ok(())
     }
-}
 
-/// Set LNA gain, 0-40 in steps of 8dB
-pub fn set_lna_gain(device: &mut HackRFDevice, gain: u32)
-                    -> Result<(), HackRFError> {
-    assert!(gain <= 40);
-    match unsafe { ffi::hackrf_set_lna_gain(device.ptr, gain) } {
-        ffi::HACKRF_SUCCESS => Ok(()),
-        err => Err(hackrf_error(err))
+    /// Set VGA gain, 0-62 in steps of 2dB.
+    /// Returns `HackRFError::InvalidParam` if `gain` is out of range.
+    pub fn set_vga_gain(&mut self, gain: u32) -> Result<(), HackRFError> {
+        if gain > 62 {
+            return Err(HackRFError::InvalidParam);
+        }
+        self.backend.set_vga_gain(gain)?;
+        self.remember(|c| c.vga_gain = gain);
+        Ok(())
+    }
+
+    /// Set TXVGA gain, 0-47 in steps of 1dB.
+    /// Returns `HackRFError::InvalidParam` if `gain` is out of range.
+    pub fn set_txvga_gain(&mut self, gain: u32) -> Result<(), HackRFError> {
+        if gain > 47 {
+            return Err(HackRFError::InvalidParam);
+        }
+        self.backend.set_txvga_gain(gain)?;
+        self.remember(|c| c.txvga_gain = gain);
+        Ok(())
+    }
+
+    /// Set antenna port power on/off
+    pub fn set_antenna_enable(&mut self, on: bool)
+                              -> Result<(), HackRFError> {
+        self.backend.set_antenna_enable(on)?;
+        self.remember(|c| c.antenna_enable = on);
+        Ok(())
     }
 }
 
-/// Set VGA gain, 0-62 in steps of 2dB
-pub fn set_vga_gain(device: &mut HackRFDevice, gain: u32)
-                    -> Result<(), HackRFError> {
-    assert!(gain <= 62);
-    match unsafe { ffi::hackrf_set_vga_gain(device.ptr, gain) } {
-        ffi::HACKRF_SUCCESS => Ok(()),
-        err => Err(hackrf_error(err))
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum RFPathFilter {Bypass, LowPass, HighPass}
+
+impl RFPathFilter {
+    /// The RF frequency received with IF `if_freq_hz` and LO `lo_freq_hz`
+    /// on this path. Through the low-pass filter either mixing product
+    /// reaches the IF, so the LO may be above or below it.
+    pub(crate) fn rf_freq_hz(self, if_freq_hz: u64, lo_freq_hz: u64) -> u64 {
+        match self {
+            RFPathFilter::Bypass => if_freq_hz,
+            RFPathFilter::LowPass => lo_freq_hz.abs_diff(if_freq_hz),
+            RFPathFilter::HighPass => if_freq_hz + lo_freq_hz
+        }
     }
 }
 
-/// Set TXVGA gain, 0-47 in steps of 1dB
-pub fn set_txvga_gain(device: &mut HackRFDevice, gain: u32)
-                    -> Result<(), HackRFError> {
-    assert!(gain <= 47);
-    match unsafe { ffi::hackrf_set_txvga_gain(device.ptr, gain) } {
-        ffi::HACKRF_SUCCESS => Ok(()),
-        err => Err(hackrf_error(err))
+/// Optional libhackrf functions, which older versions of the library lack.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Capabilities {
+    /// Frequency sweeping with `hackrf_init_sweep`.
+    pub sweep: bool,
+    /// Control of Opera Cake antenna switches.
+    pub operacake: bool,
+    /// Frequency and time based Opera Cake switching.
+    pub operacake_switching: bool,
+    /// Enabling the CLKOUT port.
+    pub clkout: bool,
+    /// Detecting a clock on CLKIN.
+    pub clkin_status: bool,
+    /// Hardware triggered streaming.
+    pub hw_sync: bool,
+    /// Reading the hardware revision with `board_rev_read`.
+    pub board_rev: bool
+}
+
+/// Report which optional functions the installed libhackrf provides.
+/// Without the `dynamic-loading` feature the binary only starts if they are
+/// all present, so every capability is reported.
+pub fn capabilities() -> Capabilities {
+    Capabilities {
+        sweep: ffi::provides("hackrf_init_sweep") &&
+               ffi::provides("hackrf_start_rx_sweep"),
+        operacake: ffi::provides("hackrf_get_operacake_boards") &&
+                   ffi::provides("hackrf_set_operacake_ports"),
+        operacake_switching:
+            ffi::provides("hackrf_set_operacake_mode") &&
+            ffi::provides("hackrf_get_operacake_mode") &&
+            ffi::provides("hackrf_set_operacake_freq_ranges") &&
+            ffi::provides("hackrf_set_operacake_dwell_times"),
+        clkout: ffi::provides("hackrf_set_clkout_enable"),
+        clkin_status: ffi::provides("hackrf_get_clkin_status"),
+        hw_sync: ffi::provides("hackrf_set_hw_sync_mode"),
+        board_rev: ffi::provides("hackrf_board_rev_read")
     }
 }
 
-/// Set antenna port power on/off
-pub fn set_antenna_enable(device: &mut HackRFDevice, on: bool)
-                      -> Result<(), HackRFError> {
-    let value = match on { false => 0u8, true => 1 };
-    match unsafe { ffi::hackrf_set_antenna_enable(device.ptr, value) } {
-        ffi::HACKRF_SUCCESS => Ok(()),
-        err => Err(hackrf_error(err))
+/// The name `hackrf_board_rev_name` gives a hardware revision. That function
+/// is newer than the others, so it is reimplemented here.
+fn board_rev_name(rev: u8) -> String {
+    match rev {
+        ffi::BOARD_REV_UNRECOGNIZED => String::from("unrecognized"),
+        ffi::BOARD_REV_UNDETECTED => String::from("undetected"),
+        _ => match rev & !ffi::BOARD_REV_GSG {
+            ffi::BOARD_REV_HACKRF1_OLD => String::from("older than r6"),
+            r @ ffi::BOARD_REV_HACKRF1_R6..=ffi::BOARD_REV_HACKRF1_R10 =>
+                format!("r{}", r + 5),
+            _ => String::from("unknown")
+        }
     }
 }
 
 /// Compute nearest frequency for bandwidth filter (manual filter)
+/// This matches libhackrf but does not need it to be installed.
 pub fn compute_baseband_filter_bw_round_down_lt(bandwidth_hz: u32) -> u32 {
-    unsafe {
-        ffi::hackrf_compute_baseband_filter_bw_round_down_lt(bandwidth_hz)
-    }
+    config::filter_below(bandwidth_hz)
 }
 
 /// Compute best default value for bandwidth filter depending on sample rate
+/// This matches libhackrf but does not need it to be installed.
 pub fn compute_baseband_filter_bw(bandwidth_hz: u32) -> u32 {
-    unsafe {
-        ffi::hackrf_compute_baseband_filter_bw(bandwidth_hz)
-    }
+    config::filter_at_most(bandwidth_hz)
 }
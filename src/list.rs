@@ -0,0 +1,184 @@
+// Enumeration of attached HackRF devices
+// Copyright Adam Greig <adam@adamgreig.com> 2014
+// Licensed under MIT license
+
+use std::ffi::{CStr, CString};
+use std::fmt;
+
+use crate::{ffi, HackRFDevice, HackRFError};
+
+/// The USB product ID a board enumerates with.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum UsbBoardId {
+    Jawbreaker,
+    HackRFOne,
+    Rad1o,
+    Unknown(u16),
+}
+
+impl UsbBoardId {
+    pub(crate) fn from_raw(id: libc::c_int) -> UsbBoardId {
+        match id {
+            ffi::USB_BOARD_ID_JAWBREAKER => UsbBoardId::Jawbreaker,
+            ffi::USB_BOARD_ID_HACKRF_ONE => UsbBoardId::HackRFOne,
+            ffi::USB_BOARD_ID_RAD1O => UsbBoardId::Rad1o,
+            id => UsbBoardId::Unknown(id as u16),
+        }
+    }
+}
+
+impl fmt::Display for UsbBoardId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            UsbBoardId::Jawbreaker => write!(f, "Jawbreaker"),
+            UsbBoardId::HackRFOne => write!(f, "HackRF One"),
+            UsbBoardId::Rad1o => write!(f, "rad1o"),
+            UsbBoardId::Unknown(id) => write!(f, "unknown (0x{:04x})", id),
+        }
+    }
+}
+
+/// Description of one HackRF found by `DeviceList`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DeviceInfo {
+    /// Position of this device in the list, as passed to `DeviceList::open`.
+    pub index: usize,
+    /// The USB serial number string, if the firmware reports one.
+    pub serial_number: Option<String>,
+    /// The USB product ID the board enumerated with.
+    pub usb_board_id: UsbBoardId,
+    /// USB bus the device is attached to.
+    pub bus_number: u8,
+    /// Hub port numbers from the root hub down to the device. Together with
+    /// `bus_number` this identifies a physical USB socket, so it stays the
+    /// same across reboots and re-plugs into the same port.
+    pub port_numbers: Vec<u8>,
+    /// USB device address, which changes every time the device enumerates.
+    pub device_address: u8,
+}
+
+impl DeviceInfo {
+    /// The bus position formatted like Linux sysfs, e.g. `1-2.4`.
+    pub fn bus_path(&self) -> String {
+        let ports: Vec<String> = self.port_numbers.iter()
+                                     .map(|p| p.to_string()).collect();
+        format!("{}-{}", self.bus_number, ports.join("."))
+    }
+}
+
+/// A snapshot of the HackRFs attached to this host.
+pub struct DeviceList {
+    ptr: *mut ffi::hackrf_device_list_t
+}
+
+impl Drop for DeviceList {
+    fn drop(&mut self) {
+        unsafe {
+            ffi::hackrf_device_list_free(self.ptr);
+        }
+    }
+}
+
+impl DeviceList {
+    /// Enumerate all connected HackRF devices.
+    /// `init` must have been called first.
+    pub fn new() -> Result<DeviceList, HackRFError> {
+        ffi::load()?;
+        let ptr = unsafe { ffi::hackrf_device_list() };
+        if ptr.is_null() {
+            Err(HackRFError::NoMem)
+        } else {
+            Ok(DeviceList { ptr })
+        }
+    }
+
+    /// Number of HackRFs found.
+    pub fn len(&self) -> usize {
+        unsafe { (*self.ptr).devicecount as usize }
+    }
+
+    /// True if no HackRFs were found.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Describe the device at `index`, or `None` if out of range.
+    pub fn get(&self, index: usize) -> Option<DeviceInfo> {
+        if index >= self.len() {
+            return None;
+        }
+        let list = unsafe { &*self.ptr };
+        let serial_number = unsafe {
+            let ptr = *list.serial_numbers.add(index);
+            if ptr.is_null() {
+                None
+            } else {
+                Some(CStr::from_ptr(ptr).to_string_lossy().into_owned())
+            }
+        };
+        let usb_board_id = UsbBoardId::from_raw(unsafe {
+            *list.usb_board_ids.add(index)
+        });
+        let usb_device = unsafe {
+            let usb_index = *list.usb_device_index.add(index) as usize;
+            *list.usb_devices.add(usb_index)
+        };
+        let mut ports = [0u8; 7];
+        let (bus_number, device_address, nports) = unsafe {
+            (ffi::libusb_get_bus_number(usb_device),
+             ffi::libusb_get_device_address(usb_device),
+             ffi::libusb_get_port_numbers(usb_device, ports.as_mut_ptr(),
+                                          ports.len() as libc::c_int))
+        };
+        let port_numbers = ports[..nports.max(0) as usize].to_vec();
+        Some(DeviceInfo { index, serial_number, usb_board_id, bus_number,
+                          port_numbers, device_address })
+    }
+
+    /// Iterate over descriptions of every device found.
+    pub fn iter(&self) -> impl Iterator<Item=DeviceInfo> + '_ {
+        (0..self.len()).filter_map(move |i| self.get(i))
+    }
+
+    /// Find the device whose serial number ends with `serial`, matching the
+    /// way `hackrf_open_by_serial` accepts a suffix of the full serial.
+    /// As there, the comparison is case-sensitive.
+    pub fn find_serial(&self, serial: &str) -> Option<DeviceInfo> {
+        self.iter().find(|d| match d.serial_number {
+            Some(ref s) => s.ends_with(serial),
+            None => false,
+        })
+    }
+
+    /// Open the device at `index`.
+    pub fn open(&self, index: usize) -> Result<HackRFDevice, HackRFError> {
+        if index >= self.len() {
+            return Err(HackRFError::InvalidParam);
+        }
+        let mut ptr = std::ptr::null_mut();
+        match unsafe { ffi::hackrf_device_list_open(self.ptr,
+                                                    index as libc::c_int,
+                                                    &mut ptr) } {
+            ffi::HACKRF_SUCCESS => Ok(HackRFDevice::from_ptr(ptr)),
+            err => Err(HackRFError::from_code(err))
+        }
+    }
+}
+
+impl HackRFDevice {
+    /// Open the connected HackRF whose serial number ends with `serial`.
+    /// Serial numbers are as reported by `DeviceList` or `hackrf_info`.
+    pub fn open_by_serial(serial: &str) -> Result<HackRFDevice, HackRFError> {
+        let c_serial = match CString::new(serial) {
+            Ok(s) => s,
+            Err(_) => return Err(HackRFError::InvalidParam)
+        };
+        ffi::load()?;
+        let mut ptr = std::ptr::null_mut();
+        match unsafe { ffi::hackrf_open_by_serial(c_serial.as_ptr(),
+                                                  &mut ptr) } {
+            ffi::HACKRF_SUCCESS => Ok(HackRFDevice::from_ptr(ptr)),
+            err => Err(HackRFError::from_code(err))
+        }
+    }
+}
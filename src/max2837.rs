@@ -0,0 +1,181 @@
+// MAX2837 transceiver register access
+// Copyright Adam Greig <adam@adamgreig.com> 2014
+// Licensed under MIT license
+
+use std::fmt;
+
+use crate::{HackRFDevice, HackRFError};
+
+/// Number of MAX2837 registers.
+pub const MAX2837_NUM_REGS: usize = 32;
+
+/// Largest value a MAX2837 register holds; registers are 10 bits wide.
+pub const MAX2837_MAX_VALUE: u16 = 0x3ff;
+
+/// The baseband lowpass filter bandwidths, indexed by the 4-bit FT field.
+pub const MAX2837_FT_BANDWIDTHS_HZ: [u32; 16] = [
+    1_750_000, 2_500_000, 3_500_000, 5_000_000, 5_500_000, 6_000_000,
+    7_000_000, 8_000_000, 9_000_000, 10_000_000, 12_000_000, 14_000_000,
+    15_000_000, 20_000_000, 24_000_000, 28_000_000
+];
+
+/// A bit field within one register: (register, lowest bit, width).
+type Field = (usize, u32, u32);
+
+/// LNA gain, with its SPI enable at bit 1.
+const LNA_GAIN: Field = (0, 2, 3);
+const LNA_GAIN_SPI_EN: Field = (0, 1, 1);
+const LNA_BAND: Field = (0, 5, 2);
+const VGA_GAIN: Field = (5, 0, 5);
+const VGA_GAIN_SPI_EN: Field = (5, 5, 1);
+const FT: Field = (8, 0, 4);
+const TXVGA_GAIN: Field = (29, 0, 6);
+const TXVGA_GAIN_SPI_EN: Field = (29, 6, 1);
+
+/// LNA gain field values and the gain in dB each selects.
+const LNA_GAINS: [(u16, u32); 6] = [
+    (0, 40), (2, 32), (3, 24), (4, 16), (6, 8), (7, 0)
+];
+
+/// A dump of every MAX2837 register, with the fields that `set_lna_gain`,
+/// `set_vga_gain`, `set_txvga_gain` and `set_baseband_filter_bandwidth`
+/// program decoded.
+/// Displays like `hackrf_debug --max2837 --read`, one register per line.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Max2837Registers {
+    pub values: [u16; MAX2837_NUM_REGS]
+}
+
+impl Max2837Registers {
+    pub fn new(values: [u16; MAX2837_NUM_REGS]) -> Max2837Registers {
+        Max2837Registers { values }
+    }
+
+    fn get(&self, (reg, shift, width): Field) -> u16 {
+        (self.values[reg] >> shift) & ((1 << width) - 1)
+    }
+
+    fn set(&mut self, (reg, shift, width): Field, value: u16) {
+        let mask = ((1 << width) - 1) << shift;
+        self.values[reg] = (self.values[reg] & !mask)
+                           | ((value << shift) & mask);
+    }
+
+    /// The RF band the LNA is matched for, as (low, high) in MHz.
+    pub fn lna_band_mhz(&self) -> (u32, u32) {
+        let low = 2300 + 100 * self.get(LNA_BAND) as u32;
+        (low, low + 100)
+    }
+
+    /// LNA gain in dB, or `None` if the field holds a reserved value.
+    pub fn lna_gain_db(&self) -> Option<u32> {
+        let field = self.get(LNA_GAIN);
+        LNA_GAINS.iter().find(|&&(v, _)| v == field).map(|&(_, db)| db)
+    }
+
+    /// RX VGA gain in dB, 0-62.
+    pub fn vga_gain_db(&self) -> u32 {
+        2 * (31 - self.get(VGA_GAIN) as u32)
+    }
+
+    /// TX VGA gain in dB. Values above 47 are not used by the firmware.
+    pub fn txvga_gain_db(&self) -> u32 {
+        47u32.saturating_sub(self.get(TXVGA_GAIN) as u32)
+    }
+
+    /// Baseband lowpass filter bandwidth in Hz.
+    pub fn baseband_filter_bandwidth(&self) -> u32 {
+        MAX2837_FT_BANDWIDTHS_HZ[self.get(FT) as usize]
+    }
+
+    /// Registers whose values differ from `other`, as
+    /// (register, this value, other value).
+    pub fn diff(&self, other: &Max2837Registers) -> Vec<(u8, u16, u16)> {
+        self.values.iter().zip(&other.values).enumerate()
+            .filter(|&(_, (a, b))| a != b)
+            .map(|(reg, (&a, &b))| (reg as u8, a, b))
+            .collect()
+    }
+
+    /// Program the fields as the firmware does for these gains, which must
+    /// already be in range.
+    pub(crate) fn set_lna_gain_db(&mut self, gain: u32) {
+        let &(field, _) = LNA_GAINS.iter().find(|&&(_, db)| db <= gain)
+                                   .unwrap_or(&LNA_GAINS[5]);
+        self.set(LNA_GAIN_SPI_EN, 1);
+        self.set(LNA_GAIN, field);
+    }
+
+    pub(crate) fn set_vga_gain_db(&mut self, gain: u32) {
+        self.set(VGA_GAIN_SPI_EN, 1);
+        self.set(VGA_GAIN, 31 - (gain >> 1) as u16);
+    }
+
+    pub(crate) fn set_txvga_gain_db(&mut self, gain: u32) {
+        self.set(TXVGA_GAIN_SPI_EN, 1);
+        self.set(TXVGA_GAIN, 47 - gain as u16);
+    }
+
+    /// Select the narrowest filter at least `bandwidth_hz` wide, returning
+    /// its bandwidth, or `None` if there is none.
+    pub(crate) fn set_baseband_filter_bandwidth(&mut self, bandwidth_hz: u32)
+                                                -> Option<u32> {
+        let ft = MAX2837_FT_BANDWIDTHS_HZ.iter()
+            .position(|&bw| bw >= bandwidth_hz)?;
+        self.set(FT, ft as u16);
+        Some(MAX2837_FT_BANDWIDTHS_HZ[ft])
+    }
+
+    /// Match the LNA to an IF frequency in Hz.
+    pub(crate) fn set_lna_band(&mut self, if_freq_hz: u64) {
+        let band = match if_freq_hz {
+            0..=2_399_999_999 => 0,
+            2_400_000_000..=2_499_999_999 => 1,
+            2_500_000_000..=2_599_999_999 => 2,
+            _ => 3
+        };
+        self.set(LNA_BAND, band);
+    }
+}
+
+impl fmt::Display for Max2837Registers {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (reg, value) in self.values.iter().enumerate() {
+            writeln!(f, "[{:2}] -> 0x{:03x}", reg, value)?;
+        }
+        Ok(())
+    }
+}
+
+impl HackRFDevice {
+    /// Read one MAX2837 register.
+    /// Returns `HackRFError::InvalidParam` if `register` is 32 or more.
+    pub fn max2837_read(&mut self, register: u8) -> Result<u16, HackRFError> {
+        if register as usize >= MAX2837_NUM_REGS {
+            return Err(HackRFError::InvalidParam);
+        }
+        self.backend.max2837_read(register)
+    }
+
+    /// Write one MAX2837 register. Later calls such as `set_lna_gain` may
+    /// overwrite it.
+    /// Returns `HackRFError::InvalidParam` if `register` is 32 or more or
+    /// `value` is wider than 10 bits.
+    pub fn max2837_write(&mut self, register: u8, value: u16)
+                         -> Result<(), HackRFError> {
+        if register as usize >= MAX2837_NUM_REGS ||
+           value > MAX2837_MAX_VALUE {
+            return Err(HackRFError::InvalidParam);
+        }
+        self.backend.max2837_write(register, value)
+    }
+
+    /// Read every MAX2837 register.
+    pub fn max2837_dump(&mut self) -> Result<Max2837Registers, HackRFError> {
+        let mut registers = Max2837Registers::default();
+        for (reg, value) in registers.values.iter_mut().enumerate() {
+            *value = self.max2837_read(reg as u8)?;
+        }
+        Ok(registers)
+    }
+}
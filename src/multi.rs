@@ -0,0 +1,160 @@
+// Coherent RX from several HackRFs started together
+// Copyright Adam Greig <adam@adamgreig.com> 2014
+// Licensed under MIT license
+
+use crate::buffer::SampleBuffer;
+use crate::clock::{reset_clock, start_synchronised_rx, SyncError};
+use crate::config::RadioConfig;
+use crate::stream::RxStream;
+use crate::{HackRFDevice, HackRFError};
+
+/// A buffer from one device of a `MultiDeviceRx`.
+pub struct TaggedBuffer {
+    /// Which device it came from, in the order the session was opened;
+    /// the leader is 0.
+    pub device: usize,
+    /// Index of the buffer's first sample since the synchronised start,
+    /// counting samples in dropped transfers. Buffers from different
+    /// devices with the same index were sampled at the same instant.
+    pub sample_index: u64,
+    pub buffer: SampleBuffer
+}
+
+/// A set of HackRFs wired for coherent reception as described in the
+/// `clock` module, configured alike and started on one trigger edge.
+pub struct MultiDeviceRx {
+    devices: Vec<HackRFDevice>
+}
+
+impl MultiDeviceRx {
+    /// Open the HackRFs whose serial numbers end with each of `serials`,
+    /// the first being the leader, and apply `config` to each.
+    /// Returns `HackRFError::InvalidParam` if `serials` is empty.
+    pub fn open<S: AsRef<str>>(serials: &[S], config: &RadioConfig)
+                               -> Result<MultiDeviceRx, HackRFError> {
+        let devices = serials.iter()
+            .map(|serial| HackRFDevice::open_by_serial(serial.as_ref()))
+            .collect::<Result<Vec<_>, _>>()?;
+        MultiDeviceRx::from_devices(devices, config)
+    }
+
+    /// Use already open `devices`, the first being the leader, applying
+    /// `config` to each.
+    /// Returns `HackRFError::InvalidParam` if `devices` is empty.
+    pub fn from_devices(mut devices: Vec<HackRFDevice>, config: &RadioConfig)
+                        -> Result<MultiDeviceRx, HackRFError> {
+        if devices.is_empty() {
+            return Err(HackRFError::InvalidParam);
+        }
+        for device in &mut devices {
+            config.apply(device)?;
+        }
+        Ok(MultiDeviceRx { devices })
+    }
+
+    /// Number of devices, including the leader.
+    pub fn len(&self) -> usize {
+        self.devices.len()
+    }
+
+    /// Always false, since a session has at least a leader.
+    pub fn is_empty(&self) -> bool {
+        self.devices.is_empty()
+    }
+
+    /// The devices, leader first, for adjusting one channel's settings.
+    pub fn devices(&mut self) -> &mut [HackRFDevice] {
+        &mut self.devices
+    }
+
+    /// Apply `config` to every device.
+    pub fn apply(&mut self, config: &RadioConfig) -> Result<(), HackRFError> {
+        self.devices.iter_mut().try_for_each(|device| config.apply(device))
+    }
+
+    /// Start every device with `start_synchronised_rx`, each receiving into
+    /// a pool of `count` buffers.
+    pub fn start(&mut self, count: usize)
+                 -> Result<MultiRxStream<'_>, SyncError> {
+        let (leader, followers) = self.devices.split_first_mut()
+            .ok_or(HackRFError::InvalidParam)?;
+        let streams = start_synchronised_rx(leader, followers, count)?;
+        let next_index = vec![0; streams.len()];
+        Ok(MultiRxStream { streams, next_index })
+    }
+
+    /// Close every device.
+    pub fn close(self) -> Result<(), HackRFError> {
+        self.devices.into_iter().try_for_each(HackRFDevice::close)
+    }
+}
+
+/// The running streams of a `MultiDeviceRx`. Receiving stops when this is
+/// dropped, and every device is put back to the default `ClockConfig`.
+pub struct MultiRxStream<'a> {
+    streams: Vec<RxStream<'a>>,
+    next_index: Vec<u64>
+}
+
+impl<'a> MultiRxStream<'a> {
+    /// Wait for the next buffer from `device`.
+    /// Returns `HackRFError::InvalidParam` if there is no such device.
+    pub fn recv_from(&mut self, device: usize)
+                     -> Result<TaggedBuffer, HackRFError> {
+        let stream = self.streams.get_mut(device)
+            .ok_or(HackRFError::InvalidParam)?;
+        let buffer = stream.recv()?;
+        // Every transfer is the same size, so dropped ones can be counted
+        // in samples from this one's length.
+        let len = buffer.len() as u64;
+        let sample_index = self.next_index[device] +
+            buffer.dropped_before() * len;
+        self.next_index[device] = sample_index + len;
+        Ok(TaggedBuffer { device, sample_index, buffer })
+    }
+
+    /// Wait for the next buffer from every device, returned in device
+    /// order.
+    pub fn recv_all(&mut self) -> Result<Vec<TaggedBuffer>, HackRFError> {
+        (0..self.streams.len()).map(|device| self.recv_from(device))
+            .collect()
+    }
+
+    /// Transfers dropped so far on each device because the consumer did
+    /// not keep up.
+    pub fn dropped(&self) -> Vec<u64> {
+        self.streams.iter().map(RxStream::dropped).collect()
+    }
+
+    /// Stop every device and reset its `ClockConfig`, reporting the first
+    /// error.
+    pub fn stop(mut self) -> Result<(), HackRFError> {
+        self.stop_all()
+    }
+
+    fn stop_all(&mut self) -> Result<(), HackRFError> {
+        let mut result = Ok(());
+        for stream in self.streams.drain(..) {
+            let stopped = stream.stop_with(reset_clock);
+            if result.is_ok() {
+                result = stopped;
+            }
+        }
+        result
+    }
+}
+
+impl<'a> Drop for MultiRxStream<'a> {
+    fn drop(&mut self) {
+        let _ = self.stop_all();
+    }
+}
+
+impl<'a> Iterator for MultiRxStream<'a> {
+    type Item = Vec<TaggedBuffer>;
+
+    /// Wait for the next buffer from every device, ending when any stops.
+    fn next(&mut self) -> Option<Vec<TaggedBuffer>> {
+        self.recv_all().ok()
+    }
+}
@@ -0,0 +1,241 @@
+// Opera Cake antenna switch control
+// Copyright Adam Greig <adam@adamgreig.com> 2014
+// Licensed under MIT license
+
+//! An Opera Cake connects its two common ports, A0 and B0, each to one of
+//! eight antenna ports, A1-A4 on one side and B1-B4 on the other. A0 and
+//! B0 are always switched to opposite sides. Up to eight boards can be
+//! stacked on one HackRF, each at its own I2C address.
+//!
+//! Each board is switched by hand, from a list of frequency ranges as the
+//! HackRF retunes, or in turn after a number of samples. The frequency
+//! ranges and dwell times are shared by every board in that mode, and in
+//! both modes B0 follows A0 to the port opposite it.
+
+use std::fmt;
+use std::str::FromStr;
+
+use crate::{ffi, HackRFDevice, HackRFError};
+
+/// Most Opera Cakes on one HackRF.
+pub const OPERACAKE_MAX_BOARDS: usize = ffi::HACKRF_OPERACAKE_MAX_BOARDS;
+
+/// Most frequency ranges for frequency switching.
+pub const OPERACAKE_MAX_FREQ_RANGES: usize =
+    ffi::HACKRF_OPERACAKE_MAX_FREQ_RANGES;
+
+/// Most dwell times for time switching.
+pub const OPERACAKE_MAX_DWELL_TIMES: usize =
+    ffi::HACKRF_OPERACAKE_MAX_DWELL_TIMES;
+
+/// One of an Opera Cake's antenna ports.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum OperaCakePort { A1, A2, A3, A4, B1, B2, B3, B4 }
+
+impl OperaCakePort {
+    /// Every port, in the order libhackrf numbers them.
+    pub const ALL: [OperaCakePort; 8] = [
+        OperaCakePort::A1, OperaCakePort::A2, OperaCakePort::A3,
+        OperaCakePort::A4, OperaCakePort::B1, OperaCakePort::B2,
+        OperaCakePort::B3, OperaCakePort::B4
+    ];
+
+    /// True for A1-A4.
+    pub fn is_a_side(self) -> bool {
+        u8::from(self) <= ffi::OPERACAKE_PA4
+    }
+
+    /// The port with the same number on the other side, so A1 for B1.
+    pub fn opposite(self) -> OperaCakePort {
+        OperaCakePort::ALL[(u8::from(self) as usize + 4) % 8]
+    }
+}
+
+impl From<OperaCakePort> for u8 {
+    fn from(port: OperaCakePort) -> u8 {
+        match port {
+            OperaCakePort::A1 => ffi::OPERACAKE_PA1,
+            OperaCakePort::A2 => ffi::OPERACAKE_PA2,
+            OperaCakePort::A3 => ffi::OPERACAKE_PA3,
+            OperaCakePort::A4 => ffi::OPERACAKE_PA4,
+            OperaCakePort::B1 => ffi::OPERACAKE_PB1,
+            OperaCakePort::B2 => ffi::OPERACAKE_PB2,
+            OperaCakePort::B3 => ffi::OPERACAKE_PB3,
+            OperaCakePort::B4 => ffi::OPERACAKE_PB4
+        }
+    }
+}
+
+/// Displays the names printed on the board, such as `A1`.
+impl fmt::Display for OperaCakePort {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let port = u8::from(*self);
+        let side = if self.is_a_side() { 'A' } else { 'B' };
+        write!(f, "{}{}", side, port % 4 + 1)
+    }
+}
+
+impl FromStr for OperaCakePort {
+    type Err = HackRFError;
+
+    /// Parse a port name such as `A1` or `b4`, as `hackrf_operacake`
+    /// accepts.
+    /// Returns `HackRFError::InvalidParam` for anything else.
+    fn from_str(s: &str) -> Result<OperaCakePort, HackRFError> {
+        OperaCakePort::ALL.iter()
+            .find(|port| port.to_string().eq_ignore_ascii_case(s.trim()))
+            .copied()
+            .ok_or(HackRFError::InvalidParam)
+    }
+}
+
+/// How an Opera Cake chooses its ports.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum OperaCakeMode {
+    /// Only `OperaCake::set_ports` switches.
+    Manual,
+    /// Switch A0 to the port of the first frequency range containing each
+    /// frequency tuned to.
+    Frequency,
+    /// Switch A0 through the dwell time list, repeating it.
+    Time
+}
+
+/// Switch to `port` while tuned between `start_mhz` and `stop_mhz`,
+/// inclusive.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct OperaCakeRange {
+    pub start_mhz: u16,
+    pub stop_mhz: u16,
+    pub port: OperaCakePort
+}
+
+impl OperaCakeRange {
+    pub fn new(start_mhz: u16, stop_mhz: u16, port: OperaCakePort)
+               -> OperaCakeRange {
+        OperaCakeRange { start_mhz, stop_mhz, port }
+    }
+
+    /// True if `freq_hz`, rounded down to a whole MHz as the firmware
+    /// does, is within the range.
+    pub fn contains(&self, freq_hz: u64) -> bool {
+        let mhz = freq_hz / 1_000_000;
+        self.start_mhz as u64 <= mhz && mhz <= self.stop_mhz as u64
+    }
+}
+
+/// Stay on `port` for `samples` samples before moving to the next entry.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct OperaCakeDwell {
+    pub samples: u32,
+    pub port: OperaCakePort
+}
+
+impl OperaCakeDwell {
+    pub fn new(samples: u32, port: OperaCakePort) -> OperaCakeDwell {
+        OperaCakeDwell { samples, port }
+    }
+}
+
+impl HackRFDevice {
+    /// The I2C addresses of the attached Opera Cakes.
+    /// Returns `HackRFError::Unsupported` if libhackrf is too old.
+    pub fn operacake_boards(&mut self) -> Result<Vec<u8>, HackRFError> {
+        self.backend.operacake_boards()
+    }
+
+    /// A handle to the Opera Cake at `address`.
+    /// Returns `HackRFError::InvalidParam` if `address` is not 0-7, or
+    /// `HackRFError::NotFound` if no board there is attached.
+    pub fn operacake(&mut self, address: u8)
+                     -> Result<OperaCake<'_>, HackRFError> {
+        if address as usize >= OPERACAKE_MAX_BOARDS {
+            return Err(HackRFError::InvalidParam);
+        }
+        if !self.operacake_boards()?.contains(&address) {
+            return Err(HackRFError::NotFound);
+        }
+        Ok(OperaCake { device: self, address })
+    }
+
+    /// Set the frequency ranges used by every Opera Cake in frequency mode,
+    /// checked in order.
+    /// Returns `HackRFError::InvalidParam` if there are none, more than
+    /// `OPERACAKE_MAX_FREQ_RANGES`, or one starts above its stop.
+    pub fn set_operacake_freq_ranges(&mut self, ranges: &[OperaCakeRange])
+                                     -> Result<(), HackRFError> {
+        let ranges_ok = !ranges.is_empty() &&
+            ranges.len() <= OPERACAKE_MAX_FREQ_RANGES &&
+            ranges.iter().all(|r| r.start_mhz <= r.stop_mhz);
+        if !ranges_ok {
+            return Err(HackRFError::InvalidParam);
+        }
+        self.backend.set_operacake_freq_ranges(ranges)
+    }
+
+    /// Set the dwell times used by every Opera Cake in time mode, visited
+    /// in order.
+    /// Returns `HackRFError::InvalidParam` if there are none, more than
+    /// `OPERACAKE_MAX_DWELL_TIMES`, or one is zero samples long.
+    pub fn set_operacake_dwell_times(&mut self, dwells: &[OperaCakeDwell])
+                                     -> Result<(), HackRFError> {
+        let dwells_ok = !dwells.is_empty() &&
+            dwells.len() <= OPERACAKE_MAX_DWELL_TIMES &&
+            dwells.iter().all(|d| d.samples > 0);
+        if !dwells_ok {
+            return Err(HackRFError::InvalidParam);
+        }
+        self.backend.set_operacake_dwell_times(dwells)
+    }
+}
+
+/// One attached Opera Cake, from `HackRFDevice::operacake`.
+pub struct OperaCake<'a> {
+    device: &'a mut HackRFDevice,
+    address: u8
+}
+
+impl<'a> OperaCake<'a> {
+    /// The board's I2C address.
+    pub fn address(&self) -> u8 {
+        self.address
+    }
+
+    /// Connect A0 to `port_a` and B0 to `port_b`. Outside manual mode the
+    /// next automatic switch overrides this.
+    /// Returns `HackRFError::InvalidParam` if both are on the same side.
+    pub fn set_ports(&mut self, port_a: OperaCakePort, port_b: OperaCakePort)
+                     -> Result<(), HackRFError> {
+        if port_a.is_a_side() == port_b.is_a_side() {
+            return Err(HackRFError::InvalidParam);
+        }
+        self.device.backend.set_operacake_ports(self.address, port_a, port_b)
+    }
+
+    /// Returns `HackRFError::Unsupported` if libhackrf is too old.
+    pub fn mode(&mut self) -> Result<OperaCakeMode, HackRFError> {
+        self.device.backend.operacake_mode(self.address)
+    }
+
+    /// Returns `HackRFError::Unsupported` if libhackrf is too old.
+    pub fn set_mode(&mut self, mode: OperaCakeMode)
+                    -> Result<(), HackRFError> {
+        self.device.backend.set_operacake_mode(self.address, mode)
+    }
+
+    /// Switch automatically as the HackRF retunes, setting `ranges` with
+    /// `set_operacake_freq_ranges` and then frequency mode.
+    pub fn switch_by_frequency(&mut self, ranges: &[OperaCakeRange])
+                               -> Result<(), HackRFError> {
+        self.device.set_operacake_freq_ranges(ranges)?;
+        self.set_mode(OperaCakeMode::Frequency)
+    }
+
+    /// Switch automatically while streaming, setting `dwells` with
+    /// `set_operacake_dwell_times` and then time mode.
+    pub fn switch_by_time(&mut self, dwells: &[OperaCakeDwell])
+                          -> Result<(), HackRFError> {
+        self.device.set_operacake_dwell_times(dwells)?;
+        self.set_mode(OperaCakeMode::Time)
+    }
+}
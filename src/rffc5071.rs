@@ -0,0 +1,251 @@
+// RFFC5071 mixer and synthesizer register access
+// Copyright Adam Greig <adam@adamgreig.com> 2014
+// Licensed under MIT license
+
+use std::fmt;
+
+use crate::{HackRFDevice, HackRFError, RFPathFilter};
+
+/// Number of RFFC5071 registers.
+pub const RFFC5071_NUM_REGS: usize = 31;
+
+/// The synthesizer's reference clock.
+pub const RFFC5071_REF_HZ: u64 = 40_000_000;
+
+const ONE_MHZ: u64 = 1_000_000;
+const LO_MAX_MHZ: u64 = 5400;
+const P1_FREQ1: usize = 12;
+const P2_FREQ1: usize = 15;
+const SDI_CTRL: usize = 21;
+const ENBL: u16 = 1 << 14;
+const MODE: u16 = 1 << 13;
+
+/// The firmware's tuning ranges, in MHz.
+const MAX_LP_FREQ_MHZ: u64 = 2170;
+const MAX_BYPASS_FREQ_MHZ: u64 = 2740;
+const MID1_HP_FREQ_MHZ: u64 = 3600;
+const MID2_HP_FREQ_MHZ: u64 = 5100;
+const MAX_HP_FREQ_MHZ: u64 = 7250;
+
+/// One of the RFFC5071's two sets of synthesizer settings.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Rffc5071Path { One, Two }
+
+impl Rffc5071Path {
+    fn base(self) -> usize {
+        match self {
+            Rffc5071Path::One => P1_FREQ1,
+            Rffc5071Path::Two => P2_FREQ1
+        }
+    }
+}
+
+/// A dump of every RFFC5071 register, with the synthesizer settings decoded
+/// into the LO frequency. Displays like `hackrf_debug --rffc5072 --read`,
+/// one register per line.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Rffc5071Registers {
+    pub values: [u16; RFFC5071_NUM_REGS]
+}
+
+impl Rffc5071Registers {
+    pub fn new(values: [u16; RFFC5071_NUM_REGS]) -> Rffc5071Registers {
+        Rffc5071Registers { values }
+    }
+
+    /// True if the mixer and synthesizer are enabled. The firmware
+    /// disables them when bypassing the mixer.
+    pub fn enabled(&self) -> bool {
+        self.values[SDI_CTRL] & ENBL != 0
+    }
+
+    /// The path whose settings are in use.
+    pub fn active_path(&self) -> Rffc5071Path {
+        match self.values[SDI_CTRL] & MODE {
+            0 => Rffc5071Path::One,
+            _ => Rffc5071Path::Two
+        }
+    }
+
+    /// The integer part of the feedback divider ratio.
+    pub fn n(&self, path: Rffc5071Path) -> u32 {
+        (self.values[path.base()] >> 7) as u32
+    }
+
+    /// The fractional part of the feedback divider ratio, in 2^-24ths.
+    pub fn frac(&self, path: Rffc5071Path) -> u32 {
+        let base = path.base();
+        ((self.values[base + 1] as u32) << 8) |
+            (self.values[base + 2] >> 8) as u32
+    }
+
+    /// The divider between the VCO and the LO: 1, 2, 4, 8, 16 or 32.
+    pub fn lo_divider(&self, path: Rffc5071Path) -> u32 {
+        1 << (self.values[path.base()] >> 4 & 0x07)
+    }
+
+    /// The feedback prescaler: 2 or 4.
+    pub fn prescaler(&self, path: Rffc5071Path) -> u32 {
+        2 * (self.values[path.base()] >> 2 & 0x03) as u32
+    }
+
+    /// The VCO frequency in Hz.
+    pub fn vco_hz(&self, path: Rffc5071Path) -> u64 {
+        let ratio = ((self.n(path) as u64) << 24) | self.frac(path) as u64;
+        (RFFC5071_REF_HZ * self.prescaler(path) as u64 * ratio) >> 24
+    }
+
+    /// The LO frequency in Hz, rounded down as the firmware does.
+    pub fn lo_hz(&self, path: Rffc5071Path) -> u64 {
+        let ratio = ((self.n(path) as u64) << 24) | self.frac(path) as u64;
+        RFFC5071_REF_HZ * self.prescaler(path) as u64 * ratio
+            / ((self.lo_divider(path) as u64) << 24)
+    }
+
+    /// Registers whose values differ from `other`, as
+    /// (register, this value, other value).
+    pub fn diff(&self, other: &Rffc5071Registers) -> Vec<(u8, u16, u16)> {
+        self.values.iter().zip(&other.values).enumerate()
+            .filter(|&(_, (a, b))| a != b)
+            .map(|(reg, (&a, &b))| (reg as u8, a, b))
+            .collect()
+    }
+
+    /// Program both paths for an LO of `lo_mhz` as the firmware does,
+    /// returning the LO frequency actually set in Hz.
+    pub(crate) fn set_lo(&mut self, lo_mhz: u64) -> u64 {
+        let synth = Synth::new(lo_mhz);
+        for base in [P1_FREQ1, P2_FREQ1] {
+            self.values[base] = (synth.n << 7) | (synth.n_lo << 4) |
+                                (synth.prescaler >> 1) << 2;
+            self.values[base + 1] = (synth.frac >> 8) as u16;
+            self.values[base + 2] = ((synth.frac & 0xff) << 8) as u16;
+        }
+        synth.lo_hz
+    }
+
+    pub(crate) fn set_enabled(&mut self, enabled: bool) {
+        match enabled {
+            true => self.values[SDI_CTRL] |= ENBL,
+            false => self.values[SDI_CTRL] &= !ENBL
+        }
+    }
+}
+
+impl fmt::Display for Rffc5071Registers {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (reg, value) in self.values.iter().enumerate() {
+            writeln!(f, "[{:2}] -> 0x{:04x}", reg, value)?;
+        }
+        Ok(())
+    }
+}
+
+/// Synthesizer settings for an LO, computed as `rffc5071_config_synth` in
+/// the firmware does.
+struct Synth {
+    n_lo: u16,
+    n: u16,
+    prescaler: u16,
+    frac: u32,
+    lo_hz: u64
+}
+
+impl Synth {
+    fn new(lo_mhz: u64) -> Synth {
+        let lo_mhz = lo_mhz.max(1);
+        let mut n_lo = 0;
+        let mut x = LO_MAX_MHZ / lo_mhz;
+        while x > 1 && n_lo < 5 {
+            n_lo += 1;
+            x >>= 1;
+        }
+        let lo_divider = 1u64 << n_lo;
+        let vco_mhz = lo_divider * lo_mhz;
+        let prescaler = if vco_mhz > 3200 { 4 } else { 2 };
+        let ref_mhz = RFFC5071_REF_HZ / ONE_MHZ;
+        let ratio = (vco_mhz << 29) / (prescaler * ref_mhz);
+        Synth {
+            n_lo,
+            n: (ratio >> 29) as u16,
+            prescaler: prescaler as u16,
+            frac: ((ratio >> 5) & 0xff_ffff) as u32,
+            lo_hz: RFFC5071_REF_HZ * (ratio >> 5) * prescaler
+                   / (lo_divider << 24)
+        }
+    }
+}
+
+/// How the firmware tunes to a frequency with `set_freq`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct TuningPlan {
+    /// The RF filter, which also selects whether the mixer is used.
+    pub filter: RFPathFilter,
+    /// The frequency the MAX2837 is tuned to, in Hz.
+    pub if_hz: u64,
+    /// The RFFC5071 LO frequency in Hz, or `None` when bypassing the mixer.
+    pub lo_hz: Option<u64>
+}
+
+/// The IF, LO and filter the firmware chooses for `freq_hz`, or `None`
+/// above 7250MHz. The IF is graduated across each band to keep mixer
+/// products out of the passband, and the LO is set in whole MHz and then
+/// rounded by the synthesizer, with the IF adjusted to compensate.
+pub fn tuning_plan(freq_hz: u64) -> Option<TuningPlan> {
+    let freq_mhz = freq_hz / ONE_MHZ;
+    if freq_mhz < MAX_LP_FREQ_MHZ {
+        let if_nominal = 2_650_000_000 - freq_hz / 7;
+        let lo_hz = Synth::new(if_nominal / ONE_MHZ + freq_mhz).lo_hz;
+        Some(TuningPlan { filter: RFPathFilter::LowPass,
+                          if_hz: lo_hz - freq_hz, lo_hz: Some(lo_hz) })
+    } else if freq_mhz < MAX_BYPASS_FREQ_MHZ {
+        Some(TuningPlan { filter: RFPathFilter::Bypass, if_hz: freq_hz,
+                          lo_hz: None })
+    } else if freq_mhz <= MAX_HP_FREQ_MHZ {
+        let if_nominal = if freq_mhz < MID1_HP_FREQ_MHZ {
+            2_170_000_000 + (freq_hz - 2_740_000_000) * 57 / 86
+        } else if freq_mhz < MID2_HP_FREQ_MHZ {
+            2_350_000_000 + (freq_hz - 3_600_000_000) / 5
+        } else {
+            2_500_000_000 + (freq_hz - 5_100_000_000) / 9
+        };
+        let lo_hz = Synth::new(freq_mhz - if_nominal / ONE_MHZ).lo_hz;
+        Some(TuningPlan { filter: RFPathFilter::HighPass,
+                          if_hz: freq_hz - lo_hz, lo_hz: Some(lo_hz) })
+    } else {
+        None
+    }
+}
+
+impl HackRFDevice {
+    /// Read one RFFC5071 register.
+    /// Returns `HackRFError::InvalidParam` if `register` is 31 or more.
+    pub fn rffc5071_read(&mut self, register: u8)
+                         -> Result<u16, HackRFError> {
+        if register as usize >= RFFC5071_NUM_REGS {
+            return Err(HackRFError::InvalidParam);
+        }
+        self.backend.rffc5071_read(register)
+    }
+
+    /// Write one RFFC5071 register. Later calls such as `set_freq` may
+    /// overwrite it.
+    /// Returns `HackRFError::InvalidParam` if `register` is 31 or more.
+    pub fn rffc5071_write(&mut self, register: u8, value: u16)
+                          -> Result<(), HackRFError> {
+        if register as usize >= RFFC5071_NUM_REGS {
+            return Err(HackRFError::InvalidParam);
+        }
+        self.backend.rffc5071_write(register, value)
+    }
+
+    /// Read every RFFC5071 register.
+    pub fn rffc5071_dump(&mut self)
+                         -> Result<Rffc5071Registers, HackRFError> {
+        let mut registers = Rffc5071Registers::default();
+        for (reg, value) in registers.values.iter_mut().enumerate() {
+            *value = self.rffc5071_read(reg as u8)?;
+        }
+        Ok(registers)
+    }
+}
@@ -0,0 +1,289 @@
+// Si5351C clock generator register access
+// Copyright Adam Greig <adam@adamgreig.com> 2014
+// Licensed under MIT license
+
+use std::fmt;
+
+use crate::{HackRFDevice, HackRFError};
+
+/// Number of Si5351C registers.
+pub const SI5351C_NUM_REGS: usize = 256;
+
+/// The HackRF's crystal, the default PLL reference.
+pub const SI5351C_XTAL_HZ: u32 = 25_000_000;
+
+/// The external reference expected on the CLKIN port.
+pub const SI5351C_CLKIN_HZ: u32 = 10_000_000;
+
+/// Frequency the firmware runs PLLA at.
+const VCO_HZ: u64 = 800_000_000;
+
+const DEVICE_STATUS: usize = 0;
+const LOS_CLKIN: u8 = 1 << 4;
+const LOL_A: u8 = 1 << 5;
+const LOL_B: u8 = 1 << 6;
+const PLL_INPUT_SOURCE: usize = 15;
+const CLK_CONTROL: usize = 16;
+const CLK_POWER_DOWN: u8 = 1 << 7;
+const MS_SRC_PLLB: u8 = 1 << 5;
+const CLK_SRC_MULTISYNTH: u8 = 3 << 2;
+const MSNA: usize = 26;
+const MSNB: usize = 34;
+const MS0: usize = 42;
+const MS6_P1: usize = 90;
+const R67_DIV: usize = 92;
+
+/// Largest P3 (denominator) the multisynth parameters hold.
+const MAX_DENOMINATOR: u64 = 0xfffff;
+
+/// One of the Si5351C's two PLLs.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Si5351cPll { A, B }
+
+/// Where a PLL takes its reference from.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Si5351cSource {
+    /// The on-board 25MHz crystal.
+    Xtal,
+    /// The CLKIN port, after its divider.
+    Clkin
+}
+
+/// A dump of every Si5351C register, with the PLL and multisynth setup
+/// decoded into frequencies. Displays like `hackrf_debug --si5351c --read`,
+/// one register per line.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Si5351cRegisters {
+    pub values: [u8; SI5351C_NUM_REGS]
+}
+
+impl Default for Si5351cRegisters {
+    fn default() -> Si5351cRegisters {
+        Si5351cRegisters { values: [0; SI5351C_NUM_REGS] }
+    }
+}
+
+impl Si5351cRegisters {
+    pub fn new(values: [u8; SI5351C_NUM_REGS]) -> Si5351cRegisters {
+        Si5351cRegisters { values }
+    }
+
+    /// The P1, P2 and P3 multisynth parameters stored from `base`.
+    fn parameters(&self, base: usize) -> (u32, u32, u32) {
+        let r = &self.values[base..base + 8];
+        let p1 = ((r[2] as u32 & 0x03) << 16) | ((r[3] as u32) << 8)
+                 | r[4] as u32;
+        let p2 = ((r[5] as u32 & 0x0f) << 16) | ((r[6] as u32) << 8)
+                 | r[7] as u32;
+        let p3 = ((r[5] as u32 & 0xf0) << 12) | ((r[0] as u32) << 8)
+                 | r[1] as u32;
+        (p1, p2, p3)
+    }
+
+    fn set_parameters(&mut self, base: usize, (p1, p2, p3): (u32, u32, u32)) {
+        let r = &mut self.values[base..base + 8];
+        r[0] = (p3 >> 8) as u8;
+        r[1] = p3 as u8;
+        r[2] = (r[2] & !0x03) | ((p1 >> 16) & 0x03) as u8;
+        r[3] = (p1 >> 8) as u8;
+        r[4] = p1 as u8;
+        r[5] = (((p3 >> 12) & 0xf0) | ((p2 >> 16) & 0x0f)) as u8;
+        r[6] = (p2 >> 8) as u8;
+        r[7] = p2 as u8;
+    }
+
+    /// The ratio `a + b/c` the parameters from `base` encode, or `None` if
+    /// the denominator is zero.
+    fn ratio(&self, base: usize) -> Option<f64> {
+        let (p1, p2, p3) = self.parameters(base);
+        if p3 == 0 {
+            return None;
+        }
+        Some((p1 as f64 + 512.0 + p2 as f64 / p3 as f64) / 128.0)
+    }
+
+    /// True while PLL `pll` is locked.
+    pub fn pll_locked(&self, pll: Si5351cPll) -> bool {
+        let bit = match pll { Si5351cPll::A => LOL_A, Si5351cPll::B => LOL_B };
+        self.values[DEVICE_STATUS] & bit == 0
+    }
+
+    /// True if a signal is present on the CLKIN port.
+    pub fn clkin_present(&self) -> bool {
+        self.values[DEVICE_STATUS] & LOS_CLKIN == 0
+    }
+
+    /// The reference PLL `pll` is using.
+    pub fn pll_source(&self, pll: Si5351cPll) -> Si5351cSource {
+        let bit = match pll { Si5351cPll::A => 2, Si5351cPll::B => 3 };
+        match self.values[PLL_INPUT_SOURCE] >> bit & 1 {
+            0 => Si5351cSource::Xtal,
+            _ => Si5351cSource::Clkin
+        }
+    }
+
+    /// The CLKIN divider, 1, 2, 4 or 8.
+    pub fn clkin_divider(&self) -> u32 {
+        1 << (self.values[PLL_INPUT_SOURCE] >> 6)
+    }
+
+    /// True if PLLA is locked to a 10MHz reference on CLKIN.
+    pub fn external_reference_locked(&self) -> bool {
+        self.pll_source(Si5351cPll::A) == Si5351cSource::Clkin &&
+            self.clkin_present() && self.pll_locked(Si5351cPll::A)
+    }
+
+    /// The feedback multisynth ratio of PLL `pll`.
+    pub fn pll_ratio(&self, pll: Si5351cPll) -> Option<f64> {
+        self.ratio(match pll { Si5351cPll::A => MSNA, Si5351cPll::B => MSNB })
+    }
+
+    /// The VCO frequency of PLL `pll` in Hz, assuming a 25MHz crystal or a
+    /// 10MHz reference on CLKIN.
+    pub fn pll_hz(&self, pll: Si5351cPll) -> Option<f64> {
+        let reference = match self.pll_source(pll) {
+            Si5351cSource::Xtal => SI5351C_XTAL_HZ as f64,
+            Si5351cSource::Clkin =>
+                SI5351C_CLKIN_HZ as f64 / self.clkin_divider() as f64
+        };
+        Some(reference * self.pll_ratio(pll)?)
+    }
+
+    /// The output multisynth ratio for clock `clock`, 0-7, including its R
+    /// divider. Multisynths 6 and 7 only divide by even integers.
+    pub fn output_divider(&self, clock: usize) -> Option<f64> {
+        let (ratio, r_div) = match clock {
+            0..=5 => {
+                let base = MS0 + 8 * clock;
+                let ratio = match self.values[base + 2] >> 2 & 0x03 {
+                    3 => 4.0,
+                    _ => self.ratio(base)?
+                };
+                (ratio, self.values[base + 2] >> 4 & 0x07)
+            },
+            6 | 7 => {
+                let p1 = self.values[MS6_P1 + clock - 6];
+                let r_div = self.values[R67_DIV] >> (4 * (clock - 6)) & 0x07;
+                (p1 as f64, r_div)
+            },
+            _ => return None
+        };
+        if ratio == 0.0 {
+            return None;
+        }
+        Some(ratio * (1u32 << r_div) as f64)
+    }
+
+    /// The frequency of output `clock` in Hz, or `None` if it is powered
+    /// down or not driven by its multisynth.
+    pub fn clock_hz(&self, clock: usize) -> Option<f64> {
+        let control = *self.values.get(CLK_CONTROL + clock)?;
+        if control & CLK_POWER_DOWN != 0 ||
+           control & CLK_SRC_MULTISYNTH != CLK_SRC_MULTISYNTH {
+            return None;
+        }
+        let pll = match control & MS_SRC_PLLB {
+            0 => Si5351cPll::A,
+            _ => Si5351cPll::B
+        };
+        Some(self.pll_hz(pll)? / self.output_divider(clock)?)
+    }
+
+    /// The sample rate in Hz. The firmware runs CLK0, which clocks the
+    /// MAX5864 ADC/DAC, at twice the sample rate.
+    pub fn sample_rate_hz(&self) -> Option<f64> {
+        Some(self.clock_hz(0)? / 2.0)
+    }
+
+    /// Registers whose values differ from `other`, as
+    /// (register, this value, other value).
+    pub fn diff(&self, other: &Si5351cRegisters) -> Vec<(u8, u8, u8)> {
+        self.values.iter().zip(&other.values).enumerate()
+            .filter(|&(_, (a, b))| a != b)
+            .map(|(reg, (&a, &b))| (reg as u8, a, b))
+            .collect()
+    }
+
+    /// Program PLLA to 800MHz from `source` and report CLKIN as present or
+    /// not, as the firmware does at startup.
+    pub(crate) fn set_reference(&mut self, source: Si5351cSource) {
+        let (bit, multiplier) = match source {
+            Si5351cSource::Xtal => (0, VCO_HZ / SI5351C_XTAL_HZ as u64),
+            Si5351cSource::Clkin => (1, VCO_HZ / SI5351C_CLKIN_HZ as u64)
+        };
+        self.values[PLL_INPUT_SOURCE] = bit << 2;
+        self.set_parameters(MSNA, (128 * multiplier as u32 - 512, 0, 1));
+        self.values[DEVICE_STATUS] = match source {
+            Si5351cSource::Xtal => LOS_CLKIN,
+            Si5351cSource::Clkin => 0
+        };
+    }
+
+    /// Program MS0 and CLK0 for a sample rate of `freq_hz / divider`, as
+    /// `set_sample_rate_manual` does.
+    pub(crate) fn set_sample_rate(&mut self, freq_hz: u32, divider: u32) {
+        // Divide the 800MHz VCO by (VCO * divider) / (2 * freq), halving
+        // with the R divider until the multisynth ratio is below 2048.
+        let mut num = VCO_HZ * divider as u64;
+        let mut den = 2 * freq_hz as u64;
+        let mut r_div = 0;
+        while num / den >= 2048 && r_div < 7 {
+            den *= 2;
+            r_div += 1;
+        }
+        let a = num / den;
+        num %= den;
+        let g = gcd(num, den);
+        num /= g;
+        den /= g;
+        if den > MAX_DENOMINATOR {
+            num = num * MAX_DENOMINATOR / den;
+            den = MAX_DENOMINATOR;
+        }
+        let floor = 128 * num / den;
+        let p1 = (128 * a + floor).saturating_sub(512) as u32;
+        let p2 = (128 * num - den * floor) as u32;
+        self.set_parameters(MS0, (p1, p2, den as u32));
+        self.values[MS0 + 2] = (r_div << 4) | (self.values[MS0 + 2] & 0x03);
+        self.values[CLK_CONTROL] = CLK_SRC_MULTISYNTH | 0x03;
+    }
+}
+
+fn gcd(mut a: u64, mut b: u64) -> u64 {
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+    a.max(1)
+}
+
+impl fmt::Display for Si5351cRegisters {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (reg, value) in self.values.iter().enumerate() {
+            writeln!(f, "[{:3}] -> 0x{:02x}", reg, value)?;
+        }
+        Ok(())
+    }
+}
+
+impl HackRFDevice {
+    /// Read one Si5351C register.
+    pub fn si5351c_read(&mut self, register: u8) -> Result<u8, HackRFError> {
+        self.backend.si5351c_read(register)
+    }
+
+    /// Write one Si5351C register. Later calls such as `set_sample_rate`
+    /// may overwrite it.
+    pub fn si5351c_write(&mut self, register: u8, value: u8)
+                         -> Result<(), HackRFError> {
+        self.backend.si5351c_write(register, value)
+    }
+
+    /// Read every Si5351C register.
+    pub fn si5351c_dump(&mut self) -> Result<Si5351cRegisters, HackRFError> {
+        let mut registers = Si5351cRegisters::default();
+        for (reg, value) in registers.values.iter_mut().enumerate() {
+            *value = self.si5351c_read(reg as u8)?;
+        }
+        Ok(registers)
+    }
+}
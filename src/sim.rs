@@ -0,0 +1,1067 @@
+// Software-simulated HackRF backend
+// Copyright Adam Greig <adam@adamgreig.com> 2014
+// Licensed under MIT license
+
+use std::f64::consts::PI;
+use std::fs;
+use std::io;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::backend::{Backend, RxCallback, TxCallback};
+use crate::board::BoardId;
+use crate::config::default_filter;
+use crate::iq::Complex;
+use crate::max2837::Max2837Registers;
+use crate::operacake::{OperaCakeDwell, OperaCakeMode, OperaCakePort,
+                       OperaCakeRange};
+use crate::rffc5071::{self, Rffc5071Registers};
+use crate::si5351c::{Si5351cRegisters, Si5351cSource};
+use crate::spiflash::SPIFLASH_SIZE;
+use crate::sweep::{SweepBlock, SweepConfig, SWEEP_BLOCK_SAMPLES,
+                   SWEEP_HEADER_BYTES};
+use crate::{ffi, HackRFError, RFPathFilter, IQ8};
+
+/// Samples per transfer, the same as libhackrf's 262144 byte transfers.
+const TRANSFER_SAMPLES: usize = 131072;
+
+/// Bytes libhackrf sends in each CPLD programming transfer.
+const CPLD_TRANSFER_BYTES: usize = 512;
+
+/// Highest frequency the firmware will tune to.
+const MAX_FREQ_HZ: u64 = 7_250_000_000;
+
+/// Range libhackrf accepts for `set_freq_explicit`.
+const MIN_IF_HZ: u64 = 2_150_000_000;
+const MAX_IF_HZ: u64 = 2_750_000_000;
+const MIN_LO_HZ: u64 = 84_375_000;
+const MAX_LO_HZ: u64 = 5_400_000_000;
+
+/// A test signal fed into the simulated receiver. Amplitudes are relative
+/// to full scale, so 1.0 spans the whole `IQ8` range. Signals are summed
+/// and then saturate, as the ADC would.
+#[derive(Clone, Debug)]
+pub enum Signal {
+    /// A continuous tone at an absolute RF frequency. It is only received
+    /// while within half the sample rate of the tuned frequency.
+    Tone { freq_hz: f64, amplitude: f32 },
+    /// Gaussian noise with the given RMS amplitude on each of I and Q.
+    Noise { amplitude: f32 },
+    /// Recorded samples, looped from the start whatever the tuning.
+    Recording(Arc<[IQ8]>)
+}
+
+impl Signal {
+    pub fn tone(freq_hz: f64, amplitude: f32) -> Signal {
+        Signal::Tone { freq_hz, amplitude }
+    }
+
+    pub fn noise(amplitude: f32) -> Signal {
+        Signal::Noise { amplitude }
+    }
+
+    pub fn recording(samples: Vec<IQ8>) -> Signal {
+        Signal::Recording(samples.into())
+    }
+
+    /// Load a recording of interleaved signed 8-bit IQ, the format written
+    /// by `hackrf_transfer -r`.
+    pub fn from_file<P: AsRef<Path>>(path: P) -> io::Result<Signal> {
+        let bytes = fs::read(path)?;
+        if bytes.len() < 2 {
+            return Err(io::Error::new(io::ErrorKind::InvalidData,
+                                      "recording holds no samples"));
+        }
+        Ok(Signal::recording(IQ8::from_bytes(&bytes).to_vec()))
+    }
+}
+
+struct SimState {
+    open: bool,
+    freq_hz: u64,
+    sample_rate: f64,
+    baseband_filter_bandwidth: u32,
+    amp_enable: bool,
+    antenna_enable: bool,
+    lna_gain: u32,
+    vga_gain: u32,
+    txvga_gain: u32,
+    max2837: Max2837Registers,
+    si5351c: Si5351cRegisters,
+    rffc5071: Rffc5071Registers,
+    spiflash: Vec<u8>,
+    cpld: Vec<u8>,
+    clkout: bool,
+    hw_sync: bool,
+    operacakes: Vec<SimOperaCake>,
+    operacake_ranges: Vec<OperaCakeRange>,
+    operacake_dwells: Vec<OperaCakeDwell>,
+    signals: Vec<Signal>,
+    transmitted: Vec<IQ8>
+}
+
+/// An Opera Cake stacked on a simulated board.
+struct SimOperaCake {
+    address: u8,
+    mode: OperaCakeMode,
+    port_a: OperaCakePort,
+    port_b: OperaCakePort
+}
+
+impl SimState {
+    fn operacake(&mut self, address: u8) -> Option<&mut SimOperaCake> {
+        self.operacakes.iter_mut().find(|board| board.address == address)
+    }
+
+    /// Switch every Opera Cake in frequency mode for a retune to
+    /// `freq_hz`, leaving them alone if no range contains it.
+    fn switch_operacakes(&mut self, freq_hz: u64) {
+        let range = self.operacake_ranges.iter()
+            .find(|range| range.contains(freq_hz));
+        if let Some(range) = range {
+            for board in &mut self.operacakes {
+                if board.mode == OperaCakeMode::Frequency {
+                    board.port_a = range.port;
+                    board.port_b = range.port.opposite();
+                }
+            }
+        }
+    }
+}
+
+/// Inspects and controls a `SimulatedHackRF` after it has been moved into a
+/// `HackRFDevice`. Reports settings as the firmware would have applied them.
+#[derive(Clone)]
+pub struct SimHandle {
+    state: Arc<Mutex<SimState>>
+}
+
+impl SimHandle {
+    fn lock(&self) -> MutexGuard<'_, SimState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// False once the device has been closed, dropped or unplugged.
+    pub fn is_open(&self) -> bool {
+        self.lock().open
+    }
+
+    /// The tuned centre frequency in Hz.
+    pub fn freq_hz(&self) -> u64 {
+        self.lock().freq_hz
+    }
+
+    pub fn sample_rate(&self) -> f64 {
+        self.lock().sample_rate
+    }
+
+    /// The baseband filter setting actually selected.
+    pub fn baseband_filter_bandwidth(&self) -> u32 {
+        self.lock().baseband_filter_bandwidth
+    }
+
+    pub fn amp_enable(&self) -> bool {
+        self.lock().amp_enable
+    }
+
+    pub fn antenna_enable(&self) -> bool {
+        self.lock().antenna_enable
+    }
+
+    /// LNA gain after rounding down to an 8dB step.
+    pub fn lna_gain(&self) -> u32 {
+        self.lock().lna_gain
+    }
+
+    /// VGA gain after rounding down to a 2dB step.
+    pub fn vga_gain(&self) -> u32 {
+        self.lock().vga_gain
+    }
+
+    pub fn txvga_gain(&self) -> u32 {
+        self.lock().txvga_gain
+    }
+
+    /// A copy of the SPI flash.
+    pub fn spiflash(&self) -> Vec<u8> {
+        self.lock().spiflash.clone()
+    }
+
+    /// The XSVF file the CPLD was last programmed with, if any.
+    pub fn cpld(&self) -> Vec<u8> {
+        self.lock().cpld.clone()
+    }
+
+    /// True while the CLKOUT port is enabled.
+    pub fn clkout(&self) -> bool {
+        self.lock().clkout
+    }
+
+    /// True while streams wait for a trigger edge before starting.
+    pub fn hw_sync(&self) -> bool {
+        self.lock().hw_sync
+    }
+
+    /// The ports A0 and B0 of the Opera Cake at `address` connect to, if
+    /// there is one. Frequency mode is followed on every retune, but time
+    /// mode does not switch.
+    pub fn operacake_ports(&self, address: u8)
+                           -> Option<(OperaCakePort, OperaCakePort)> {
+        self.lock().operacake(address).map(|b| (b.port_a, b.port_b))
+    }
+
+    pub fn operacake_mode(&self, address: u8) -> Option<OperaCakeMode> {
+        self.lock().operacake(address).map(|board| board.mode)
+    }
+
+    /// The frequency ranges last set for frequency mode.
+    pub fn operacake_freq_ranges(&self) -> Vec<OperaCakeRange> {
+        self.lock().operacake_ranges.clone()
+    }
+
+    /// The dwell times last set for time mode.
+    pub fn operacake_dwell_times(&self) -> Vec<OperaCakeDwell> {
+        self.lock().operacake_dwells.clone()
+    }
+
+    /// Replace the signals received from now on.
+    pub fn set_signals(&self, signals: Vec<Signal>) {
+        self.lock().signals = signals;
+    }
+
+    /// A copy of every sample transmitted so far.
+    pub fn transmitted(&self) -> Vec<IQ8> {
+        self.lock().transmitted.clone()
+    }
+
+    /// Take the samples transmitted so far, leaving none captured.
+    pub fn take_transmitted(&self) -> Vec<IQ8> {
+        std::mem::take(&mut self.lock().transmitted)
+    }
+
+    /// Disconnect the board as if its USB cable were pulled: any stream
+    /// stops and later calls fail with `HackRFError::Libusb`.
+    pub fn unplug(&self) {
+        self.lock().open = false;
+    }
+}
+
+/// A trigger line wired between simulated boards, like the trigger pins on
+/// P28. A connected board in hardware sync mode holds off streaming until
+/// a rising edge, which any connected board starting a stream outside sync
+/// mode raises, as the leader's trigger output does.
+#[derive(Clone, Default)]
+pub struct SimTrigger {
+    edges: Arc<(Mutex<u64>, Condvar)>
+}
+
+impl SimTrigger {
+    pub fn new() -> SimTrigger {
+        SimTrigger::default()
+    }
+
+    /// Raise a rising edge, as an external trigger source would.
+    pub fn fire(&self) {
+        let (ref edges, ref condvar) = *self.edges;
+        *edges.lock().unwrap_or_else(|e| e.into_inner()) += 1;
+        condvar.notify_all();
+    }
+
+    fn edges(&self) -> u64 {
+        *self.edges.0.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Wait for an edge after the first `seen`, returning false if `stop`
+    /// is set first.
+    fn wait(&self, seen: u64, stop: &AtomicBool) -> bool {
+        let (ref edges, ref condvar) = *self.edges;
+        let mut count = edges.lock().unwrap_or_else(|e| e.into_inner());
+        while *count == seen {
+            if stop.load(Ordering::Relaxed) {
+                return false;
+            }
+            count = condvar.wait_timeout(count, Duration::from_millis(10))
+                .unwrap_or_else(|e| e.into_inner()).0;
+        }
+        true
+    }
+}
+
+/// The transfer thread of a running stream.
+struct SimStream {
+    stop: Arc<AtomicBool>,
+    running: Arc<AtomicBool>,
+    thread: thread::JoinHandle<()>
+}
+
+impl SimStream {
+    fn stop(self) {
+        self.stop.store(true, Ordering::Relaxed);
+        // The thread owns the callback, so it is dropped once joined.
+        let _ = self.thread.join();
+    }
+}
+
+/// A HackRF simulated in software, for exercising code without hardware.
+/// Settings are checked the way libhackrf and the firmware check them, RX
+/// streams deliver the configured `Signal`s as they would appear at the
+/// tuned frequency and sample rate, and everything sent by TX streams is
+/// captured for inspection through a `SimHandle`.
+/// Wrap it with `HackRFDevice::from_backend` to use it.
+pub struct SimulatedHackRF {
+    state: Arc<Mutex<SimState>>,
+    board_id: u8,
+    version: String,
+    usb_api_version: u16,
+    board_rev: u8,
+    part_id: [u32; 2],
+    serial_no: [u32; 4],
+    transfer_samples: usize,
+    realtime: bool,
+    sweep: Option<SweepConfig>,
+    trigger: Option<SimTrigger>,
+    stream: Option<SimStream>
+}
+
+impl Default for SimulatedHackRF {
+    fn default() -> SimulatedHackRF {
+        SimulatedHackRF::new()
+    }
+}
+
+impl Drop for SimulatedHackRF {
+    fn drop(&mut self) {
+        let _ = self.close();
+    }
+}
+
+impl SimulatedHackRF {
+    /// A HackRF One receiving silence at 10Msps, the firmware's default.
+    /// MAX2837, Si5351C and RFFC5071 registers start at zero apart from
+    /// the fields which hold the initial gains, filter, reference and
+    /// sample rate.
+    pub fn new() -> SimulatedHackRF {
+        let mut max2837 = Max2837Registers::default();
+        max2837.set_lna_gain_db(0);
+        max2837.set_vga_gain_db(0);
+        max2837.set_txvga_gain_db(0);
+        max2837.set_baseband_filter_bandwidth(15_000_000);
+        let mut si5351c = Si5351cRegisters::default();
+        si5351c.set_reference(Si5351cSource::Xtal);
+        si5351c.set_sample_rate(10_000_000, 1);
+        SimulatedHackRF {
+            state: Arc::new(Mutex::new(SimState {
+                open: true,
+                freq_hz: 0,
+                sample_rate: 10e6,
+                baseband_filter_bandwidth: 15_000_000,
+                amp_enable: false,
+                antenna_enable: false,
+                lna_gain: 0,
+                vga_gain: 0,
+                txvga_gain: 0,
+                max2837,
+                si5351c,
+                rffc5071: Rffc5071Registers::default(),
+                spiflash: vec![0xff; SPIFLASH_SIZE],
+                cpld: Vec::new(),
+                clkout: false,
+                hw_sync: false,
+                operacakes: Vec::new(),
+                operacake_ranges: Vec::new(),
+                operacake_dwells: Vec::new(),
+                signals: Vec::new(),
+                transmitted: Vec::new()
+            })),
+            board_id: ffi::BOARD_ID_HACKRF_ONE,
+            version: String::from("simulated"),
+            usb_api_version: 0x0108,
+            board_rev: ffi::BOARD_REV_GSG | 4,
+            part_id: [0xa000cb3c, 0x00574f4b],
+            serial_no: [0, 0, 0x457863c8, 0x2b7d8b5f],
+            transfer_samples: TRANSFER_SAMPLES,
+            realtime: true,
+            sweep: None,
+            trigger: None,
+            stream: None
+        }
+    }
+
+    /// Add a signal to what the receiver sees.
+    pub fn signal(self, signal: Signal) -> SimulatedHackRF {
+        self.lock().signals.push(signal);
+        self
+    }
+
+    /// Whether a 10MHz reference is connected to CLKIN, in which case the
+    /// Si5351C reports PLLA locked to it.
+    pub fn clkin(self, connected: bool) -> SimulatedHackRF {
+        let source = match connected {
+            true => Si5351cSource::Clkin,
+            false => Si5351cSource::Xtal
+        };
+        self.lock().si5351c.set_reference(source);
+        self
+    }
+
+    /// Connect the board's trigger input and output to `trigger`.
+    pub fn trigger(mut self, trigger: &SimTrigger) -> SimulatedHackRF {
+        self.trigger = Some(trigger.clone());
+        self
+    }
+
+    /// Stack an Opera Cake at I2C `address`, starting in manual mode with
+    /// A0 on A1 and B0 on B1.
+    pub fn operacake(self, address: u8) -> SimulatedHackRF {
+        {
+            let mut state = self.lock();
+            if state.operacake(address).is_none() {
+                state.operacakes.push(SimOperaCake {
+                    address,
+                    mode: OperaCakeMode::Manual,
+                    port_a: OperaCakePort::A1,
+                    port_b: OperaCakePort::B1
+                });
+            }
+        }
+        self
+    }
+
+    /// Put `image` at the start of the SPI flash, which is otherwise
+    /// erased. Like real flash, writing only clears bits until erased.
+    pub fn firmware(self, image: &[u8]) -> SimulatedHackRF {
+        let len = image.len().min(SPIFLASH_SIZE);
+        self.lock().spiflash[..len].copy_from_slice(&image[..len]);
+        self
+    }
+
+    /// Set the board ID reported by `board_id_read`.
+    pub fn board_id(mut self, board_id: BoardId) -> SimulatedHackRF {
+        self.board_id = board_id.into();
+        self
+    }
+
+    /// Set the string reported by `version_string_read`.
+    pub fn version(mut self, version: &str) -> SimulatedHackRF {
+        self.version = version.to_owned();
+        self
+    }
+
+    /// Set the version reported by `usb_api_version_read`.
+    pub fn usb_api_version(mut self, version: u16) -> SimulatedHackRF {
+        self.usb_api_version = version;
+        self
+    }
+
+    /// Set the revision reported by `board_rev_read`.
+    pub fn board_rev(mut self, board_rev: u8) -> SimulatedHackRF {
+        self.board_rev = board_rev;
+        self
+    }
+
+    /// Set the values reported by `board_partid_serialno_read`.
+    pub fn partid_serialno(mut self, part_id: [u32; 2], serial_no: [u32; 4])
+                           -> SimulatedHackRF {
+        self.part_id = part_id;
+        self.serial_no = serial_no;
+        self
+    }
+
+    /// Set how many samples each transfer holds. Sweep transfers are
+    /// rounded down to whole blocks.
+    pub fn transfer_samples(mut self, samples: usize) -> SimulatedHackRF {
+        self.transfer_samples = samples.max(1);
+        self
+    }
+
+    /// Whether transfers are paced at the sample rate, as from a real
+    /// board, or delivered as fast as the callback accepts them. Defaults
+    /// to paced.
+    pub fn realtime(mut self, realtime: bool) -> SimulatedHackRF {
+        self.realtime = realtime;
+        self
+    }
+
+    /// Get a handle to inspect this device once it is in use.
+    pub fn handle(&self) -> SimHandle {
+        SimHandle { state: self.state.clone() }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, SimState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Lock the state of an open device, failing like a disconnected board
+    /// once closed.
+    fn open_state(&self) -> Result<MutexGuard<'_, SimState>, HackRFError> {
+        let state = self.lock();
+        if state.open { Ok(state) } else { Err(HackRFError::Libusb) }
+    }
+
+    fn check_open(&self) -> Result<(), HackRFError> {
+        self.open_state().map(|_| ())
+    }
+
+    fn start<F>(&mut self, run: F) -> Result<(), HackRFError>
+        where F: FnOnce(&AtomicBool) + Send + 'static
+    {
+        self.check_open()?;
+        if self.stream.is_some() {
+            return Err(HackRFError::Busy);
+        }
+        // In sync mode, wait for the next edge; a board with no trigger
+        // wired up never sees one.
+        let hw_sync = self.lock().hw_sync;
+        let armed = match (hw_sync, &self.trigger) {
+            (true, Some(trigger)) => Some((trigger.clone(), trigger.edges())),
+            (true, None) => Some((SimTrigger::new(), 0)),
+            (false, _) => None
+        };
+        let stop = Arc::new(AtomicBool::new(false));
+        let running = Arc::new(AtomicBool::new(true));
+        let (thread_stop, thread_running) = (stop.clone(), running.clone());
+        let thread = thread::Builder::new()
+            .name(String::from("hackrf-sim"))
+            .spawn(move || {
+                let triggered = match armed {
+                    Some((trigger, seen)) => trigger.wait(seen, &thread_stop),
+                    None => true
+                };
+                if triggered {
+                    run(&thread_stop);
+                }
+                thread_running.store(false, Ordering::Relaxed);
+            })
+            .map_err(|_| HackRFError::Thread)?;
+        self.stream = Some(SimStream { stop, running, thread });
+        if let (false, Some(trigger)) = (hw_sync, &self.trigger) {
+            trigger.fire();
+        }
+        Ok(())
+    }
+
+    fn stop(&mut self) -> Result<(), HackRFError> {
+        if let Some(stream) = self.stream.take() {
+            stream.stop();
+        }
+        Ok(())
+    }
+}
+
+/// Sleeps as needed to deliver samples no faster than the sample rate.
+struct Pacer {
+    start: Instant,
+    seconds: f64
+}
+
+impl Pacer {
+    fn new() -> Pacer {
+        Pacer { start: Instant::now(), seconds: 0.0 }
+    }
+
+    fn wait(&mut self, samples: usize, sample_rate: f64) {
+        self.seconds += samples as f64 / sample_rate;
+        let due = self.start + Duration::from_secs_f64(self.seconds);
+        let now = Instant::now();
+        if due > now {
+            thread::sleep(due - now);
+        }
+    }
+}
+
+/// Synthesises received samples, keeping each signal's phase continuous
+/// across transfers and retuning.
+struct Generator {
+    phases: Vec<f64>,
+    positions: Vec<usize>,
+    rng: u64
+}
+
+impl Generator {
+    fn new() -> Generator {
+        Generator { phases: Vec::new(), positions: Vec::new(),
+                    rng: 0x9e3779b97f4a7c15 }
+    }
+
+    /// xorshift64*, uniform in (0, 1].
+    fn uniform(&mut self) -> f64 {
+        self.rng ^= self.rng >> 12;
+        self.rng ^= self.rng << 25;
+        self.rng ^= self.rng >> 27;
+        let x = self.rng.wrapping_mul(0x2545f4914f6cdd1d) >> 11;
+        (x + 1) as f64 / (1u64 << 53) as f64
+    }
+
+    /// A pair of independent standard normal values, by Box-Muller.
+    fn gaussian(&mut self) -> (f64, f64) {
+        let r = (-2.0 * self.uniform().ln()).sqrt();
+        let theta = 2.0 * PI * self.uniform();
+        (r * theta.cos(), r * theta.sin())
+    }
+
+    fn fill(&mut self, buffer: &mut [IQ8], signals: &[Signal], freq_hz: u64,
+            sample_rate: f64) {
+        self.phases.resize(signals.len(), 0.0);
+        self.positions.resize(signals.len(), 0);
+        for sample in buffer.iter_mut() {
+            let mut sum = Complex::new(0.0f64, 0.0);
+            for (k, signal) in signals.iter().enumerate() {
+                match *signal {
+                    Signal::Tone { freq_hz: tone_hz, amplitude } => {
+                        let offset = tone_hz - freq_hz as f64;
+                        if offset.abs() >= sample_rate / 2.0 {
+                            continue;
+                        }
+                        let phase = self.phases[k];
+                        sum += Complex::new(phase.cos(), phase.sin())
+                               * amplitude as f64;
+                        self.phases[k] = (phase + 2.0 * PI * offset
+                                          / sample_rate) % (2.0 * PI);
+                    },
+                    Signal::Noise { amplitude } => {
+                        let (i, q) = self.gaussian();
+                        sum += Complex::new(i, q) * amplitude as f64;
+                    },
+                    Signal::Recording(ref samples) => {
+                        if samples.is_empty() {
+                            continue;
+                        }
+                        let position = self.positions[k] % samples.len();
+                        let s = samples[position].to_complex_f32();
+                        sum += Complex::new(s.re as f64, s.im as f64);
+                        self.positions[k] = position + 1;
+                    }
+                }
+            }
+            *sample = IQ8::from_complex_f32(Complex::new(sum.re as f32,
+                                                         sum.im as f32));
+        }
+    }
+}
+
+impl Backend for SimulatedHackRF {
+    fn close(&mut self) -> Result<(), HackRFError> {
+        self.stop()?;
+        self.lock().open = false;
+        Ok(())
+    }
+
+    fn start_rx(&mut self, mut callback: RxCallback)
+                -> Result<(), HackRFError> {
+        let state = self.state.clone();
+        let transfer_samples = self.transfer_samples;
+        let realtime = self.realtime;
+        self.start(move |stop| {
+            let mut generator = Generator::new();
+            let mut pacer = Pacer::new();
+            let mut buffer = vec![IQ8::default(); transfer_samples];
+            while !stop.load(Ordering::Relaxed) {
+                let (signals, freq_hz, sample_rate) = {
+                    let s = state.lock().unwrap_or_else(|e| e.into_inner());
+                    if !s.open {
+                        break;
+                    }
+                    (s.signals.clone(), s.freq_hz, s.sample_rate)
+                };
+                generator.fill(&mut buffer, &signals, freq_hz, sample_rate);
+                // A panicking callback stops the stream, as with libhackrf.
+                let more = panic::catch_unwind(AssertUnwindSafe(|| {
+                    callback(&buffer)
+                }));
+                if !matches!(more, Ok(true)) {
+                    break;
+                }
+                if realtime {
+                    pacer.wait(buffer.len(), sample_rate);
+                }
+            }
+        })
+    }
+
+    fn stop_rx(&mut self) -> Result<(), HackRFError> {
+        self.stop()
+    }
+
+    fn start_tx(&mut self, mut callback: TxCallback)
+                -> Result<(), HackRFError> {
+        let state = self.state.clone();
+        let transfer_samples = self.transfer_samples;
+        let realtime = self.realtime;
+        self.start(move |stop| {
+            let mut pacer = Pacer::new();
+            let mut buffer = vec![IQ8::default(); transfer_samples];
+            while !stop.load(Ordering::Relaxed) {
+                buffer.fill(IQ8::default());
+                let more = panic::catch_unwind(AssertUnwindSafe(|| {
+                    callback(&mut buffer)
+                }));
+                if !matches!(more, Ok(true)) {
+                    break;
+                }
+                let sample_rate = {
+                    let mut s = state.lock().unwrap_or_else(|e| e.into_inner());
+                    if !s.open {
+                        break;
+                    }
+                    s.transmitted.extend_from_slice(&buffer);
+                    s.sample_rate
+                };
+                if realtime {
+                    pacer.wait(buffer.len(), sample_rate);
+                }
+            }
+        })
+    }
+
+    fn stop_tx(&mut self) -> Result<(), HackRFError> {
+        self.stop()
+    }
+
+    fn is_streaming(&mut self) -> Result<bool, HackRFError> {
+        match self.stream {
+            Some(ref stream) => Ok(stream.running.load(Ordering::Relaxed)),
+            None => Err(HackRFError::StreamingThreadErr)
+        }
+    }
+
+    fn set_baseband_filter_bandwidth(&mut self, bandwidth_hz: u32)
+                                     -> Result<(), HackRFError> {
+        let mut state = self.open_state()?;
+        match state.max2837.set_baseband_filter_bandwidth(bandwidth_hz) {
+            Some(bw) => {
+                state.baseband_filter_bandwidth = bw;
+                Ok(())
+            },
+            None => Err(HackRFError::Libusb)
+        }
+    }
+
+    fn board_id_read(&mut self) -> Result<u8, HackRFError> {
+        self.check_open()?;
+        Ok(self.board_id)
+    }
+
+    fn version_string_read(&mut self) -> Result<String, HackRFError> {
+        self.check_open()?;
+        Ok(self.version.clone())
+    }
+
+    fn board_partid_serialno_read(&mut self)
+                                  -> Result<([u32; 2], [u32; 4]),
+                                            HackRFError> {
+        self.check_open()?;
+        Ok((self.part_id, self.serial_no))
+    }
+
+    fn usb_api_version_read(&mut self) -> Result<u16, HackRFError> {
+        self.check_open()?;
+        Ok(self.usb_api_version)
+    }
+
+    fn board_rev_read(&mut self) -> Result<u8, HackRFError> {
+        self.check_open()?;
+        Ok(self.board_rev)
+    }
+
+    fn set_clkout_enable(&mut self, on: bool) -> Result<(), HackRFError> {
+        self.open_state()?.clkout = on;
+        Ok(())
+    }
+
+    fn clkin_status(&mut self) -> Result<bool, HackRFError> {
+        Ok(self.open_state()?.si5351c.clkin_present())
+    }
+
+    fn set_hw_sync_mode(&mut self, on: bool) -> Result<(), HackRFError> {
+        self.open_state()?.hw_sync = on;
+        Ok(())
+    }
+
+    fn operacake_boards(&mut self) -> Result<Vec<u8>, HackRFError> {
+        let state = self.open_state()?;
+        Ok(state.operacakes.iter().map(|board| board.address).collect())
+    }
+
+    // Like the firmware, requests for an address with no board are
+    // accepted and do nothing.
+    fn set_operacake_ports(&mut self, address: u8, port_a: OperaCakePort,
+                           port_b: OperaCakePort) -> Result<(), HackRFError> {
+        let mut state = self.open_state()?;
+        if let Some(board) = state.operacake(address) {
+            board.port_a = port_a;
+            board.port_b = port_b;
+        }
+        Ok(())
+    }
+
+    fn operacake_mode(&mut self, address: u8)
+                      -> Result<OperaCakeMode, HackRFError> {
+        let mut state = self.open_state()?;
+        Ok(state.operacake(address).map_or(OperaCakeMode::Manual,
+                                           |board| board.mode))
+    }
+
+    fn set_operacake_mode(&mut self, address: u8, mode: OperaCakeMode)
+                          -> Result<(), HackRFError> {
+        let mut state = self.open_state()?;
+        if let Some(board) = state.operacake(address) {
+            board.mode = mode;
+        }
+        Ok(())
+    }
+
+    fn set_operacake_freq_ranges(&mut self, ranges: &[OperaCakeRange])
+                                 -> Result<(), HackRFError> {
+        self.open_state()?.operacake_ranges = ranges.to_vec();
+        Ok(())
+    }
+
+    fn set_operacake_dwell_times(&mut self, dwells: &[OperaCakeDwell])
+                                 -> Result<(), HackRFError> {
+        self.open_state()?.operacake_dwells = dwells.to_vec();
+        Ok(())
+    }
+
+    fn set_freq(&mut self, freq_hz: u64) -> Result<(), HackRFError> {
+        let mut state = self.open_state()?;
+        let plan = match rffc5071::tuning_plan(freq_hz) {
+            Some(plan) if freq_hz <= MAX_FREQ_HZ => plan,
+            _ => return Err(HackRFError::Libusb)
+        };
+        match plan.lo_hz {
+            // The synthesizer lands within a few Hz of the whole MHz the
+            // firmware asked for.
+            Some(lo_hz) => {
+                state.rffc5071.set_lo((lo_hz + 500_000) / 1_000_000);
+                state.rffc5071.set_enabled(true);
+            },
+            None => state.rffc5071.set_enabled(false)
+        }
+        state.max2837.set_lna_band(plan.if_hz);
+        state.freq_hz = freq_hz;
+        state.switch_operacakes(freq_hz);
+        Ok(())
+    }
+
+    fn set_freq_explicit(&mut self, if_freq_hz: u64, lo_freq_hz: u64,
+                         path: RFPathFilter) -> Result<(), HackRFError> {
+        let mut state = self.open_state()?;
+        if !(MIN_IF_HZ..=MAX_IF_HZ).contains(&if_freq_hz) {
+            return Err(HackRFError::InvalidParam);
+        }
+        if path != RFPathFilter::Bypass &&
+           !(MIN_LO_HZ..=MAX_LO_HZ).contains(&lo_freq_hz) {
+            return Err(HackRFError::InvalidParam);
+        }
+        state.max2837.set_lna_band(if_freq_hz);
+        if path == RFPathFilter::Bypass {
+            state.rffc5071.set_enabled(false);
+        } else {
+            state.rffc5071.set_lo(lo_freq_hz / 1_000_000);
+            state.rffc5071.set_enabled(true);
+        }
+        state.freq_hz = path.rf_freq_hz(if_freq_hz, lo_freq_hz);
+        Ok(())
+    }
+
+    fn set_sample_rate_manual(&mut self, freq_hz: u32, divider: u32)
+                              -> Result<(), HackRFError> {
+        let mut state = self.open_state()?;
+        if freq_hz == 0 || divider == 0 {
+            return Err(HackRFError::Libusb);
+        }
+        state.sample_rate = freq_hz as f64 / divider as f64;
+        state.si5351c.set_sample_rate(freq_hz, divider);
+        // libhackrf sets the filter to suit each new sample rate.
+        let filter = default_filter(state.sample_rate);
+        drop(state);
+        self.set_baseband_filter_bandwidth(filter)
+    }
+
+    fn set_sample_rate(&mut self, freq_hz: f64) -> Result<(), HackRFError> {
+        let mut state = self.open_state()?;
+        if !(freq_hz >= 1.0 && freq_hz <= u32::MAX as f64) {
+            return Err(HackRFError::Libusb);
+        }
+        state.sample_rate = freq_hz;
+        // libhackrf picks the smallest divider giving an integer frequency.
+        let divider = (1..32).find(|&d| {
+            let f = freq_hz * d as f64;
+            (f - f.round()).abs() < 1e-6 && f <= u32::MAX as f64
+        }).unwrap_or(1);
+        state.si5351c.set_sample_rate((freq_hz * divider as f64).round()
+                                      as u32, divider);
+        drop(state);
+        self.set_baseband_filter_bandwidth(default_filter(freq_hz))
+    }
+
+    fn set_amp_enable(&mut self, on: bool) -> Result<(), HackRFError> {
+        self.open_state()?.amp_enable = on;
+        Ok(())
+    }
+
+    fn set_lna_gain(&mut self, gain: u32) -> Result<(), HackRFError> {
+        let mut state = self.open_state()?;
+        if gain > 40 {
+            return Err(HackRFError::InvalidParam);
+        }
+        state.lna_gain = gain & !0x07;
+        state.max2837.set_lna_gain_db(gain);
+        Ok(())
+    }
+
+    fn set_vga_gain(&mut self, gain: u32) -> Result<(), HackRFError> {
+        let mut state = self.open_state()?;
+        if gain > 62 {
+            return Err(HackRFError::InvalidParam);
+        }
+        state.vga_gain = gain & !0x01;
+        state.max2837.set_vga_gain_db(gain);
+        Ok(())
+    }
+
+    fn set_txvga_gain(&mut self, gain: u32) -> Result<(), HackRFError> {
+        let mut state = self.open_state()?;
+        if gain > 47 {
+            return Err(HackRFError::InvalidParam);
+        }
+        state.txvga_gain = gain;
+        state.max2837.set_txvga_gain_db(gain);
+        Ok(())
+    }
+
+    fn set_antenna_enable(&mut self, on: bool) -> Result<(), HackRFError> {
+        self.open_state()?.antenna_enable = on;
+        Ok(())
+    }
+
+    fn max2837_read(&mut self, register: u8) -> Result<u16, HackRFError> {
+        Ok(self.open_state()?.max2837.values[register as usize])
+    }
+
+    fn max2837_write(&mut self, register: u8, value: u16)
+                     -> Result<(), HackRFError> {
+        self.open_state()?.max2837.values[register as usize] = value;
+        Ok(())
+    }
+
+    fn si5351c_read(&mut self, register: u8) -> Result<u8, HackRFError> {
+        Ok(self.open_state()?.si5351c.values[register as usize])
+    }
+
+    fn si5351c_write(&mut self, register: u8, value: u8)
+                     -> Result<(), HackRFError> {
+        self.open_state()?.si5351c.values[register as usize] = value;
+        Ok(())
+    }
+
+    fn rffc5071_read(&mut self, register: u8) -> Result<u16, HackRFError> {
+        Ok(self.open_state()?.rffc5071.values[register as usize])
+    }
+
+    fn rffc5071_write(&mut self, register: u8, value: u16)
+                      -> Result<(), HackRFError> {
+        self.open_state()?.rffc5071.values[register as usize] = value;
+        Ok(())
+    }
+
+    fn spiflash_erase(&mut self) -> Result<(), HackRFError> {
+        self.open_state()?.spiflash.fill(0xff);
+        Ok(())
+    }
+
+    fn spiflash_write(&mut self, address: u32, data: &[u8])
+                      -> Result<(), HackRFError> {
+        let mut state = self.open_state()?;
+        let address = address as usize;
+        for (byte, &value) in state.spiflash[address..address + data.len()]
+                                   .iter_mut().zip(data) {
+            *byte &= value;
+        }
+        Ok(())
+    }
+
+    fn spiflash_read(&mut self, address: u32, buffer: &mut [u8])
+                     -> Result<(), HackRFError> {
+        let state = self.open_state()?;
+        let address = address as usize;
+        buffer.copy_from_slice(&state.spiflash[address..address
+                                               + buffer.len()]);
+        Ok(())
+    }
+
+    fn cpld_write(&mut self, xsvf: &[u8], progress: &mut dyn FnMut(usize))
+                  -> Result<(), HackRFError> {
+        self.open_state()?.cpld.clear();
+        for chunk in xsvf.chunks(CPLD_TRANSFER_BYTES) {
+            let sent = {
+                let mut state = self.open_state()?;
+                state.cpld.extend_from_slice(chunk);
+                state.cpld.len()
+            };
+            progress(sent);
+        }
+        Ok(())
+    }
+
+    fn init_sweep(&mut self, config: &SweepConfig)
+                  -> Result<(), HackRFError> {
+        self.check_open()?;
+        config.validate()?;
+        self.sweep = Some(config.clone());
+        Ok(())
+    }
+
+    fn start_rx_sweep(&mut self, mut callback: RxCallback)
+                      -> Result<(), HackRFError> {
+        let config = match self.sweep {
+            Some(ref config) => config.clone(),
+            None => return Err(HackRFError::InvalidParam)
+        };
+        let state = self.state.clone();
+        let blocks = (self.transfer_samples / SWEEP_BLOCK_SAMPLES).max(1);
+        let realtime = self.realtime;
+        self.start(move |stop| {
+            let steps = config.steps();
+            let dwell = config.dwell_blocks();
+            let mut generator = Generator::new();
+            let mut pacer = Pacer::new();
+            let mut buffer = vec![IQ8::default(); blocks * SWEEP_BLOCK_SAMPLES];
+            let (mut step, mut dwelt) = (0, 0);
+            while !stop.load(Ordering::Relaxed) {
+                let (signals, sample_rate) = {
+                    let s = state.lock().unwrap_or_else(|e| e.into_inner());
+                    if !s.open {
+                        break;
+                    }
+                    (s.signals.clone(), s.sample_rate)
+                };
+                for block in buffer.chunks_mut(SWEEP_BLOCK_SAMPLES) {
+                    let freq_hz = steps[step];
+                    SweepBlock::write_header(block, freq_hz);
+                    generator.fill(&mut block[SWEEP_HEADER_BYTES / 2..],
+                                   &signals, freq_hz + config.offset as u64,
+                                   sample_rate);
+                    dwelt += 1;
+                    if dwelt == dwell {
+                        dwelt = 0;
+                        step = (step + 1) % steps.len();
+                    }
+                }
+                let more = panic::catch_unwind(AssertUnwindSafe(|| {
+                    callback(&buffer)
+                }));
+                if !matches!(more, Ok(true)) {
+                    break;
+                }
+                if realtime {
+                    pacer.wait(buffer.len(), sample_rate);
+                }
+            }
+        })
+    }
+}
@@ -0,0 +1,287 @@
+// Power spectra from sweeps, in hackrf_sweep's CSV format
+// Copyright Adam Greig <adam@adamgreig.com> 2014
+// Licensed under MIT license
+
+//! Power spectra from sweep mode, enabled by the `spectrum` feature.
+//!
+//! This follows `hackrf_sweep`: the last `fft_size` samples of each block
+//! are Hann windowed and transformed, and each step's capture produces two
+//! rows, one for the quarter of the band starting at the step frequency and
+//! one for the quarter starting half a sample rate above it. The bins near
+//! DC and the band edges are discarded. Blocks captured at the same step are
+//! averaged before conversion to dB. Rows print as `hackrf_sweep` CSV lines,
+//! with times in the local time zone.
+
+use std::f32::consts::PI;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use rustfft::num_complex::Complex;
+use rustfft::{Fft, FftPlanner};
+
+use crate::stream::RxStream;
+use crate::sweep::{SweepBlock, SweepConfig, SweepStyle};
+use crate::{HackRFDevice, HackRFError, SWEEP_BLOCK_SAMPLES,
+            SWEEP_HEADER_BYTES};
+
+/// Sample rate used by `hackrf_sweep` and `HackRFDevice::sweep_spectrum`.
+pub const SWEEP_SAMPLE_RATE_HZ: u32 = 20_000_000;
+
+/// Baseband filter used by `hackrf_sweep` and
+/// `HackRFDevice::sweep_spectrum`.
+pub const SWEEP_BASEBAND_FILTER_HZ: u32 = 15_000_000;
+
+/// One line of `hackrf_sweep` output: the power in each bin between
+/// `hz_low` and `hz_high`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SpectrumRow {
+    /// When the first transfer of this step was received.
+    pub time: SystemTime,
+    pub hz_low: u64,
+    pub hz_high: u64,
+    pub hz_bin_width: f64,
+    /// The FFT size, which `hackrf_sweep` reports as the sample count.
+    pub num_samples: u32,
+    /// Power in each bin, in dB relative to full scale.
+    pub db: Vec<f32>
+}
+
+impl SpectrumRow {
+    /// Format as a CSV line without the trailing newline, with the time
+    /// `utc_offset_secs` ahead of UTC rather than in the local time zone.
+    pub fn to_csv_with_offset(&self, utc_offset_secs: i64) -> String {
+        let mut line = format!("{}, {}, {}, {:.2}, {}",
+                               format_time(self.time, utc_offset_secs),
+                               self.hz_low, self.hz_high, self.hz_bin_width,
+                               self.num_samples);
+        for db in &self.db {
+            line += &format!(", {:.2}", db);
+        }
+        line
+    }
+}
+
+impl fmt::Display for SpectrumRow {
+    /// Format as a CSV line without the trailing newline, with the time in
+    /// the local time zone as `hackrf_sweep` prints it.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.to_csv_with_offset(local_utc_offset(self.time)))
+    }
+}
+
+/// How far ahead of UTC local time was at `time`, in seconds.
+#[cfg(unix)]
+fn local_utc_offset(time: SystemTime) -> i64 {
+    let since_epoch = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    let secs = since_epoch.as_secs() as libc::time_t;
+    let mut tm: libc::tm = unsafe { std::mem::zeroed() };
+    if unsafe { libc::localtime_r(&secs, &mut tm) }.is_null() {
+        return 0;
+    }
+    tm.tm_gmtoff as i64
+}
+
+/// Local time is not available here, so times are printed in UTC.
+#[cfg(not(unix))]
+fn local_utc_offset(_time: SystemTime) -> i64 {
+    0
+}
+
+/// Format `time` as `YYYY-MM-DD, HH:MM:SS.uuuuuu`, `utc_offset_secs` ahead
+/// of UTC.
+fn format_time(time: SystemTime, utc_offset_secs: i64) -> String {
+    let since_epoch = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    let secs = since_epoch.as_secs() as i64 + utc_offset_secs;
+    let (days, secs_of_day) = (secs.div_euclid(86400), secs.rem_euclid(86400));
+
+    // Days since 1970-01-01 to a civil date, after Howard Hinnant.
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    format!("{:04}-{:02}-{:02}, {:02}:{:02}:{:02}.{:06}", year, month, day,
+            secs_of_day / 3600, secs_of_day / 60 % 60, secs_of_day % 60,
+            since_epoch.subsec_micros())
+}
+
+/// Accumulated power for the step currently being received.
+struct Step {
+    freq_hz: u64,
+    time: SystemTime,
+    power: Vec<f32>,
+    blocks: u32
+}
+
+/// Turns sweep blocks into `SpectrumRow`s.
+pub struct PowerSpectrum {
+    sample_rate: u32,
+    fft_size: usize,
+    bin_width: f64,
+    window: Vec<f32>,
+    fft: Arc<dyn Fft<f32>>,
+    buffer: Vec<Complex<f32>>,
+    step: Option<Step>
+}
+
+impl PowerSpectrum {
+    /// Prepare to process sweeps sampled at `sample_rate` with bins of
+    /// about `bin_width_hz`. As in `hackrf_sweep`, the FFT size is rounded
+    /// up to an odd multiple of four and the bin width adjusted to match.
+    /// Returns `HackRFError::InvalidParam` if the FFT would be smaller than
+    /// four bins or larger than a block.
+    pub fn new(sample_rate: u32, bin_width_hz: u32)
+               -> Result<PowerSpectrum, HackRFError> {
+        if bin_width_hz == 0 {
+            return Err(HackRFError::InvalidParam);
+        }
+        let mut fft_size = (sample_rate / bin_width_hz) as usize;
+        while !(fft_size + 4).is_multiple_of(8) {
+            fft_size += 1;
+        }
+        let max_size = SWEEP_BLOCK_SAMPLES - SWEEP_HEADER_BYTES / 2;
+        if fft_size < 4 || fft_size > max_size {
+            return Err(HackRFError::InvalidParam);
+        }
+        let window = (0..fft_size).map(|i| {
+            0.5 * (1.0 - (2.0 * PI * i as f32 / (fft_size - 1) as f32).cos())
+        }).collect();
+        Ok(PowerSpectrum {
+            sample_rate,
+            fft_size,
+            bin_width: sample_rate as f64 / fft_size as f64,
+            window,
+            fft: FftPlanner::new().plan_fft_forward(fft_size),
+            buffer: vec![Complex::new(0.0, 0.0); fft_size],
+            step: None
+        })
+    }
+
+    pub fn fft_size(&self) -> usize {
+        self.fft_size
+    }
+
+    /// The actual width of each bin, in Hz.
+    pub fn bin_width(&self) -> f64 {
+        self.bin_width
+    }
+
+    /// Add one block received at `time`. Returns the two rows for the
+    /// previous step once a block from a new step arrives, or nothing.
+    pub fn push(&mut self, block: &SweepBlock, time: SystemTime)
+                -> Vec<SpectrumRow> {
+        let rows = match self.step {
+            Some(ref step) if step.freq_hz != block.freq_hz => self.flush(),
+            _ => Vec::new()
+        };
+        let samples = &block.samples[block.samples.len() - self.fft_size..];
+        for ((out, s), w) in self.buffer.iter_mut().zip(samples)
+                                 .zip(&self.window) {
+            let c = s.to_complex_f32();
+            *out = Complex::new(c.re * w, c.im * w);
+        }
+        self.fft.process(&mut self.buffer);
+
+        let fft_size = self.fft_size;
+        let step = self.step.get_or_insert_with(|| Step {
+            freq_hz: block.freq_hz, time,
+            power: vec![0.0; fft_size], blocks: 0
+        });
+        let scale = 1.0 / fft_size as f32;
+        for (p, c) in step.power.iter_mut().zip(&self.buffer) {
+            *p += (c * scale).norm_sqr();
+        }
+        step.blocks += 1;
+        rows
+    }
+
+    /// Emit the rows for the step in progress, if any.
+    pub fn flush(&mut self) -> Vec<SpectrumRow> {
+        let step = match self.step.take() {
+            Some(step) => step,
+            None => return Vec::new()
+        };
+        let n = self.fft_size;
+        let quarter = self.sample_rate as u64 / 4;
+        let db = |first: usize| -> Vec<f32> {
+            step.power[first..first + n / 4].iter().map(|p| {
+                10.0 * (p / step.blocks as f32).log10()
+            }).collect()
+        };
+        let row = |hz_low: u64, first: usize| SpectrumRow {
+            time: step.time,
+            hz_low,
+            hz_high: hz_low + quarter,
+            hz_bin_width: self.bin_width,
+            num_samples: n as u32,
+            db: db(first)
+        };
+        vec![row(step.freq_hz, 1 + (n * 5) / 8),
+             row(step.freq_hz + 2 * quarter, 1 + n / 8)]
+    }
+}
+
+/// A `PowerSpectrum` feeding rows to a callback until it returns `false`.
+struct SpectrumSink<F> {
+    spectrum: PowerSpectrum,
+    callback: F,
+    running: bool
+}
+
+impl<F> SpectrumSink<F> where F: FnMut(&SpectrumRow) -> bool {
+    fn emit(&mut self, rows: Vec<SpectrumRow>) -> bool {
+        if self.running {
+            self.running = rows.iter().all(&mut self.callback);
+        }
+        self.running
+    }
+}
+
+impl HackRFDevice {
+    /// Sweep as `hackrf_sweep` does, passing each row of the power spectrum
+    /// to `callback` until it returns `false` or the returned stream is
+    /// stopped or dropped. Stopping the stream passes the rows for the last
+    /// step too.
+    /// This sets the sample rate to `SWEEP_SAMPLE_RATE_HZ` and the baseband
+    /// filter to `SWEEP_BASEBAND_FILTER_HZ`, and `config` must use
+    /// interleaved steps one sample rate wide with an offset of three
+    /// eighths of it, as `SweepConfig::new` does. Otherwise returns
+    /// `HackRFError::InvalidParam`.
+    pub fn sweep_spectrum<F>(&mut self, config: &SweepConfig,
+                             bin_width_hz: u32, callback: F)
+                             -> Result<RxStream<'_>, HackRFError>
+        where F: FnMut(&SpectrumRow) -> bool + Send + 'static
+    {
+        if config.style != SweepStyle::Interleaved ||
+           config.step_width != SWEEP_SAMPLE_RATE_HZ ||
+           config.offset != SWEEP_SAMPLE_RATE_HZ / 8 * 3 {
+            return Err(HackRFError::InvalidParam);
+        }
+        let spectrum = PowerSpectrum::new(SWEEP_SAMPLE_RATE_HZ,
+                                          bin_width_hz)?;
+        self.set_sample_rate(SWEEP_SAMPLE_RATE_HZ as f64)?;
+        self.set_baseband_filter_bandwidth(SWEEP_BASEBAND_FILTER_HZ)?;
+        let sink = Arc::new(Mutex::new(SpectrumSink {
+            spectrum, callback, running: true
+        }));
+        let last = sink.clone();
+        let stream = self.sweep(config, move |block: SweepBlock| {
+            let mut sink = sink.lock().unwrap();
+            let rows = sink.spectrum.push(&block, SystemTime::now());
+            sink.emit(rows)
+        })?;
+        Ok(stream.on_stop(move || {
+            if let Ok(mut sink) = last.lock() {
+                let rows = sink.spectrum.flush();
+                sink.emit(rows);
+                sink.running = false;
+            }
+        }))
+    }
+}
@@ -0,0 +1,212 @@
+// SPI flash access and firmware updates
+// Copyright Adam Greig <adam@adamgreig.com> 2014
+// Licensed under MIT license
+
+use std::error::Error;
+use std::fmt;
+
+use crate::{HackRFDevice, HackRFError};
+
+/// Size of the SPI flash holding the firmware, in bytes.
+pub const SPIFLASH_SIZE: usize = 0x100000;
+
+/// Largest read or write libhackrf makes in one request, and the flash's
+/// page size.
+pub const SPIFLASH_PAGE_SIZE: usize = 256;
+
+/// RAM the LPC43xx's initial stack pointer may point into: local SRAM and
+/// AHB SRAM.
+const RAM_RANGES: [(u32, u32); 2] = [
+    (0x1000_0000, 0x1009_2000), (0x2000_0000, 0x2001_0000)
+];
+
+/// What `flash_firmware` and `read_firmware` are doing.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum FlashStage { Reading, Erasing, Writing, Verifying }
+
+/// Progress reported to the callback of `flash_firmware` and
+/// `read_firmware`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct FlashProgress {
+    pub stage: FlashStage,
+    /// Bytes done so far in this stage.
+    pub done: usize,
+    /// Bytes this stage will handle in total.
+    pub total: usize
+}
+
+/// Why a firmware or CPLD image was refused or did not write correctly.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum FirmwareError {
+    /// The image failed a sanity check and nothing was written, with the
+    /// reason.
+    Invalid(String),
+    /// Flash read back after writing differed from the image, first at the
+    /// given address.
+    VerifyFailed(u32),
+    /// libhackrf failed while talking to the device.
+    Device(HackRFError)
+}
+
+impl From<HackRFError> for FirmwareError {
+    fn from(err: HackRFError) -> FirmwareError {
+        FirmwareError::Device(err)
+    }
+}
+
+impl fmt::Display for FirmwareError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            FirmwareError::Invalid(ref reason) =>
+                write!(f, "firmware image rejected: {}", reason),
+            FirmwareError::VerifyFailed(address) =>
+                write!(f, "flash verification failed at 0x{:06x}", address),
+            FirmwareError::Device(ref err) => err.fmt(f)
+        }
+    }
+}
+
+impl Error for FirmwareError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match *self {
+            FirmwareError::Device(ref err) => Some(err),
+            _ => None
+        }
+    }
+}
+
+/// Check that `image` looks like HackRF firmware for the LPC43xx, as a
+/// `.bin` rather than a `.dfu`, returning `FirmwareError::Invalid` with the
+/// reason if not. This catches truncated, blank and wrong files rather than
+/// proving the image will run.
+pub fn check_firmware_image(image: &[u8]) -> Result<(), FirmwareError> {
+    let reject = |reason: &str| Err(FirmwareError::Invalid(
+        reason.to_owned()));
+    if image.len() < 8 {
+        return reject("image is too short to hold a vector table");
+    }
+    if image.len() > SPIFLASH_SIZE {
+        return reject("image is larger than the SPI flash");
+    }
+    if image.iter().all(|&b| b == 0xff) || image.iter().all(|&b| b == 0) {
+        return reject("image is blank");
+    }
+    let suffix = image.len().saturating_sub(8);
+    if image.len() >= 16 && &image[suffix..suffix + 3] == b"UFD" {
+        return reject("image has a DFU suffix; flash the .bin instead");
+    }
+    let word = |i: usize| u32::from_le_bytes([image[i], image[i + 1],
+                                              image[i + 2], image[i + 3]]);
+    let (stack, reset) = (word(0), word(4));
+    let stack_ok = stack.is_multiple_of(4) &&
+        RAM_RANGES.iter().any(|&(low, high)| stack > low && stack <= high);
+    if !stack_ok {
+        return reject("initial stack pointer is not in RAM");
+    }
+    if reset & 1 == 0 {
+        return reject("reset vector is not a Thumb address");
+    }
+    Ok(())
+}
+
+impl HackRFDevice {
+    /// Read `buffer.len()` bytes of SPI flash starting at `address`.
+    /// Returns `HackRFError::InvalidParam` if this runs past the end of the
+    /// flash.
+    pub fn spiflash_read(&mut self, address: u32, buffer: &mut [u8])
+                         -> Result<(), HackRFError> {
+        check_range(address, buffer.len())?;
+        for (i, chunk) in buffer.chunks_mut(SPIFLASH_PAGE_SIZE).enumerate() {
+            let offset = (i * SPIFLASH_PAGE_SIZE) as u32;
+            self.backend.spiflash_read(address + offset, chunk)?;
+        }
+        Ok(())
+    }
+
+    /// Write `data` to SPI flash starting at `address`, which must have
+    /// been erased first.
+    /// Returns `HackRFError::InvalidParam` if this runs past the end of the
+    /// flash.
+    pub fn spiflash_write(&mut self, address: u32, data: &[u8])
+                          -> Result<(), HackRFError> {
+        check_range(address, data.len())?;
+        for (i, chunk) in data.chunks(SPIFLASH_PAGE_SIZE).enumerate() {
+            let offset = (i * SPIFLASH_PAGE_SIZE) as u32;
+            self.backend.spiflash_write(address + offset, chunk)?;
+        }
+        Ok(())
+    }
+
+    /// Erase the whole SPI flash, leaving every byte 0xff.
+    pub fn spiflash_erase(&mut self) -> Result<(), HackRFError> {
+        self.backend.spiflash_erase()
+    }
+
+    /// Read the whole SPI flash, for example to back up the firmware
+    /// before an update. `progress` is called after each page.
+    pub fn read_firmware<F>(&mut self, mut progress: F)
+                            -> Result<Vec<u8>, HackRFError>
+        where F: FnMut(FlashProgress)
+    {
+        let mut image = vec![0; SPIFLASH_SIZE];
+        self.read_pages(0, &mut image, FlashStage::Reading, &mut progress)?;
+        Ok(image)
+    }
+
+    /// Replace the firmware with `image`, the contents of a `.bin` file:
+    /// check it with `check_firmware_image`, erase the flash, write the
+    /// image a page at a time and read it back to compare. `progress` is
+    /// called as each stage starts and after each page.
+    /// Returns `FirmwareError::Invalid` without touching the flash if the
+    /// image is rejected, and `FirmwareError::VerifyFailed` if what was read
+    /// back differs. The new firmware runs after the HackRF is reset.
+    pub fn flash_firmware<F>(&mut self, image: &[u8], mut progress: F)
+                             -> Result<(), FirmwareError>
+        where F: FnMut(FlashProgress)
+    {
+        check_firmware_image(image)?;
+        let total = image.len();
+
+        progress(FlashProgress { stage: FlashStage::Erasing, done: 0, total });
+        self.spiflash_erase()?;
+        progress(FlashProgress { stage: FlashStage::Erasing, done: total,
+                                 total });
+
+        progress(FlashProgress { stage: FlashStage::Writing, done: 0, total });
+        for (i, page) in image.chunks(SPIFLASH_PAGE_SIZE).enumerate() {
+            let address = i * SPIFLASH_PAGE_SIZE;
+            self.backend.spiflash_write(address as u32, page)?;
+            progress(FlashProgress { stage: FlashStage::Writing,
+                                     done: address + page.len(), total });
+        }
+
+        let mut readback = vec![0; total];
+        self.read_pages(0, &mut readback, FlashStage::Verifying,
+                        &mut progress)?;
+        match image.iter().zip(&readback).position(|(a, b)| a != b) {
+            Some(i) => Err(FirmwareError::VerifyFailed(i as u32)),
+            None => Ok(())
+        }
+    }
+
+    fn read_pages(&mut self, address: u32, buffer: &mut [u8],
+                  stage: FlashStage, progress: &mut dyn FnMut(FlashProgress))
+                  -> Result<(), HackRFError> {
+        let total = buffer.len();
+        progress(FlashProgress { stage, done: 0, total });
+        for (i, page) in buffer.chunks_mut(SPIFLASH_PAGE_SIZE).enumerate() {
+            let offset = i * SPIFLASH_PAGE_SIZE;
+            self.backend.spiflash_read(address + offset as u32, page)?;
+            progress(FlashProgress { stage, done: offset + page.len(),
+                                     total });
+        }
+        Ok(())
+    }
+}
+
+fn check_range(address: u32, len: usize) -> Result<(), HackRFError> {
+    if address as usize + len > SPIFLASH_SIZE {
+        return Err(HackRFError::InvalidParam);
+    }
+    Ok(())
+}
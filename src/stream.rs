@@ -0,0 +1,245 @@
+// RAII guards for RX and TX streams
+// Copyright Adam Greig <adam@adamgreig.com> 2014
+// Licensed under MIT license
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::buffer::{BufferPool, SampleBuffer};
+use crate::{HackRFDevice, HackRFError, IQ8};
+
+/// How often a blocked `recv` checks the device is still streaming.
+const RECV_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Run when a stream is stopped, to end whatever the callback feeds, as
+/// the device keeps the callback itself until it is replaced or closed.
+type OnStop = Box<dyn FnOnce() + Send>;
+
+/// An active RX stream. Receiving stops when this is dropped.
+/// The stream borrows the device mutably, so the device cannot be
+/// reconfigured or start a TX stream while it is alive.
+///
+/// Streams started with `HackRFDevice::rx_buffers` queue received data for
+/// `recv`, or can be iterated over to get each `SampleBuffer` in turn.
+pub struct RxStream<'a> {
+    device: &'a mut HackRFDevice,
+    stopped: bool,
+    pool: Option<Arc<BufferPool>>,
+    on_stop: Option<OnStop>
+}
+
+/// An active TX stream. Transmitting stops when this is dropped.
+/// The stream borrows the device mutably, so the device cannot be
+/// reconfigured or start an RX stream while it is alive.
+pub struct TxStream<'a> {
+    device: &'a mut HackRFDevice,
+    stopped: bool,
+    on_stop: Option<OnStop>
+}
+
+impl HackRFDevice {
+    /// Begin receiving.
+    /// `callback` is a closure like:
+    ///     callback(buffer: &[IQ8]) -> bool
+    /// which is given `buffer`, the received samples, and returns `true` if it
+    /// should continue receiving data or `false` to stop. It runs on
+    /// libhackrf's transfer thread until the returned stream is stopped or
+    /// dropped.
+    pub fn rx<F>(&mut self, callback: F) -> Result<RxStream<'_>, HackRFError>
+        where F: FnMut(&[IQ8]) -> bool + Send + 'static
+    {
+        self.start_rx(callback)?;
+        Ok(RxStream::started(self))
+    }
+
+    /// Begin receiving into a pool of `count` reusable buffers, to be read
+    /// with `RxStream::recv` or by iterating over the stream.
+    /// The transfer thread never waits for the consumer: if all `count`
+    /// buffers are queued or still held, new transfers are dropped and
+    /// reported through `SampleBuffer::dropped_before` and
+    /// `RxStream::dropped`.
+    pub fn rx_buffers(&mut self, count: usize)
+                      -> Result<RxStream<'_>, HackRFError> {
+        self.try_rx_buffers(count).map_err(|(err, _)| err)
+    }
+
+    /// As `rx_buffers`, but handing the device back on error so it can be
+    /// cleaned up.
+    pub(crate) fn try_rx_buffers(&mut self, count: usize)
+        -> Result<RxStream<'_>, (HackRFError, &mut HackRFDevice)>
+    {
+        if count == 0 {
+            return Err((HackRFError::InvalidParam, self));
+        }
+        let pool = Arc::new(BufferPool::new(count));
+        let cb_pool = pool.clone();
+        let started = self.start_rx(move |buffer: &[IQ8]| {
+            cb_pool.push(IQ8::as_bytes(buffer))
+        });
+        if let Err(err) = started {
+            return Err((err, self));
+        }
+        let mut stream = RxStream::started(self);
+        stream.pool = Some(pool);
+        Ok(stream)
+    }
+
+    /// Begin transmitting.
+    /// `callback` is a closure like:
+    ///     callback(buffer: &mut [IQ8]) -> bool
+    /// which is given `buffer`, the TX samples, to fill with samples, and
+    /// returns `true` if it should continue sending data or `false` to stop.
+    /// It runs on libhackrf's transfer thread until the returned stream is
+    /// stopped or dropped.
+    pub fn tx<F>(&mut self, callback: F) -> Result<TxStream<'_>, HackRFError>
+        where F: FnMut(&mut [IQ8]) -> bool + Send + 'static
+    {
+        self.start_tx(callback)?;
+        Ok(TxStream { device: self, stopped: false, on_stop: None })
+    }
+}
+
+impl<'a> RxStream<'a> {
+    /// Guard a stream already started on `device` with a callback.
+    pub(crate) fn started(device: &'a mut HackRFDevice) -> RxStream<'a> {
+        RxStream { device, stopped: false, pool: None, on_stop: None }
+    }
+
+    /// Run `f` when the stream is stopped or dropped.
+    pub(crate) fn on_stop<F>(mut self, f: F) -> RxStream<'a>
+        where F: FnOnce() + Send + 'static
+    {
+        self.on_stop = Some(Box::new(f));
+        self
+    }
+
+    fn finish(&mut self) {
+        self.stopped = true;
+        if let Some(ref pool) = self.pool {
+            pool.finish();
+        }
+        if let Some(f) = self.on_stop.take() {
+            f();
+        }
+    }
+
+    /// Check if the device is still receiving. Returns false once the
+    /// callback has asked to stop, and an error if streaming failed.
+    pub fn is_streaming(&mut self) -> Result<bool, HackRFError> {
+        self.device.is_streaming()
+    }
+
+    /// Wait for the next buffer of received samples.
+    /// Returns `HackRFError::StreamingStopped` once the device stops
+    /// streaming and every queued buffer has been received, or
+    /// `HackRFError::InvalidParam` if this stream was started with a
+    /// callback rather than `rx_buffers`.
+    pub fn recv(&mut self) -> Result<SampleBuffer, HackRFError> {
+        let pool = match self.pool {
+            Some(ref pool) => pool.clone(),
+            None => return Err(HackRFError::InvalidParam)
+        };
+        loop {
+            if let Some(buffer) = BufferPool::pop(&pool,
+                                                  Some(RECV_POLL_INTERVAL)) {
+                return Ok(buffer);
+            }
+            if !self.device.is_streaming()? {
+                return match BufferPool::pop(&pool, Some(Duration::ZERO)) {
+                    Some(buffer) => Ok(buffer),
+                    None => Err(HackRFError::StreamingStopped)
+                };
+            }
+        }
+    }
+
+    /// Take the next buffer of received samples if one is already queued.
+    pub fn try_recv(&mut self) -> Option<SampleBuffer> {
+        match self.pool {
+            Some(ref pool) => BufferPool::pop(pool, Some(Duration::ZERO)),
+            None => None
+        }
+    }
+
+    /// Total number of transfers dropped so far because the consumer did
+    /// not keep up. Always zero for streams started with a callback.
+    pub fn dropped(&self) -> u64 {
+        match self.pool {
+            Some(ref pool) => pool.dropped(),
+            None => 0
+        }
+    }
+
+    /// Stop receiving, reporting any error from libhackrf.
+    pub fn stop(mut self) -> Result<(), HackRFError> {
+        self.finish();
+        self.device.stop_rx()
+    }
+
+    /// Stop receiving and then run `after` on the device, reporting the
+    /// first error.
+    pub(crate) fn stop_with<F>(mut self, after: F) -> Result<(), HackRFError>
+        where F: FnOnce(&mut HackRFDevice) -> Result<(), HackRFError>
+    {
+        self.finish();
+        let stopped = self.device.stop_rx();
+        let after = after(self.device);
+        stopped.and(after)
+    }
+}
+
+impl<'a> Iterator for RxStream<'a> {
+    type Item = SampleBuffer;
+
+    /// Wait for the next buffer, ending when streaming stops or fails.
+    fn next(&mut self) -> Option<SampleBuffer> {
+        self.recv().ok()
+    }
+}
+
+impl<'a> Drop for RxStream<'a> {
+    fn drop(&mut self) {
+        if !self.stopped {
+            self.finish();
+            let _ = self.device.stop_rx();
+        }
+    }
+}
+
+impl<'a> TxStream<'a> {
+    /// Run `f` when the stream is stopped or dropped.
+    pub(crate) fn on_stop<F>(mut self, f: F) -> TxStream<'a>
+        where F: FnOnce() + Send + 'static
+    {
+        self.on_stop = Some(Box::new(f));
+        self
+    }
+
+    fn finish(&mut self) {
+        self.stopped = true;
+        if let Some(f) = self.on_stop.take() {
+            f();
+        }
+    }
+
+    /// Check if the device is still transmitting. Returns false once the
+    /// callback has asked to stop, and an error if streaming failed.
+    pub fn is_streaming(&mut self) -> Result<bool, HackRFError> {
+        self.device.is_streaming()
+    }
+
+    /// Stop transmitting, reporting any error from libhackrf.
+    pub fn stop(mut self) -> Result<(), HackRFError> {
+        self.finish();
+        self.device.stop_tx()
+    }
+}
+
+impl<'a> Drop for TxStream<'a> {
+    fn drop(&mut self) {
+        if !self.stopped {
+            self.finish();
+            let _ = self.device.stop_tx();
+        }
+    }
+}
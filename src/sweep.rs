@@ -0,0 +1,217 @@
+// Frequency sweep mode
+// Copyright Adam Greig <adam@adamgreig.com> 2014
+// Licensed under MIT license
+
+use crate::stream::RxStream;
+use crate::{HackRFDevice, HackRFError, IQ8};
+
+/// Bytes in each block of a sweep transfer, including its header.
+pub const SWEEP_BLOCK_BYTES: usize = 16384;
+
+/// Samples in each block of a sweep transfer, including its header.
+pub const SWEEP_BLOCK_SAMPLES: usize = SWEEP_BLOCK_BYTES / 2;
+
+/// Bytes at the start of each block: `0x7f 0x7f` and then the block's
+/// frequency in Hz as a little-endian u64.
+pub const SWEEP_HEADER_BYTES: usize = 10;
+
+/// Most frequency ranges a single sweep can cover.
+pub const MAX_SWEEP_RANGES: usize = 10;
+
+/// Most samples captured at each step. libhackrf takes the dwell in bytes
+/// as a u32, two per sample.
+pub const MAX_SWEEP_DWELL_SAMPLES: u32 =
+    (u32::MAX / 2) / SWEEP_BLOCK_SAMPLES as u32 * SWEEP_BLOCK_SAMPLES as u32;
+
+/// Highest frequency a sweep can reach, in MHz.
+const MAX_SWEEP_FREQ_MHZ: u16 = 7250;
+
+const ONE_MHZ: u64 = 1_000_000;
+
+/// How the firmware steps through each range.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum SweepStyle {
+    /// Step by the full step width each time.
+    Linear,
+    /// Alternate steps of a quarter and three quarters of the step width,
+    /// so that with `hackrf_sweep`'s offset the good parts of each capture
+    /// tile the range.
+    Interleaved
+}
+
+/// The frequencies, step and dwell for `HackRFDevice::sweep`.
+/// Defaults match `hackrf_sweep`, which expects a 20Msps sample rate.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SweepConfig {
+    /// (start, stop) ranges in MHz, swept in order.
+    pub ranges: Vec<(u16, u16)>,
+    /// Distance between steps, in Hz.
+    pub step_width: u32,
+    /// How far above each step's frequency the radio is tuned, in Hz.
+    pub offset: u32,
+    /// Samples captured at each step, a multiple of `SWEEP_BLOCK_SAMPLES`
+    /// up to `MAX_SWEEP_DWELL_SAMPLES`.
+    pub dwell_samples: u32,
+    pub style: SweepStyle
+}
+
+impl SweepConfig {
+    /// Sweep from `start_mhz` up to `stop_mhz`.
+    pub fn new(start_mhz: u16, stop_mhz: u16) -> SweepConfig {
+        SweepConfig {
+            ranges: vec![(start_mhz, stop_mhz)],
+            step_width: 20_000_000,
+            offset: 7_500_000,
+            dwell_samples: SWEEP_BLOCK_SAMPLES as u32,
+            style: SweepStyle::Interleaved
+        }
+    }
+
+    /// Add another range to sweep after the previous ones.
+    pub fn range(mut self, start_mhz: u16, stop_mhz: u16) -> SweepConfig {
+        self.ranges.push((start_mhz, stop_mhz));
+        self
+    }
+
+    /// Set the distance between steps, in Hz.
+    pub fn step_width(mut self, step_width_hz: u32) -> SweepConfig {
+        self.step_width = step_width_hz;
+        self
+    }
+
+    /// Set how far above each step's frequency the radio is tuned, in Hz.
+    pub fn offset(mut self, offset_hz: u32) -> SweepConfig {
+        self.offset = offset_hz;
+        self
+    }
+
+    /// Set how many samples to capture at each step, a multiple of
+    /// `SWEEP_BLOCK_SAMPLES`.
+    pub fn dwell_samples(mut self, samples: u32) -> SweepConfig {
+        self.dwell_samples = samples;
+        self
+    }
+
+    pub fn style(mut self, style: SweepStyle) -> SweepConfig {
+        self.style = style;
+        self
+    }
+
+    /// Blocks captured at each step.
+    pub fn dwell_blocks(&self) -> usize {
+        self.dwell_samples as usize / SWEEP_BLOCK_SAMPLES
+    }
+
+    /// Check the configuration the way libhackrf and `hackrf_sweep` do.
+    pub fn validate(&self) -> Result<(), HackRFError> {
+        let ranges_ok = !self.ranges.is_empty() &&
+            self.ranges.len() <= MAX_SWEEP_RANGES &&
+            self.ranges.iter().all(|&(start, stop)| {
+                start < stop && stop <= MAX_SWEEP_FREQ_MHZ
+            });
+        let min_step = match self.style {
+            SweepStyle::Linear => 1,
+            SweepStyle::Interleaved => 4
+        };
+        let dwell_ok = self.dwell_samples > 0 &&
+            self.dwell_samples <= MAX_SWEEP_DWELL_SAMPLES &&
+            (self.dwell_samples as usize).is_multiple_of(SWEEP_BLOCK_SAMPLES);
+        if !ranges_ok || !dwell_ok || self.step_width < min_step {
+            return Err(HackRFError::InvalidParam);
+        }
+        Ok(())
+    }
+
+    /// The frequency of every step in one pass of the sweep, in the order
+    /// the firmware visits them. Each block received is tagged with one of
+    /// these, and the radio is tuned `offset` above it.
+    pub fn steps(&self) -> Vec<u64> {
+        let step = self.step_width as u64;
+        let mut steps = Vec::new();
+        for &(start, stop) in &self.ranges {
+            let stop = stop as u64 * ONE_MHZ;
+            let mut freq = start as u64 * ONE_MHZ;
+            let mut odd = false;
+            while freq < stop {
+                steps.push(freq);
+                freq += match self.style {
+                    SweepStyle::Linear => step,
+                    SweepStyle::Interleaved if odd => 3 * step / 4,
+                    SweepStyle::Interleaved => step / 4
+                };
+                odd = !odd;
+            }
+        }
+        steps
+    }
+}
+
+/// One block of samples captured at a single sweep step.
+#[derive(Copy, Clone, Debug)]
+pub struct SweepBlock<'a> {
+    /// The step frequency from the block header, in Hz. The radio was tuned
+    /// to this plus the sweep's offset.
+    pub freq_hz: u64,
+    /// The samples following the header.
+    pub samples: &'a [IQ8]
+}
+
+impl<'a> SweepBlock<'a> {
+    /// Parse one block, returning `None` if it lacks the `0x7f 0x7f`
+    /// header.
+    pub fn parse(block: &'a [IQ8]) -> Option<SweepBlock<'a>> {
+        let bytes = IQ8::as_bytes(block);
+        if bytes.len() < SWEEP_HEADER_BYTES || bytes[0] != 0x7f ||
+           bytes[1] != 0x7f {
+            return None;
+        }
+        let mut freq = [0u8; 8];
+        freq.copy_from_slice(&bytes[2..SWEEP_HEADER_BYTES]);
+        Some(SweepBlock {
+            freq_hz: u64::from_le_bytes(freq),
+            samples: &block[SWEEP_HEADER_BYTES / 2..]
+        })
+    }
+
+    /// Split a sweep transfer into its blocks, skipping any without a
+    /// valid header.
+    pub fn split(transfer: &'a [IQ8])
+                 -> impl Iterator<Item = SweepBlock<'a>> + 'a {
+        transfer.chunks(SWEEP_BLOCK_SAMPLES).filter_map(SweepBlock::parse)
+    }
+
+    /// Write the header for a block at `freq_hz` into the start of `block`.
+    pub fn write_header(block: &mut [IQ8], freq_hz: u64) {
+        let mut header = [0u8; SWEEP_HEADER_BYTES];
+        header[0] = 0x7f;
+        header[1] = 0x7f;
+        header[2..].copy_from_slice(&freq_hz.to_le_bytes());
+        for (s, iq) in block.iter_mut().zip(header.chunks(2)) {
+            *s = IQ8::new(iq[0] as i8, iq[1] as i8);
+        }
+    }
+}
+
+impl HackRFDevice {
+    /// Begin sweeping across the frequency ranges in `config`.
+    /// Set the sample rate and baseband filter first; `hackrf_sweep` uses
+    /// 20Msps and 15MHz.
+    /// `callback` is a closure like:
+    ///     callback(block: SweepBlock) -> bool
+    /// which is called with each block of samples tagged with its step
+    /// frequency, and returns `true` to keep sweeping or `false` to stop.
+    /// Like `rx`, it runs on libhackrf's transfer thread until the returned
+    /// stream is stopped or dropped.
+    /// Returns `HackRFError::Unsupported` if libhackrf is too old.
+    pub fn sweep<F>(&mut self, config: &SweepConfig, mut callback: F)
+                    -> Result<RxStream<'_>, HackRFError>
+        where F: FnMut(SweepBlock<'_>) -> bool + Send + 'static
+    {
+        config.validate()?;
+        self.backend.init_sweep(config)?;
+        self.backend.start_rx_sweep(Box::new(move |transfer: &[IQ8]| {
+            SweepBlock::split(transfer).all(&mut callback)
+        }))?;
+        Ok(RxStream::started(self))
+    }
+}
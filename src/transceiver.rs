@@ -0,0 +1,173 @@
+// Half-duplex RX and TX on one HackRF, re-opening between directions
+// Copyright Adam Greig <adam@adamgreig.com> 2014
+// Licensed under MIT license
+
+use crate::config::RadioConfig;
+use crate::stream::{RxStream, TxStream};
+use crate::{HackRFDevice, HackRFError, IQ8};
+
+/// The direction a `Transceiver` last streamed in.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum StreamDirection { Rx, Tx }
+
+/// Opens the device a `Transceiver` drives, each time it is re-opened.
+type Opener = Box<dyn FnMut() -> Result<HackRFDevice, HackRFError>>;
+
+/// A HackRF used for receiving and transmitting in turn.
+///
+/// A HackRF cannot start transmitting after it has received without being
+/// closed and opened again, so whenever a stream runs in the other
+/// direction to the last one the device is re-opened and its `RadioConfig`
+/// applied again. Callers only see the change in direction.
+pub struct Transceiver {
+    open: Opener,
+    device: Option<HackRFDevice>,
+    config: RadioConfig,
+    direction: Option<StreamDirection>,
+    reopens: u64
+}
+
+impl Transceiver {
+    /// Open a device with `open`, which is called again for every re-open,
+    /// and apply `config` to it.
+    pub fn new<F>(open: F, config: RadioConfig)
+                  -> Result<Transceiver, HackRFError>
+        where F: FnMut() -> Result<HackRFDevice, HackRFError> + 'static
+    {
+        let mut transceiver = Transceiver {
+            open: Box::new(open),
+            device: None,
+            config,
+            direction: None,
+            reopens: 0
+        };
+        transceiver.device()?;
+        Ok(transceiver)
+    }
+
+    /// Drive the first HackRF found, as `HackRFDevice::open` would.
+    pub fn open(config: RadioConfig) -> Result<Transceiver, HackRFError> {
+        Transceiver::new(HackRFDevice::open, config)
+    }
+
+    /// Drive the HackRF whose serial number ends with `serial`, as
+    /// `HackRFDevice::open_by_serial` would.
+    pub fn open_by_serial(serial: &str, config: RadioConfig)
+                          -> Result<Transceiver, HackRFError> {
+        let serial = serial.to_owned();
+        Transceiver::new(move || HackRFDevice::open_by_serial(&serial),
+                         config)
+    }
+
+    /// The configuration applied on the next re-open: the device's
+    /// `radio_config` snapshot, including settings changed through
+    /// `device`, or the last configuration applied if it is not open.
+    pub fn config(&self) -> RadioConfig {
+        self.device.as_ref()
+            .and_then(HackRFDevice::radio_config)
+            .unwrap_or(self.config)
+    }
+
+    /// Apply `config` to the device and remember it for later re-opens.
+    /// On error the device may be partly configured.
+    pub fn set_config(&mut self, config: RadioConfig)
+                      -> Result<(), HackRFError> {
+        config.apply(self.device()?)?;
+        self.config = config;
+        Ok(())
+    }
+
+    /// The direction of the last stream started, if any since the device
+    /// was last re-opened.
+    pub fn direction(&self) -> Option<StreamDirection> {
+        self.direction
+    }
+
+    /// How many times the device has been closed and opened again.
+    pub fn reopens(&self) -> u64 {
+        self.reopens
+    }
+
+    /// The device, opening it first if a previous re-open failed.
+    /// Settings changed through it are kept when it is re-opened.
+    pub fn device(&mut self) -> Result<&mut HackRFDevice, HackRFError> {
+        open_device(&mut self.device, &mut self.open, &self.config)
+    }
+
+    /// Close the device and open it again with its current configuration.
+    /// If closing fails the device is still re-opened on next use.
+    pub fn reopen(&mut self) -> Result<(), HackRFError> {
+        self.direction = None;
+        if let Some(device) = self.device.take() {
+            if let Some(config) = device.radio_config() {
+                self.config = config;
+            }
+            self.reopens += 1;
+            device.close()?;
+        }
+        self.device().map(|_| ())
+    }
+
+    /// Start a stream in `direction` with `start`, re-opening the device
+    /// first if it last streamed the other way.
+    fn start<'a, S, F>(&'a mut self, direction: StreamDirection, start: F)
+                       -> Result<S, HackRFError>
+        where F: FnOnce(&'a mut HackRFDevice) -> Result<S, HackRFError>
+    {
+        if self.direction.is_some_and(|last| last != direction) {
+            self.reopen()?;
+        }
+        let device = open_device(&mut self.device, &mut self.open,
+                                 &self.config)?;
+        let stream = start(device)?;
+        self.direction = Some(direction);
+        Ok(stream)
+    }
+
+    /// Begin receiving as with `HackRFDevice::rx`, re-opening the device
+    /// first if it last transmitted.
+    pub fn rx<F>(&mut self, callback: F) -> Result<RxStream<'_>, HackRFError>
+        where F: FnMut(&[IQ8]) -> bool + Send + 'static
+    {
+        self.start(StreamDirection::Rx, |device| device.rx(callback))
+    }
+
+    /// Begin receiving as with `HackRFDevice::rx_buffers`, re-opening the
+    /// device first if it last transmitted.
+    pub fn rx_buffers(&mut self, count: usize)
+                      -> Result<RxStream<'_>, HackRFError> {
+        self.start(StreamDirection::Rx, |device| device.rx_buffers(count))
+    }
+
+    /// Begin transmitting as with `HackRFDevice::tx`, re-opening the device
+    /// first if it last received.
+    pub fn tx<F>(&mut self, callback: F) -> Result<TxStream<'_>, HackRFError>
+        where F: FnMut(&mut [IQ8]) -> bool + Send + 'static
+    {
+        self.start(StreamDirection::Tx, |device| device.tx(callback))
+    }
+
+    /// Close the device.
+    pub fn close(mut self) -> Result<(), HackRFError> {
+        match self.device.take() {
+            Some(device) => device.close(),
+            None => Ok(())
+        }
+    }
+}
+
+/// The device in `slot`, opening it with `open` and applying `config` if
+/// there is none.
+fn open_device<'a>(slot: &'a mut Option<HackRFDevice>, open: &mut Opener,
+                   config: &RadioConfig)
+                   -> Result<&'a mut HackRFDevice, HackRFError> {
+    let device = match slot.take() {
+        Some(device) => device,
+        None => {
+            let mut device = open()?;
+            config.apply(&mut device)?;
+            device
+        }
+    };
+    Ok(slot.insert(device))
+}
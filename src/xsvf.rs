@@ -0,0 +1,252 @@
+// XSVF parsing and CPLD programming
+// Copyright Adam Greig <adam@adamgreig.com> 2014
+// Licensed under MIT license
+
+//! XSVF is Xilinx's compact binary form of SVF, a series of JTAG commands
+//! which here program the HackRF's CoolRunner-II CPLD. The format is
+//! described in Xilinx application note XAPP503.
+
+use std::error::Error;
+use std::fmt;
+
+use crate::spiflash::{FirmwareError, FlashProgress, FlashStage};
+use crate::HackRFDevice;
+
+/// One XSVF instruction. Bit vectors are kept as the big-endian bytes in
+/// the file, whose length depends on the instruction or the last
+/// `SdrSize`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum XsvfCommand {
+    /// End of the program.
+    Complete,
+    /// Mask applied to TDO when comparing captured values.
+    TdoMask(Vec<u8>),
+    /// Shift `bits` bits into the instruction register.
+    Sir { bits: u16, tdi: Vec<u8> },
+    /// Shift into the data register without checking TDO.
+    Sdr(Vec<u8>),
+    /// Microseconds to wait in Run-Test/Idle after each shift.
+    RunTest(u32),
+    /// Times to retry a shift whose TDO does not match.
+    Repeat(u8),
+    /// Length in bits of later data register shifts.
+    SdrSize(u32),
+    /// Shift into the data register and check TDO against `tdo`.
+    SdrTdo { tdi: Vec<u8>, tdo: Vec<u8> },
+    /// Masks for `SdrInc`.
+    SetSdrMasks { address_mask: Vec<u8>, data_mask: Vec<u8> },
+    /// Shift `start`, then each of `data` with the address incremented.
+    SdrInc { start: Vec<u8>, data: Vec<Vec<u8>> },
+    /// Begin, continue or end a data register shift split across several
+    /// instructions.
+    SdrB(Vec<u8>),
+    SdrC(Vec<u8>),
+    SdrE(Vec<u8>),
+    SdrTdoB { tdi: Vec<u8>, tdo: Vec<u8> },
+    SdrTdoC { tdi: Vec<u8>, tdo: Vec<u8> },
+    SdrTdoE { tdi: Vec<u8>, tdo: Vec<u8> },
+    /// Move to a TAP state, 0-15.
+    State(u8),
+    /// State to finish instruction register shifts in.
+    EndIr(u8),
+    /// State to finish data register shifts in.
+    EndDr(u8),
+    Comment(String),
+    /// Wait in `wait_state` for `microseconds`, then move to `end_state`.
+    Wait { wait_state: u8, end_state: u8, microseconds: u32 }
+}
+
+/// Why an XSVF file could not be parsed. Offsets are from the start of the
+/// file.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum XsvfError {
+    /// An instruction byte which is not part of XSVF.
+    UnknownCommand { offset: usize, opcode: u8 },
+    /// The instruction at `offset` runs past the end of the file.
+    Truncated { offset: usize },
+    /// A TAP state outside 0-15.
+    InvalidState { offset: usize, state: u8 },
+    /// The file ends without an `XCOMPLETE` instruction.
+    MissingComplete
+}
+
+impl fmt::Display for XsvfError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            XsvfError::UnknownCommand { offset, opcode } =>
+                write!(f, "unknown XSVF command 0x{:02x} at offset {}",
+                       opcode, offset),
+            XsvfError::Truncated { offset } =>
+                write!(f, "XSVF command at offset {} is truncated", offset),
+            XsvfError::InvalidState { offset, state } =>
+                write!(f, "invalid TAP state {} at offset {}", state, offset),
+            XsvfError::MissingComplete =>
+                write!(f, "XSVF ends without XCOMPLETE")
+        }
+    }
+}
+
+impl Error for XsvfError {}
+
+/// Reads the fields of one instruction, reporting truncation against the
+/// instruction's offset.
+struct Reader<'a> {
+    data: &'a [u8],
+    position: usize,
+    start: usize
+}
+
+impl<'a> Reader<'a> {
+    fn bytes(&mut self, len: usize) -> Result<&'a [u8], XsvfError> {
+        let end = self.position.checked_add(len)
+            .filter(|&end| end <= self.data.len())
+            .ok_or(XsvfError::Truncated { offset: self.start })?;
+        let bytes = &self.data[self.position..end];
+        self.position = end;
+        Ok(bytes)
+    }
+
+    fn vector(&mut self, bits: u32) -> Result<Vec<u8>, XsvfError> {
+        Ok(self.bytes(bits.div_ceil(8) as usize)?.to_vec())
+    }
+
+    fn u8(&mut self) -> Result<u8, XsvfError> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, XsvfError> {
+        let b = self.bytes(2)?;
+        Ok(u16::from_be_bytes([b[0], b[1]]))
+    }
+
+    fn u32(&mut self) -> Result<u32, XsvfError> {
+        let b = self.bytes(4)?;
+        Ok(u32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+    }
+
+    fn state(&mut self) -> Result<u8, XsvfError> {
+        let offset = self.position;
+        match self.u8()? {
+            state @ 0..=15 => Ok(state),
+            state => Err(XsvfError::InvalidState { offset, state })
+        }
+    }
+}
+
+/// A parsed XSVF program.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Xsvf {
+    /// Each instruction with its offset in the file, ending with
+    /// `XsvfCommand::Complete`.
+    pub commands: Vec<(usize, XsvfCommand)>
+}
+
+impl Xsvf {
+    /// Parse `data` up to its `XCOMPLETE` instruction. Anything after that
+    /// is ignored, as players stop there.
+    pub fn parse(data: &[u8]) -> Result<Xsvf, XsvfError> {
+        let mut commands = Vec::new();
+        let mut reader = Reader { data, position: 0, start: 0 };
+        let mut sdr_bits = 0;
+        let mut data_mask_bits = 0;
+        while reader.position < data.len() {
+            reader.start = reader.position;
+            let offset = reader.position;
+            let command = match reader.u8()? {
+                0x00 => XsvfCommand::Complete,
+                0x01 => XsvfCommand::TdoMask(reader.vector(sdr_bits)?),
+                0x02 => {
+                    let bits = reader.u8()? as u16;
+                    XsvfCommand::Sir { bits, tdi: reader.vector(bits as u32)? }
+                },
+                0x03 => XsvfCommand::Sdr(reader.vector(sdr_bits)?),
+                0x04 => XsvfCommand::RunTest(reader.u32()?),
+                0x07 => XsvfCommand::Repeat(reader.u8()?),
+                0x08 => {
+                    sdr_bits = reader.u32()?;
+                    XsvfCommand::SdrSize(sdr_bits)
+                },
+                0x09 => XsvfCommand::SdrTdo { tdi: reader.vector(sdr_bits)?,
+                                              tdo: reader.vector(sdr_bits)? },
+                0x0a => {
+                    let address_mask = reader.vector(sdr_bits)?;
+                    let data_mask = reader.vector(sdr_bits)?;
+                    data_mask_bits = data_mask.iter()
+                        .map(|b| b.count_ones()).sum();
+                    XsvfCommand::SetSdrMasks { address_mask, data_mask }
+                },
+                0x0b => {
+                    let start = reader.vector(sdr_bits)?;
+                    let count = reader.u8()?;
+                    let data = (0..count)
+                        .map(|_| reader.vector(data_mask_bits))
+                        .collect::<Result<_, _>>()?;
+                    XsvfCommand::SdrInc { start, data }
+                },
+                0x0c => XsvfCommand::SdrB(reader.vector(sdr_bits)?),
+                0x0d => XsvfCommand::SdrC(reader.vector(sdr_bits)?),
+                0x0e => XsvfCommand::SdrE(reader.vector(sdr_bits)?),
+                0x0f => XsvfCommand::SdrTdoB { tdi: reader.vector(sdr_bits)?,
+                                               tdo: reader.vector(sdr_bits)? },
+                0x10 => XsvfCommand::SdrTdoC { tdi: reader.vector(sdr_bits)?,
+                                               tdo: reader.vector(sdr_bits)? },
+                0x11 => XsvfCommand::SdrTdoE { tdi: reader.vector(sdr_bits)?,
+                                               tdo: reader.vector(sdr_bits)? },
+                0x12 => XsvfCommand::State(reader.state()?),
+                0x13 => XsvfCommand::EndIr(reader.state()?),
+                0x14 => XsvfCommand::EndDr(reader.state()?),
+                0x15 => {
+                    let bits = reader.u16()?;
+                    XsvfCommand::Sir { bits, tdi: reader.vector(bits as u32)? }
+                },
+                0x16 => {
+                    let rest = &data[reader.position..];
+                    let len = rest.iter().position(|&b| b == 0)
+                        .ok_or(XsvfError::Truncated { offset })?;
+                    let text = reader.bytes(len + 1)?;
+                    let text = String::from_utf8_lossy(&text[..len]);
+                    XsvfCommand::Comment(text.into_owned())
+                },
+                0x17 => XsvfCommand::Wait {
+                    wait_state: reader.state()?,
+                    end_state: reader.state()?,
+                    microseconds: reader.u32()?
+                },
+                opcode => {
+                    return Err(XsvfError::UnknownCommand { offset, opcode });
+                }
+            };
+            let complete = command == XsvfCommand::Complete;
+            commands.push((offset, command));
+            if complete {
+                return Ok(Xsvf { commands });
+            }
+        }
+        Err(XsvfError::MissingComplete)
+    }
+}
+
+impl HackRFDevice {
+    /// Program the CPLD with `xsvf`, the contents of an XSVF file such as
+    /// `hackrf_cpld_default.xsvf`. The file is parsed first and refused with
+    /// `FirmwareError::Invalid` if it is not valid XSVF.
+    ///
+    /// `progress` is called with the bytes sent so far, but there is no
+    /// intermediate progress on a real HackRF: libhackrf sends the whole
+    /// file in one blocking call, so `progress` only sees 0 before it and
+    /// the file's length after it. Only the simulator reports each chunk.
+    ///
+    /// Reset the HackRF afterwards to run the new CPLD configuration.
+    pub fn cpld_write<F>(&mut self, xsvf: &[u8], mut progress: F)
+                         -> Result<(), FirmwareError>
+        where F: FnMut(FlashProgress)
+    {
+        Xsvf::parse(xsvf).map_err(|e| FirmwareError::Invalid(e.to_string()))?;
+        let total = xsvf.len();
+        progress(FlashProgress { stage: FlashStage::Writing, done: 0, total });
+        self.backend.cpld_write(xsvf, &mut |done| {
+            progress(FlashProgress { stage: FlashStage::Writing, done, total })
+        })?;
+        Ok(())
+    }
+}
@@ -0,0 +1,163 @@
+// Tests for the async RX/TX adapters, both on their own and driven by the
+// simulator's transfer thread.
+#![cfg(feature = "async")]
+
+use std::task::{Context, Poll};
+use std::thread;
+use std::time::Duration;
+
+use futures::executor::block_on;
+use futures::task::noop_waker_ref;
+use futures::{SinkExt, StreamExt};
+use hackrf::{Signal, SimulatedHackRF, IQ8};
+
+mod common;
+use common::simulated;
+
+fn samples(value: i8, len: usize) -> Vec<IQ8> {
+    vec![IQ8::new(value, -value); len]
+}
+
+#[test]
+fn rx_stream_delivers_transfers_in_order() {
+    // Each 8 sample transfer of the looped recording holds its own index.
+    let recording = (0..20).flat_map(|i| samples(i, 8)).collect();
+    let sim = SimulatedHackRF::new().transfer_samples(8)
+        .signal(Signal::recording(recording));
+    let (mut device, _) = simulated(sim);
+    let (stream, mut rx) = device.rx_async(64).unwrap();
+    let buffers: Vec<_> = block_on(rx.by_ref().take(50).collect());
+    stream.stop().unwrap();
+
+    // Transfers may be dropped, but never reordered.
+    let mut next = 0;
+    for buffer in &buffers {
+        assert_eq!(buffer.sequence(), next + buffer.dropped_before());
+        let index = (buffer.sequence() % 20) as i8;
+        assert_eq!(&buffer[..], &samples(index, 8)[..]);
+        next = buffer.sequence() + 1;
+    }
+    // Stopping the device ends the stream.
+    drop(buffers);
+    assert!(block_on(rx.collect::<Vec<_>>()).len() <= 64);
+}
+
+#[test]
+fn rx_stream_drops_when_consumer_falls_behind() {
+    let (mut sender, mut rx) = hackrf::rx_channel(2);
+    for i in 0..5 {
+        assert!(sender.push(&samples(i, 2)));
+    }
+    assert_eq!(rx.dropped(), 3);
+
+    let first = block_on(rx.next()).unwrap();
+    let second = block_on(rx.next()).unwrap();
+    assert_eq!((first.sequence(), second.sequence()), (0, 1));
+    drop(first);
+    drop(second);
+
+    assert!(sender.push(&samples(5, 2)));
+    let third = block_on(rx.next()).unwrap();
+    assert_eq!(third.sequence(), 5);
+    assert_eq!(third.dropped_before(), 3);
+    assert_eq!(&third[..], &samples(5, 2)[..]);
+}
+
+#[test]
+fn rx_stream_is_pending_until_a_transfer_arrives() {
+    let (mut sender, mut rx) = hackrf::rx_channel(4);
+    let mut cx = Context::from_waker(noop_waker_ref());
+    assert!(rx.poll_next_unpin(&mut cx).is_pending());
+    sender.push(&[IQ8::new(1, 2)]);
+    match rx.poll_next_unpin(&mut cx) {
+        Poll::Ready(Some(buffer)) => assert_eq!(buffer.as_bytes(), &[1, 2]),
+        _ => panic!("expected a buffer"),
+    }
+    drop(sender);
+    assert!(matches!(rx.poll_next_unpin(&mut cx), Poll::Ready(None)));
+}
+
+#[test]
+fn dropping_rx_stream_stops_transfers() {
+    let (mut sender, rx) = hackrf::rx_channel(4);
+    assert!(sender.push(&samples(0, 2)));
+    drop(rx);
+    assert!(!sender.push(&samples(0, 2)));
+}
+
+#[test]
+fn tx_sink_applies_backpressure() {
+    let (mut tx, mut source) = hackrf::tx_channel(2);
+    let mut cx = Context::from_waker(noop_waker_ref());
+    block_on(tx.feed(samples(1, 4))).unwrap();
+    block_on(tx.feed(samples(2, 4))).unwrap();
+    assert!(tx.poll_ready_unpin(&mut cx).is_pending());
+
+    let mut transfer = [IQ8::default(); 4];
+    assert!(source.fill(&mut transfer));
+    assert_eq!(&transfer[..], &samples(1, 4)[..]);
+    assert!(matches!(tx.poll_ready_unpin(&mut cx), Poll::Ready(Ok(()))));
+}
+
+#[test]
+fn tx_sink_pads_underruns_with_zeros() {
+    let (mut tx, mut source) = hackrf::tx_channel(4);
+    block_on(tx.send(samples(7, 3))).unwrap();
+    let mut transfer = [IQ8::new(-1, -1); 6];
+    assert!(source.fill(&mut transfer));
+    let mut expected = samples(7, 3);
+    expected.extend(samples(0, 3));
+    assert_eq!(&transfer[..], &expected[..]);
+    assert_eq!(tx.underruns(), 1);
+}
+
+#[test]
+fn tx_sink_splits_buffers_across_transfers() {
+    let (mut tx, mut source) = hackrf::tx_channel(4);
+    block_on(tx.send(samples(1, 6))).unwrap();
+    block_on(tx.send(samples(2, 2))).unwrap();
+    let mut transfer = [IQ8::default(); 4];
+    assert!(source.fill(&mut transfer));
+    assert_eq!(&transfer[..], &samples(1, 4)[..]);
+    assert!(source.fill(&mut transfer));
+    let mut expected = samples(1, 2);
+    expected.extend(samples(2, 2));
+    assert_eq!(&transfer[..], &expected[..]);
+    assert_eq!(tx.underruns(), 0);
+}
+
+#[test]
+fn closing_tx_sink_drains_then_stops() {
+    let (mut tx, mut source) = hackrf::tx_channel(4);
+    block_on(async {
+        tx.send(samples(3, 4)).await.unwrap();
+        tx.close().await.unwrap();
+    });
+    let mut transfer = [IQ8::default(); 4];
+    assert!(source.fill(&mut transfer));
+    assert_eq!(&transfer[..], &samples(3, 4)[..]);
+    assert!(!source.fill(&mut transfer));
+    assert!(block_on(tx.send(samples(0, 4))).is_err());
+}
+
+#[test]
+fn tx_sink_feeds_simulated_transfer_thread() {
+    let sim = SimulatedHackRF::new().transfer_samples(8);
+    let (mut device, handle) = simulated(sim);
+    let (mut stream, mut tx) = device.tx_async(2).unwrap();
+    block_on(async {
+        for i in 1..=10 {
+            tx.send(samples(i, 8)).await.unwrap();
+        }
+        tx.close().await.unwrap();
+    });
+    // Once the queue drains the transfer callback ends the stream.
+    while stream.is_streaming().unwrap() {
+        thread::sleep(Duration::from_millis(1));
+    }
+    stream.stop().unwrap();
+    let data: Vec<IQ8> = handle.transmitted().into_iter()
+                               .filter(|&s| s != IQ8::default()).collect();
+    let expected: Vec<IQ8> = (1..=10).flat_map(|i| samples(i, 8)).collect();
+    assert_eq!(data, expected);
+}
@@ -0,0 +1,64 @@
+// Tests for the board ID, part ID and serial number types.
+
+use hackrf::{BoardId, HackRFDevice, HackRFError, PartId, SerialNumber,
+             SimulatedHackRF, UsbBoardId};
+
+#[test]
+fn board_ids_match_libhackrf() {
+    let ids: Vec<BoardId> = (0..6).map(BoardId::from).collect();
+    assert_eq!(ids, [BoardId::Jellybean, BoardId::Jawbreaker,
+                     BoardId::HackRFOne, BoardId::Rad1o,
+                     BoardId::HackRFOneR9, BoardId::Unknown(5)]);
+    for id in 0..=255 {
+        assert_eq!(u8::from(BoardId::from(id)), id);
+    }
+    assert_eq!(BoardId::HackRFOne.to_string(), "HackRF One");
+    assert_eq!(BoardId::HackRFOneR9.to_string(), "HackRF One");
+    assert_eq!(BoardId::Unknown(0xfe).to_string(), "unrecognized");
+    assert_eq!(BoardId::Unknown(0xff).to_string(), "undetected");
+    assert_eq!(BoardId::Unknown(5).to_string(), "unknown");
+    assert_eq!(BoardId::HackRFOneR9.usb_board_id(),
+               Some(UsbBoardId::HackRFOne));
+    assert_eq!(BoardId::Jellybean.usb_board_id(), None);
+}
+
+#[test]
+fn part_id_round_trips_hackrf_info_format() {
+    let part_id = PartId([0xa000cb3c, 0x00574f4b]);
+    assert_eq!(part_id.to_string(), "0xa000cb3c 0x00574f4b");
+    assert_eq!("0xa000cb3c 0x00574f4b".parse(), Ok(part_id));
+    assert_eq!("a000cb3c   574f4b".parse(), Ok(part_id));
+    assert_eq!("0xa000cb3c".parse::<PartId>(), Err(HackRFError::InvalidParam));
+    assert_eq!("0xa000cb3c 0xg".parse::<PartId>(),
+               Err(HackRFError::InvalidParam));
+}
+
+#[test]
+fn serial_number_round_trips_usb_format() {
+    let serial = SerialNumber([0, 0, 0x457863c8, 0x2b7d8b5f]);
+    let text = "0000000000000000457863c82b7d8b5f";
+    assert_eq!(serial.to_string(), text);
+    assert_eq!(text.parse(), Ok(serial));
+    assert_eq!(text.to_uppercase().parse(), Ok(serial));
+    assert_eq!(format!("0x{}", text).parse(), Ok(serial));
+    assert_eq!("0x00000000 0x00000000 0x457863c8 0x2b7d8b5f".parse(),
+               Ok(serial));
+    for bad in ["457863c82b7d8b5f", "0000000000000000457863c82b7d8b5fa",
+                "000000000000000x457863c82b7d8b5f", "0 0 0"] {
+        assert_eq!(bad.parse::<SerialNumber>(),
+                   Err(HackRFError::InvalidParam), "{}", bad);
+    }
+}
+
+#[test]
+fn device_reports_typed_identity() {
+    let sim = SimulatedHackRF::new().realtime(false)
+        .board_id(BoardId::HackRFOneR9)
+        .partid_serialno([0xa000cb3c, 0x00574f4b],
+                         [0, 0, 0x457863c8, 0x2b7d8b5f]);
+    let mut device = HackRFDevice::from_backend(sim);
+    assert_eq!(device.board_id_read().unwrap(), BoardId::HackRFOneR9);
+    let (part_id, serial) = device.board_partid_serialno_read().unwrap();
+    assert_eq!(part_id.to_string(), "0xa000cb3c 0x00574f4b");
+    assert_eq!(serial.to_string(), "0000000000000000457863c82b7d8b5f");
+}
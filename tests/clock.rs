@@ -0,0 +1,93 @@
+// Tests for clock sharing and hardware triggered starts, against the
+// simulator.
+
+use std::thread;
+use std::time::Duration;
+
+use hackrf::{start_synchronised_rx, ClockConfig, HackRFDevice, HackRFError,
+             SimTrigger, SimulatedHackRF, Signal, SyncError};
+
+mod common;
+use common::simulated;
+
+/// A simulated board receiving a tone, with small transfers.
+fn board() -> SimulatedHackRF {
+    SimulatedHackRF::new().transfer_samples(1024)
+        .signal(Signal::tone(100_250_000.0, 0.5))
+}
+
+#[test]
+fn clock_config_is_applied() {
+    let (mut device, handle) = simulated(board());
+    assert!(!device.clkin_status().unwrap());
+    device.set_clock_config(ClockConfig::leader()).unwrap();
+    assert!(handle.clkout() && !handle.hw_sync());
+    device.set_clock_config(ClockConfig::follower()).unwrap();
+    assert!(!handle.clkout() && handle.hw_sync());
+    device.set_clock_config(ClockConfig::default()).unwrap();
+    assert!(!handle.clkout() && !handle.hw_sync());
+
+    let (mut device, _) = simulated(board().clkin(true));
+    assert!(device.clkin_status().unwrap());
+}
+
+#[test]
+fn hw_sync_waits_for_trigger() {
+    let trigger = SimTrigger::new();
+    let (mut device, _) = simulated(board().trigger(&trigger));
+    device.set_hw_sync_mode(true).unwrap();
+    let mut stream = device.rx_buffers(4).unwrap();
+    thread::sleep(Duration::from_millis(50));
+    assert!(stream.try_recv().is_none());
+    trigger.fire();
+    assert_eq!(stream.recv().unwrap().len(), 1024);
+}
+
+#[test]
+fn synchronised_rx_starts_together() {
+    let trigger = SimTrigger::new();
+    let (mut leader, leader_handle) =
+        simulated(board().trigger(&trigger));
+    let mut followers: Vec<HackRFDevice> = (0..2).map(|_| {
+        simulated(board().clkin(true).trigger(&trigger)).0
+    }).collect();
+    for device in followers.iter_mut().chain(Some(&mut leader)) {
+        device.set_freq(100_000_000).unwrap();
+    }
+    let mut streams = start_synchronised_rx(&mut leader, &mut followers, 4)
+        .unwrap();
+    assert_eq!(streams.len(), 3);
+    assert!(leader_handle.clkout() && !leader_handle.hw_sync());
+    let first: Vec<Vec<_>> = streams.iter_mut()
+        .map(|stream| stream.recv().unwrap().to_vec())
+        .collect();
+    assert_eq!(first[0], first[1]);
+    assert_eq!(first[0], first[2]);
+}
+
+#[test]
+fn synchronised_rx_needs_shared_clock() {
+    let trigger = SimTrigger::new();
+    let (mut leader, leader_handle) = simulated(board().trigger(&trigger));
+    let (mut good, _) =
+        simulated(board().clkin(true).trigger(&trigger));
+    let (mut bad, bad_handle) =
+        simulated(board().trigger(&trigger));
+    let result = start_synchronised_rx(&mut leader, [&mut good, &mut bad], 4);
+    assert_eq!(result.err(), Some(SyncError::NoClockIn(1)));
+    assert!(!bad_handle.hw_sync());
+    assert!(!leader_handle.clkout());
+}
+
+#[test]
+fn failed_synchronised_start_resets_every_board() {
+    let trigger = SimTrigger::new();
+    let (mut leader, leader_handle) = simulated(board().trigger(&trigger));
+    let (mut follower, follower_handle) =
+        simulated(board().clkin(true).trigger(&trigger));
+    let result = start_synchronised_rx(&mut leader, [&mut follower], 0);
+    assert_eq!(result.err(),
+               Some(SyncError::Device(HackRFError::InvalidParam)));
+    assert!(!leader_handle.clkout() && !follower_handle.hw_sync());
+    assert_eq!(follower.is_streaming(), Err(HackRFError::StreamingThreadErr));
+}
@@ -0,0 +1,11 @@
+// Helpers shared by the tests run against the simulator.
+
+use hackrf::{HackRFDevice, SimHandle, SimulatedHackRF};
+
+/// A device backed by `sim`, streaming as fast as it is read rather than in
+/// real time, and a handle to inspect the simulated board.
+pub fn simulated(sim: SimulatedHackRF) -> (HackRFDevice, SimHandle) {
+    let sim = sim.realtime(false);
+    let handle = sim.handle();
+    (HackRFDevice::from_backend(sim), handle)
+}
@@ -0,0 +1,118 @@
+// Tests for RadioConfig validation and the applied config snapshot,
+// against the simulator.
+
+use hackrf::{HackRFError, RadioConfig, RFPathFilter, SimulatedHackRF};
+
+mod common;
+use common::simulated;
+
+#[test]
+fn invalid_configs_are_rejected() {
+    let good = RadioConfig::new(433_920_000, 10e6);
+    assert_eq!(good.validate(), Ok(()));
+    let bad = [
+        RadioConfig { freq_hz: 7_300_000_000, ..good },
+        RadioConfig { sample_rate: 1e6, ..good },
+        RadioConfig { sample_rate: 25e6, ..good },
+        RadioConfig { sample_rate: f64::NAN, ..good },
+        RadioConfig { baseband_filter_bandwidth: 1_000_000, ..good },
+        RadioConfig { baseband_filter_bandwidth: 30_000_000, ..good },
+        RadioConfig { lna_gain: 48, ..good },
+        RadioConfig { vga_gain: 64, ..good },
+        RadioConfig { txvga_gain: 48, ..good },
+    ];
+    for config in &bad {
+        assert_eq!(config.validate(), Err(HackRFError::InvalidParam));
+    }
+
+    // Nothing is changed by an invalid configuration.
+    let (mut device, handle) = simulated(SimulatedHackRF::new());
+    let amp_on = RadioConfig { amp_enable: true, lna_gain: 48, ..good };
+    assert_eq!(amp_on.apply(&mut device), Err(HackRFError::InvalidParam));
+    assert!(!handle.amp_enable());
+    assert_eq!(handle.freq_hz(), 0);
+    assert_eq!(device.radio_config(), None);
+}
+
+#[test]
+fn apply_sets_everything() {
+    let (mut device, handle) = simulated(SimulatedHackRF::new());
+    let mut config = RadioConfig::new(2_450_000_000, 20e6);
+    config.amp_enable = true;
+    config.antenna_enable = true;
+    config.lna_gain = 24;
+    config.vga_gain = 30;
+    config.txvga_gain = 10;
+    config.apply(&mut device).unwrap();
+    assert_eq!(handle.freq_hz(), 2_450_000_000);
+    assert_eq!(handle.sample_rate(), 20e6);
+    assert_eq!(handle.baseband_filter_bandwidth(), 15_000_000);
+    assert!(handle.amp_enable() && handle.antenna_enable());
+    assert_eq!((handle.lna_gain(), handle.vga_gain(), handle.txvga_gain()),
+               (24, 30, 10));
+
+    RadioConfig::new(100_000_000, 2e6).apply(&mut device).unwrap();
+    assert!(!handle.amp_enable() && !handle.antenna_enable());
+    assert_eq!(handle.lna_gain(), 0);
+}
+
+#[test]
+fn snapshot_follows_later_settings() {
+    let (mut device, _) = simulated(SimulatedHackRF::new());
+    let config = RadioConfig::new(433_920_000, 10e6);
+    config.apply(&mut device).unwrap();
+    assert_eq!(device.radio_config(), Some(config));
+
+    device.set_freq(868_000_000).unwrap();
+    device.set_sample_rate_manual(16_000_000, 2).unwrap();
+    device.set_lna_gain(32).unwrap();
+    device.set_amp_enable(true).unwrap();
+    assert_eq!(device.radio_config().unwrap().baseband_filter_bandwidth,
+               6_000_000);
+    device.set_baseband_filter_bandwidth(5_000_000).unwrap();
+    let snapshot = device.radio_config().unwrap();
+    assert_eq!(snapshot.freq_hz, 868_000_000);
+    assert_eq!(snapshot.sample_rate, 8e6);
+    assert_eq!(snapshot.lna_gain, 32);
+    assert!(snapshot.amp_enable);
+    assert_eq!(snapshot.baseband_filter_bandwidth, 5_000_000);
+
+    // A failed call leaves the snapshot alone.
+    assert!(device.set_vga_gain(70).is_err());
+    assert_eq!(device.radio_config(), Some(snapshot));
+
+    device.set_freq_explicit(2_600_000_000, 3_000_000_000,
+                             RFPathFilter::LowPass).unwrap();
+    assert_eq!(device.radio_config().unwrap().freq_hz, 400_000_000);
+    // The low-pass path also tunes with the LO below the IF.
+    device.set_freq_explicit(2_400_000_000, 1_000_000_000,
+                             RFPathFilter::LowPass).unwrap();
+    assert_eq!(device.radio_config().unwrap().freq_hz, 1_400_000_000);
+
+    // The snapshot can be applied to another board to match this one.
+    let (mut other, handle) = simulated(SimulatedHackRF::new());
+    device.radio_config().unwrap().apply(&mut other).unwrap();
+    assert_eq!(handle.freq_hz(), 1_400_000_000);
+    assert_eq!(handle.lna_gain(), 32);
+}
+
+#[test]
+fn no_snapshot_without_apply() {
+    let (mut device, _) = simulated(SimulatedHackRF::new());
+    device.set_freq(100_000_000).unwrap();
+    assert_eq!(device.radio_config(), None);
+}
+
+#[test]
+fn filter_bandwidths_are_computed_without_libhackrf() {
+    assert_eq!(hackrf::compute_baseband_filter_bw(2000), 1_750_000);
+    assert_eq!(hackrf::compute_baseband_filter_bw(5_000_000), 5_000_000);
+    assert_eq!(hackrf::compute_baseband_filter_bw(5_400_000), 5_000_000);
+    assert_eq!(hackrf::compute_baseband_filter_bw(30_000_000), 28_000_000);
+    assert_eq!(hackrf::compute_baseband_filter_bw_round_down_lt(2000),
+               1_750_000);
+    assert_eq!(hackrf::compute_baseband_filter_bw_round_down_lt(5_000_000),
+               3_500_000);
+    assert_eq!(hackrf::compute_baseband_filter_bw_round_down_lt(30_000_000),
+               28_000_000);
+}
@@ -0,0 +1,50 @@
+// Tests for storing RadioConfig profiles as TOML and JSON.
+#![cfg(feature = "serde")]
+
+use std::collections::BTreeMap;
+
+use hackrf::RadioConfig;
+
+#[test]
+fn named_profiles_load_from_toml() {
+    let profiles: BTreeMap<String, RadioConfig> = toml::from_str(r#"
+        [ism433]
+        freq_hz = 433920000
+        sample_rate = 2e6
+        baseband_filter_bandwidth = 1750000
+        lna_gain = 16
+        vga_gain = 20
+
+        [wifi]
+        freq_hz = 2412000000
+        sample_rate = 20e6
+        baseband_filter_bandwidth = 15000000
+        amp_enable = true
+    "#).unwrap();
+    let ism = profiles["ism433"];
+    assert_eq!(ism.freq_hz, 433_920_000);
+    assert_eq!((ism.lna_gain, ism.vga_gain, ism.txvga_gain), (16, 20, 0));
+    assert!(!ism.amp_enable && !ism.antenna_enable);
+    assert!(profiles["wifi"].amp_enable);
+    assert!(profiles.values().all(|p| p.validate().is_ok()));
+}
+
+#[test]
+fn configs_round_trip() {
+    let mut config = RadioConfig::new(915_000_000, 12.5e6);
+    config.antenna_enable = true;
+    config.txvga_gain = 40;
+    let text = toml::to_string(&config).unwrap();
+    assert_eq!(toml::from_str::<RadioConfig>(&text).unwrap(), config);
+    let json = serde_json::to_string(&config).unwrap();
+    assert_eq!(serde_json::from_str::<RadioConfig>(&json).unwrap(), config);
+}
+
+#[test]
+fn unknown_and_missing_fields_are_rejected() {
+    let typo = r#"{"freq_hz": 1, "sample_rate": 2e6,
+                   "baseband_filter_bandwidth": 1750000, "lna_gian": 8}"#;
+    assert!(serde_json::from_str::<RadioConfig>(typo).is_err());
+    let missing = r#"{"freq_hz": 1, "sample_rate": 2e6}"#;
+    assert!(serde_json::from_str::<RadioConfig>(missing).is_err());
+}
@@ -0,0 +1,74 @@
+// Tests for firmware image inspection, on fixture files and the simulator.
+
+use hackrf::{check_firmware_image, BoardId, FirmwareError, FirmwareFormat,
+             FirmwareImage, HackRFDevice, SimulatedHackRF, UsbBoardId};
+
+const BIN: &str = "tests/fixtures/hackrf_one_usb.bin";
+const DFU: &str = "tests/fixtures/hackrf_one_usb.dfu";
+
+#[test]
+fn bin_is_inspected() {
+    let image = FirmwareImage::from_file(BIN).unwrap();
+    assert_eq!(image.format, FirmwareFormat::Bin);
+    assert_eq!(image.version.as_deref(), Some("2024.02.1"));
+    assert_eq!(image.usb_board_ids, [UsbBoardId::HackRFOne]);
+    assert_eq!(image.crc32, 0x9046_9020);
+    assert_eq!(image.dfu, None);
+    assert!(image.supports(UsbBoardId::HackRFOne));
+    assert!(!image.supports(UsbBoardId::Jawbreaker));
+    check_firmware_image(image.binary()).unwrap();
+}
+
+#[test]
+fn dfu_is_inspected() {
+    let bin = std::fs::read(BIN).unwrap();
+    let image = FirmwareImage::from_file(DFU).unwrap();
+    assert_eq!(image.format, FirmwareFormat::Dfu);
+    assert_eq!(image.binary(), &bin[..]);
+    assert_eq!(image.version.as_deref(), Some("2024.02.1"));
+    assert_eq!(image.usb_board_ids, [UsbBoardId::HackRFOne]);
+    assert_eq!(image.crc32, 0x9046_9020);
+    let suffix = image.dfu.unwrap();
+    assert_eq!((suffix.vendor_id, suffix.product_id), (0x1fc9, 0x000c));
+    assert_eq!(suffix.bcd_dfu, 0x0100);
+    assert_eq!(suffix.crc, 0x31d1_061e);
+    assert!(suffix.crc_ok());
+}
+
+#[test]
+fn damaged_dfu_is_detected() {
+    let mut dfu = std::fs::read(DFU).unwrap();
+    dfu[100] ^= 0x01;
+    let suffix = FirmwareImage::parse(&dfu).unwrap().dfu.unwrap();
+    assert!(!suffix.crc_ok());
+
+    let mut dfu = std::fs::read(DFU).unwrap();
+    dfu[2] = 1;
+    assert!(matches!(FirmwareImage::parse(&dfu),
+                     Err(FirmwareError::Invalid(_))));
+    dfu[2] = 0;
+    dfu[3] = 0;
+    assert!(matches!(FirmwareImage::parse(&dfu),
+                     Err(FirmwareError::Invalid(_))));
+    dfu[0] = 0xfe;
+    assert!(matches!(FirmwareImage::parse(&dfu),
+                     Err(FirmwareError::Invalid(_))));
+}
+
+#[test]
+fn image_is_compared_with_device() {
+    let image = FirmwareImage::from_file(BIN).unwrap();
+    let sim = SimulatedHackRF::new().realtime(false).version("2024.02.1");
+    let mut device = HackRFDevice::from_backend(sim);
+    let comparison = device.compare_firmware(&image).unwrap();
+    assert_eq!(comparison.device_version, "2024.02.1");
+    assert!(comparison.same_version);
+    assert_eq!(comparison.board_supported, Some(true));
+
+    let sim = SimulatedHackRF::new().realtime(false).version("git-0123abcd")
+                                    .board_id(BoardId::Jawbreaker);
+    let mut device = HackRFDevice::from_backend(sim);
+    let comparison = device.compare_firmware(&image).unwrap();
+    assert!(!comparison.same_version);
+    assert_eq!(comparison.board_supported, Some(false));
+}
@@ -0,0 +1,66 @@
+// Tests for the board reports hackrf-info prints, against the simulator.
+
+use hackrf::{BoardReport, DeviceInfo, HackRFDevice, HackRFError,
+             SimulatedHackRF, UsbBoardId};
+
+fn info(serial_number: Option<&str>) -> DeviceInfo {
+    DeviceInfo {
+        index: 0,
+        serial_number: serial_number.map(String::from),
+        usb_board_id: UsbBoardId::HackRFOne,
+        bus_number: 1,
+        port_numbers: vec![2, 4],
+        device_address: 7
+    }
+}
+
+#[test]
+fn json_has_every_field_read() {
+    let sim = SimulatedHackRF::new()
+        .version("2024.02.1")
+        .partid_serialno([1, 2], [3, 4, 5, 6]);
+    let mut device = HackRFDevice::from_backend(sim);
+    let report = BoardReport::from_device(info(Some("0000cafe")),
+                                          &mut device);
+    assert_eq!(report.usb_api_version_string().unwrap(), "1.08");
+    assert_eq!(report.to_json(), concat!(
+        r#"{"index": 0, "usb_serial_number": "0000cafe", "#,
+        r#""usb_board_id": "HackRF One", "bus_path": "1-2.4", "#,
+        r#""board_id": 2, "board_name": "HackRF One", "#,
+        r#""firmware_version": "2024.02.1", "usb_api_version": "1.08", "#,
+        r#""part_id": "0x00000001 0x00000002", "#,
+        r#""serial_number": "00000003000000040000000500000006", "#,
+        r#""board_rev": 132, "board_rev_name": "r9", "error": null}"#));
+}
+
+#[test]
+fn json_is_null_for_what_could_not_be_read() {
+    let report = BoardReport {
+        info: info(None),
+        open_error: Some(HackRFError::NotFound),
+        board_id: None,
+        version: None,
+        usb_api_version: None,
+        part_serial: None,
+        board_rev: None
+    };
+    assert_eq!(report.to_json(), concat!(
+        r#"{"index": 0, "usb_serial_number": null, "#,
+        r#""usb_board_id": "HackRF One", "bus_path": "1-2.4", "#,
+        r#""board_id": null, "board_name": null, "#,
+        r#""firmware_version": null, "usb_api_version": null, "#,
+        r#""part_id": null, "serial_number": null, "#,
+        r#""board_rev": null, "board_rev_name": null, "#,
+        r#""error": "HackRF not found (-5)"}"#));
+}
+
+#[test]
+fn json_strings_are_escaped() {
+    let sim = SimulatedHackRF::new().version("v\"1\"\\beta\n\u{1f}é");
+    let mut device = HackRFDevice::from_backend(sim);
+    let report = BoardReport::from_device(info(Some("a\tb")), &mut device);
+    let json = report.to_json();
+    assert!(json.contains(r#""usb_serial_number": "a\u0009b""#), "{}", json);
+    let version = r#""firmware_version": "v\"1\"\\beta\u000a\u001fé""#;
+    assert!(json.contains(version), "{}", json);
+}
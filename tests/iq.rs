@@ -0,0 +1,94 @@
+// Tests for IQ8 sample conversions and byte views.
+
+use hackrf::iq::{self, Complex};
+use hackrf::IQ8;
+
+#[test]
+fn f32_is_scaled_to_full_scale() {
+    assert_eq!(IQ8::new(64, -64).to_complex_f32(), Complex::new(0.5, -0.5));
+    assert_eq!(IQ8::new(-128, 0).to_complex_f32(), Complex::new(-1.0, 0.0));
+    assert_eq!(IQ8::new(127, 0).to_complex_f32().re, 127.0 / 128.0);
+    assert_eq!(IQ8::from_complex_f32(Complex::new(0.5, -0.25)),
+               IQ8::new(64, -32));
+    for i in i8::MIN..=i8::MAX {
+        let s = IQ8::new(i, i);
+        assert_eq!(IQ8::from(Complex::<f32>::from(s)), s);
+    }
+}
+
+#[test]
+fn f32_saturates_at_full_scale() {
+    // +1.0 is one step beyond the largest positive sample.
+    assert_eq!(IQ8::from_complex_f32(Complex::new(1.0, -1.0)),
+               IQ8::new(127, -128));
+    assert_eq!(IQ8::from_complex_f32(Complex::new(3.5, -7.0)),
+               IQ8::new(127, -128));
+    assert_eq!(IQ8::from_complex_f32(Complex::new(f32::INFINITY,
+                                                  f32::NEG_INFINITY)),
+               IQ8::new(127, -128));
+    assert_eq!(IQ8::from_complex_f32(Complex::new(f32::NAN, 0.0)),
+               IQ8::new(0, 0));
+}
+
+#[test]
+fn f32_rounds_to_nearest() {
+    let step = 1.0 / 128.0;
+    assert_eq!(IQ8::from_complex_f32(Complex::new(0.4 * step, -0.4 * step)),
+               IQ8::new(0, 0));
+    assert_eq!(IQ8::from_complex_f32(Complex::new(0.6 * step, -0.6 * step)),
+               IQ8::new(1, -1));
+    // Halfway rounds away from zero.
+    assert_eq!(IQ8::from_complex_f32(Complex::new(0.5 * step, -0.5 * step)),
+               IQ8::new(1, -1));
+    assert_eq!(IQ8::from_complex_f32(Complex::new(126.5 * step,
+                                                  -127.5 * step)),
+               IQ8::new(127, -128));
+}
+
+#[test]
+fn i16_is_unscaled_and_saturates_at_128() {
+    assert_eq!(IQ8::new(-128, 127).to_complex_i16(), Complex::new(-128, 127));
+    assert_eq!(IQ8::from_complex_i16(Complex::new(127, -128)),
+               IQ8::new(127, -128));
+    assert_eq!(IQ8::from_complex_i16(Complex::new(128, -129)),
+               IQ8::new(127, -128));
+    assert_eq!(IQ8::from_complex_i16(Complex::new(i16::MAX, i16::MIN)),
+               IQ8::new(127, -128));
+    for i in i8::MIN..=i8::MAX {
+        let s = IQ8::new(i, i.saturating_neg());
+        assert_eq!(IQ8::from(Complex::<i16>::from(s)), s);
+    }
+}
+
+#[test]
+fn bytes_view_interleaved_samples() {
+    let mut bytes = [1u8, 2, 0xff, 0x80, 7];
+    let samples = IQ8::from_bytes(&bytes);
+    assert_eq!(samples, [IQ8::new(1, 2), IQ8::new(-1, -128)]);
+    assert_eq!(IQ8::as_bytes(samples), &bytes[..4]);
+
+    IQ8::from_bytes_mut(&mut bytes)[1] = IQ8::new(3, -3);
+    assert_eq!(bytes, [1, 2, 3, 0xfd, 7]);
+    assert!(IQ8::from_bytes(&bytes[..1]).is_empty());
+    assert_eq!(IQ8::as_bytes(&[IQ8::new(-2, 5)]), [0xfe, 5]);
+}
+
+#[test]
+fn bulk_conversions_stop_at_the_shorter_slice() {
+    let samples = [IQ8::new(64, 0), IQ8::new(0, -128), IQ8::new(127, 1)];
+    let mut floats = [Complex::new(9.0, 9.0); 2];
+    assert_eq!(iq::to_complex_f32(&samples, &mut floats), 2);
+    assert_eq!(floats, [Complex::new(0.5, 0.0), Complex::new(0.0, -1.0)]);
+
+    let mut wide = [Complex::new(0, 0); 4];
+    assert_eq!(iq::to_complex_i16(&samples, &mut wide), 3);
+    assert_eq!(wide[2], Complex::new(127, 1));
+    assert_eq!(wide[3], Complex::new(0, 0));
+
+    let mut out = [IQ8::default(); 3];
+    assert_eq!(iq::from_complex_f32(&floats, &mut out), 2);
+    assert_eq!(out, [IQ8::new(64, 0), IQ8::new(0, -128), IQ8::default()]);
+    assert_eq!(iq::from_complex_i16(&[Complex::new(300, -300)], &mut out),
+               1);
+    assert_eq!(out[0], IQ8::new(127, -128));
+}
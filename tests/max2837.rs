@@ -0,0 +1,57 @@
+// Tests for MAX2837 register access and decoding, against the simulator.
+
+use hackrf::{HackRFError, Max2837Registers, RFPathFilter, SimulatedHackRF};
+
+mod common;
+use common::simulated;
+
+#[test]
+fn dump_decodes_what_the_setters_programmed() {
+    let mut device = simulated(SimulatedHackRF::new()).0;
+    device.set_lna_gain(30).unwrap();
+    device.set_vga_gain(21).unwrap();
+    device.set_txvga_gain(47).unwrap();
+    device.set_baseband_filter_bandwidth(4_000_000).unwrap();
+    device.set_freq_explicit(2_450_000_000, 1_000_000_000,
+                             RFPathFilter::LowPass).unwrap();
+    let registers = device.max2837_dump().unwrap();
+    assert_eq!(registers.lna_gain_db(), Some(24));
+    assert_eq!(registers.vga_gain_db(), 20);
+    assert_eq!(registers.txvga_gain_db(), 47);
+    assert_eq!(registers.baseband_filter_bandwidth(), 5_000_000);
+    assert_eq!(registers.lna_band_mhz(), (2400, 2500));
+}
+
+#[test]
+fn registers_are_range_checked() {
+    let mut device = simulated(SimulatedHackRF::new()).0;
+    device.max2837_write(31, 0x3ff).unwrap();
+    assert_eq!(device.max2837_read(31), Ok(0x3ff));
+    assert_eq!(device.max2837_read(32), Err(HackRFError::InvalidParam));
+    assert_eq!(device.max2837_write(32, 0), Err(HackRFError::InvalidParam));
+    assert_eq!(device.max2837_write(0, 0x400),
+               Err(HackRFError::InvalidParam));
+}
+
+#[test]
+fn dumps_from_two_boards_can_be_diffed() {
+    let mut a = simulated(SimulatedHackRF::new()).0;
+    let mut b = simulated(SimulatedHackRF::new()).0;
+    b.set_vga_gain(40).unwrap();
+    b.max2837_write(12, 0x155).unwrap();
+    let (a, b) = (a.max2837_dump().unwrap(), b.max2837_dump().unwrap());
+    let diff = a.diff(&b);
+    assert_eq!(diff.len(), 2);
+    assert_eq!(diff[0].0, 5);
+    assert_eq!(diff[1], (12, 0, 0x155));
+    assert!(a.diff(&a).is_empty());
+}
+
+#[test]
+fn dump_displays_one_register_per_line() {
+    let mut values = [0; 32];
+    values[3] = 0x1a2;
+    let text = Max2837Registers::new(values).to_string();
+    assert_eq!(text.lines().count(), 32);
+    assert_eq!(text.lines().nth(3), Some("[ 3] -> 0x1a2"));
+}
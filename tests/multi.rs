@@ -0,0 +1,105 @@
+// Tests for coherent multi-device RX and shared radio configuration,
+// against the simulator.
+
+use std::thread;
+use std::time::Duration;
+
+use hackrf::{HackRFDevice, HackRFError, MultiDeviceRx, RadioConfig,
+             SimHandle, SimTrigger, SimulatedHackRF, Signal};
+
+const TRANSFER: usize = 1024;
+
+/// A leader and `followers` simulated boards sharing a clock and trigger.
+fn array(followers: usize) -> (Vec<HackRFDevice>, Vec<SimHandle>) {
+    let trigger = SimTrigger::new();
+    (0..=followers).map(|i| {
+        let sim = SimulatedHackRF::new().clkin(i > 0).trigger(&trigger)
+            .transfer_samples(TRANSFER).realtime(false)
+            .signal(Signal::tone(433_950_000.0, 0.5));
+        let handle = sim.handle();
+        (HackRFDevice::from_backend(sim), handle)
+    }).unzip()
+}
+
+#[test]
+fn radio_config_defaults_follow_libhackrf() {
+    let config = RadioConfig::new(433_920_000, 10e6);
+    assert_eq!(config.baseband_filter_bandwidth, 7_000_000);
+    assert_eq!(RadioConfig::new(0, 20e6).baseband_filter_bandwidth,
+               15_000_000);
+    assert_eq!(RadioConfig::new(0, 2e6).baseband_filter_bandwidth,
+               1_750_000);
+    assert!(!config.amp_enable && !config.antenna_enable);
+}
+
+#[test]
+fn config_is_applied_to_every_device() {
+    let (devices, handles) = array(2);
+    let mut config = RadioConfig::new(433_920_000, 8e6);
+    config.lna_gain = 16;
+    config.vga_gain = 20;
+    let mut session = MultiDeviceRx::from_devices(devices, &config).unwrap();
+    assert_eq!(session.len(), 3);
+    for handle in &handles {
+        assert_eq!(handle.freq_hz(), 433_920_000);
+        assert_eq!(handle.sample_rate(), 8e6);
+        assert_eq!(handle.baseband_filter_bandwidth(), 6_000_000);
+        assert_eq!((handle.lna_gain(), handle.vga_gain()), (16, 20));
+    }
+
+    config.freq_hz = 915_000_000;
+    session.apply(&config).unwrap();
+    assert!(handles.iter().all(|h| h.freq_hz() == 915_000_000));
+    session.devices()[1].set_lna_gain(24).unwrap();
+    assert_eq!(handles[1].lna_gain(), 24);
+}
+
+#[test]
+fn buffers_are_tagged_by_device_and_sample_index() {
+    let (devices, handles) = array(2);
+    let config = RadioConfig::new(433_920_000, 10e6);
+    let mut session = MultiDeviceRx::from_devices(devices, &config).unwrap();
+    let mut stream = session.start(2).unwrap();
+    assert!(handles[0].clkout() && !handles[0].hw_sync());
+    assert!(handles[1..].iter().all(|h| h.hw_sync()));
+
+    let first = stream.recv_all().unwrap();
+    assert_eq!(first.iter().map(|t| t.device).collect::<Vec<_>>(),
+               [0, 1, 2]);
+    for tagged in &first {
+        assert_eq!(tagged.sample_index, 0);
+        assert_eq!(&tagged.buffer[..], &first[0].buffer[..]);
+    }
+    drop(first);
+
+    // Fall behind so transfers are dropped; the index still counts them.
+    thread::sleep(Duration::from_millis(20));
+    for _ in 0..4 {
+        for tagged in stream.recv_all().unwrap() {
+            assert_eq!(tagged.sample_index,
+                       tagged.buffer.sequence() * TRANSFER as u64);
+        }
+    }
+    assert!(stream.dropped().iter().all(|&d| d > 0));
+    assert!(matches!(stream.recv_from(3), Err(HackRFError::InvalidParam)));
+    stream.stop().unwrap();
+    assert!(handles.iter().all(|h| !h.clkout() && !h.hw_sync()));
+}
+
+#[test]
+fn dropping_the_stream_resets_clock_sharing() {
+    let (devices, handles) = array(1);
+    let config = RadioConfig::new(433_920_000, 10e6);
+    let mut session = MultiDeviceRx::from_devices(devices, &config).unwrap();
+    let stream = session.start(2).unwrap();
+    assert!(handles[0].clkout() && handles[1].hw_sync());
+    drop(stream);
+    assert!(!handles[0].clkout() && !handles[1].hw_sync());
+}
+
+#[test]
+fn empty_session_is_rejected() {
+    let config = RadioConfig::new(433_920_000, 10e6);
+    assert!(matches!(MultiDeviceRx::from_devices(Vec::new(), &config),
+                     Err(HackRFError::InvalidParam)));
+}
@@ -0,0 +1,99 @@
+// Tests for Opera Cake control, against the simulator.
+
+use hackrf::{HackRFError, OperaCakeDwell, OperaCakeMode, OperaCakePort,
+             OperaCakeRange, SimulatedHackRF, OPERACAKE_MAX_DWELL_TIMES,
+             OPERACAKE_MAX_FREQ_RANGES};
+
+mod common;
+use common::simulated;
+
+#[test]
+fn ports_display_and_parse() {
+    for port in OperaCakePort::ALL.iter() {
+        assert_eq!(port.to_string().parse::<OperaCakePort>(), Ok(*port));
+        assert_eq!(port.opposite().opposite(), *port);
+        assert_ne!(port.is_a_side(), port.opposite().is_a_side());
+    }
+    assert_eq!(OperaCakePort::B3.to_string(), "B3");
+    assert_eq!(" a2".parse::<OperaCakePort>(), Ok(OperaCakePort::A2));
+    assert_eq!(OperaCakePort::A4.opposite(), OperaCakePort::B4);
+    assert_eq!("A5".parse::<OperaCakePort>(),
+               Err(HackRFError::InvalidParam));
+    assert_eq!(u8::from(OperaCakePort::B1), 4);
+}
+
+#[test]
+fn boards_are_found_by_address() {
+    let sim = SimulatedHackRF::new().operacake(0).operacake(3);
+    let (mut device, _) = simulated(sim);
+    assert_eq!(device.operacake_boards().unwrap(), vec![0, 3]);
+    assert_eq!(device.operacake(3).unwrap().address(), 3);
+    assert_eq!(device.operacake(1).err(), Some(HackRFError::NotFound));
+    assert_eq!(device.operacake(8).err(), Some(HackRFError::InvalidParam));
+
+    let (mut device, _) = simulated(SimulatedHackRF::new());
+    assert!(device.operacake_boards().unwrap().is_empty());
+}
+
+#[test]
+fn manual_ports_are_set() {
+    let (mut device, handle) = simulated(SimulatedHackRF::new().operacake(0));
+    assert_eq!(handle.operacake_ports(0),
+               Some((OperaCakePort::A1, OperaCakePort::B1)));
+    let mut cake = device.operacake(0).unwrap();
+    assert_eq!(cake.mode().unwrap(), OperaCakeMode::Manual);
+    cake.set_ports(OperaCakePort::B2, OperaCakePort::A4).unwrap();
+    assert_eq!(handle.operacake_ports(0),
+               Some((OperaCakePort::B2, OperaCakePort::A4)));
+    assert_eq!(cake.set_ports(OperaCakePort::A1, OperaCakePort::A2),
+               Err(HackRFError::InvalidParam));
+    assert_eq!(handle.operacake_ports(0),
+               Some((OperaCakePort::B2, OperaCakePort::A4)));
+}
+
+#[test]
+fn frequency_ranges_switch_on_retune() {
+    let (mut device, handle) = simulated(SimulatedHackRF::new().operacake(0));
+    let ranges = [OperaCakeRange::new(100, 500, OperaCakePort::A2),
+                  OperaCakeRange::new(400, 2000, OperaCakePort::B3)];
+    device.operacake(0).unwrap().switch_by_frequency(&ranges).unwrap();
+    assert_eq!(handle.operacake_mode(0), Some(OperaCakeMode::Frequency));
+    assert_eq!(handle.operacake_freq_ranges(), ranges.to_vec());
+
+    device.set_freq(450_000_000).unwrap();
+    assert_eq!(handle.operacake_ports(0),
+               Some((OperaCakePort::A2, OperaCakePort::B2)));
+    device.set_freq(1_500_999_999).unwrap();
+    assert_eq!(handle.operacake_ports(0),
+               Some((OperaCakePort::B3, OperaCakePort::A3)));
+    // Outside every range the ports stay where they were.
+    device.set_freq(3_000_000_000).unwrap();
+    assert_eq!(handle.operacake_ports(0),
+               Some((OperaCakePort::B3, OperaCakePort::A3)));
+
+    let too_many = [ranges[0]; OPERACAKE_MAX_FREQ_RANGES + 1];
+    let backwards = [OperaCakeRange::new(500, 100, OperaCakePort::A1)];
+    for bad in [&too_many[..], &backwards[..], &[]] {
+        assert_eq!(device.set_operacake_freq_ranges(bad),
+                   Err(HackRFError::InvalidParam));
+    }
+    assert_eq!(handle.operacake_freq_ranges(), ranges.to_vec());
+}
+
+#[test]
+fn dwell_times_set_time_mode() {
+    let (mut device, handle) = simulated(SimulatedHackRF::new().operacake(1));
+    let dwells = [OperaCakeDwell::new(100_000, OperaCakePort::A1),
+                  OperaCakeDwell::new(200_000, OperaCakePort::B4)];
+    let mut cake = device.operacake(1).unwrap();
+    cake.switch_by_time(&dwells).unwrap();
+    assert_eq!(cake.mode().unwrap(), OperaCakeMode::Time);
+    assert_eq!(handle.operacake_dwell_times(), dwells.to_vec());
+
+    let too_many = [dwells[0]; OPERACAKE_MAX_DWELL_TIMES + 1];
+    let empty = [OperaCakeDwell::new(0, OperaCakePort::A1)];
+    for bad in [&too_many[..], &empty[..], &[]] {
+        assert_eq!(cake.switch_by_time(bad), Err(HackRFError::InvalidParam));
+    }
+    assert_eq!(cake.mode().unwrap(), OperaCakeMode::Time);
+}
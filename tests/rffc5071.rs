@@ -0,0 +1,69 @@
+// Tests for RFFC5071 register access, LO decoding and the firmware's tuning
+// plan, against the simulator.
+
+use hackrf::{tuning_plan, HackRFError, RFPathFilter, Rffc5071Path,
+             SimulatedHackRF};
+
+mod common;
+use common::simulated;
+
+#[test]
+fn tuning_plan_follows_the_firmware_bands() {
+    let plan = tuning_plan(100_000_000).unwrap();
+    assert_eq!(plan.filter, RFPathFilter::LowPass);
+    let lo_hz = plan.lo_hz.unwrap();
+    assert!((lo_hz as i64 - 2_735_000_000).abs() < 10, "{}", lo_hz);
+    assert_eq!(plan.if_hz, lo_hz - 100_000_000);
+
+    let plan = tuning_plan(2_450_000_000).unwrap();
+    assert_eq!(plan.filter, RFPathFilter::Bypass);
+    assert_eq!((plan.if_hz, plan.lo_hz), (2_450_000_000, None));
+
+    for &freq in &[2_800_000_000u64, 4_000_000_000, 5_800_000_000] {
+        let plan = tuning_plan(freq).unwrap();
+        assert_eq!(plan.filter, RFPathFilter::HighPass);
+        assert_eq!(plan.if_hz + plan.lo_hz.unwrap(), freq);
+        assert!((2_170_000_000..=2_740_000_000).contains(&plan.if_hz));
+    }
+    assert_eq!(tuning_plan(7_300_000_000), None);
+}
+
+#[test]
+fn programmed_lo_matches_the_tuning_plan() {
+    let mut device = simulated(SimulatedHackRF::new()).0;
+    for &freq in &[10_000_000u64, 433_920_000, 915_000_000, 3_500_000_000,
+                   6_000_000_000] {
+        device.set_freq(freq).unwrap();
+        let registers = device.rffc5071_dump().unwrap();
+        assert!(registers.enabled());
+        let path = registers.active_path();
+        assert_eq!(path, Rffc5071Path::One);
+        assert_eq!(Some(registers.lo_hz(path)),
+                   tuning_plan(freq).unwrap().lo_hz);
+        assert!(registers.vco_hz(path) >= 2_700_000_000);
+    }
+
+    device.set_freq(2_450_000_000).unwrap();
+    assert!(!device.rffc5071_dump().unwrap().enabled());
+}
+
+#[test]
+fn explicit_lo_is_programmed_in_whole_mhz() {
+    let mut device = simulated(SimulatedHackRF::new()).0;
+    device.set_freq_explicit(2_400_000_000, 1_000_000_000,
+                             RFPathFilter::LowPass).unwrap();
+    let registers = device.rffc5071_dump().unwrap();
+    let lo_hz = registers.lo_hz(Rffc5071Path::One);
+    assert!((lo_hz as i64 - 1_000_000_000).abs() < 10, "{}", lo_hz);
+    assert_eq!(registers.lo_divider(Rffc5071Path::One), 4);
+    assert_eq!(registers.prescaler(Rffc5071Path::One), 4);
+}
+
+#[test]
+fn registers_are_range_checked() {
+    let mut device = simulated(SimulatedHackRF::new()).0;
+    device.rffc5071_write(30, 0xbeef).unwrap();
+    assert_eq!(device.rffc5071_read(30), Ok(0xbeef));
+    assert_eq!(device.rffc5071_read(31), Err(HackRFError::InvalidParam));
+    assert_eq!(device.rffc5071_write(31, 0), Err(HackRFError::InvalidParam));
+}
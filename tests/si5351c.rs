@@ -0,0 +1,57 @@
+// Tests for Si5351C register access and clock decoding, against the
+// simulator.
+
+use hackrf::{Si5351cPll, Si5351cRegisters, Si5351cSource, SimulatedHackRF};
+
+mod common;
+use common::simulated;
+
+#[test]
+fn sample_rate_reads_back_from_the_multisynth() {
+    let mut device = simulated(SimulatedHackRF::new()).0;
+    let registers = device.si5351c_dump().unwrap();
+    assert_eq!(registers.pll_hz(Si5351cPll::A), Some(800e6));
+    assert_eq!(registers.sample_rate_hz(), Some(10e6));
+
+    device.set_sample_rate_manual(20_000_000, 3).unwrap();
+    let rate = device.si5351c_dump().unwrap().sample_rate_hz().unwrap();
+    assert!((rate - 20e6 / 3.0).abs() < 1e-3, "{}", rate);
+
+    device.set_sample_rate(12.5e6).unwrap();
+    let rate = device.si5351c_dump().unwrap().sample_rate_hz().unwrap();
+    assert_eq!(rate, 12.5e6);
+
+    // Low rates use the R divider.
+    device.set_sample_rate_manual(100_000, 1).unwrap();
+    let registers = device.si5351c_dump().unwrap();
+    assert!(registers.output_divider(0).unwrap() > 2048.0);
+    assert_eq!(registers.sample_rate_hz(), Some(100e3));
+}
+
+#[test]
+fn external_reference_is_detected() {
+    let mut device = simulated(SimulatedHackRF::new()).0;
+    let registers = device.si5351c_dump().unwrap();
+    assert_eq!(registers.pll_source(Si5351cPll::A), Si5351cSource::Xtal);
+    assert!(!registers.clkin_present());
+    assert!(!registers.external_reference_locked());
+
+    let mut device = simulated(SimulatedHackRF::new().clkin(true)).0;
+    let registers = device.si5351c_dump().unwrap();
+    assert!(registers.external_reference_locked());
+    assert_eq!(registers.pll_hz(Si5351cPll::A), Some(800e6));
+
+    // Losing lock on PLLA is reported.
+    device.si5351c_write(0, 1 << 5).unwrap();
+    assert!(!device.si5351c_dump().unwrap().external_reference_locked());
+}
+
+#[test]
+fn powered_down_clocks_have_no_frequency() {
+    let mut device = simulated(SimulatedHackRF::new()).0;
+    device.si5351c_write(16, 0x80).unwrap();
+    let registers = device.si5351c_dump().unwrap();
+    assert_eq!(registers.clock_hz(0), None);
+    assert_eq!(registers.clock_hz(8), None);
+    assert_eq!(Si5351cRegisters::default().sample_rate_hz(), None);
+}
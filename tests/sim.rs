@@ -0,0 +1,237 @@
+// Tests for the simulated HackRF backend, driven through HackRFDevice.
+
+use std::f64::consts::PI;
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+
+use hackrf::{BoardId, HackRFDevice, HackRFError, PartId, RFPathFilter,
+             SerialNumber, Signal, SimulatedHackRF, IQ8};
+
+mod common;
+use common::simulated;
+
+/// A simulated board with small transfers, to keep the tests quick.
+fn board() -> SimulatedHackRF {
+    SimulatedHackRF::new().transfer_samples(4096)
+}
+
+/// Receive one transfer from the device.
+fn receive(device: &mut HackRFDevice) -> Vec<IQ8> {
+    let mut stream = device.rx_buffers(2).unwrap();
+    let buffer = stream.recv().unwrap();
+    buffer.to_vec()
+}
+
+/// Mean phase step between consecutive samples, in radians.
+fn phase_step(samples: &[IQ8]) -> f64 {
+    let (mut re, mut im) = (0.0, 0.0);
+    for pair in samples.windows(2) {
+        let a = pair[0].to_complex_f32();
+        let b = pair[1].to_complex_f32();
+        let step = b * a.conj();
+        re += step.re as f64;
+        im += step.im as f64;
+    }
+    im.atan2(re)
+}
+
+#[test]
+fn reports_board_identity() {
+    let sim = board()
+        .version("2024.02.1")
+        .partid_serialno([1, 2], [3, 4, 5, 6]);
+    let (mut device, _) = simulated(sim);
+    assert_eq!(device.board_id_read().unwrap(), BoardId::HackRFOne);
+    assert_eq!(device.version_string_read().unwrap(), "2024.02.1");
+    assert_eq!(device.board_partid_serialno_read().unwrap(),
+               (PartId([1, 2]), SerialNumber([3, 4, 5, 6])));
+    assert_eq!(device.usb_api_version_read().unwrap(), 0x0108);
+    assert_eq!(device.board_rev_read().unwrap(), (0x84, "r9".into()));
+
+    let sim = board().usb_api_version(0x0102).board_rev(0);
+    let (mut device, _) = simulated(sim);
+    assert_eq!(device.usb_api_version_read().unwrap(), 0x0102);
+    assert_eq!(device.board_rev_read().unwrap(), (0, "older than r6".into()));
+    let (mut device, _) = simulated(board().board_rev(0xfe));
+    assert_eq!(device.board_rev_read().unwrap().1, "unrecognized");
+}
+
+#[test]
+fn validates_tuning_like_the_firmware() {
+    let (mut device, handle) = simulated(board());
+    device.set_freq(433_920_000).unwrap();
+    assert_eq!(handle.freq_hz(), 433_920_000);
+    assert_eq!(device.set_freq(7_300_000_000), Err(HackRFError::Libusb));
+    assert_eq!(handle.freq_hz(), 433_920_000);
+
+    device.set_freq_explicit(2_400_000_000, 1_000_000_000,
+                             RFPathFilter::LowPass).unwrap();
+    assert_eq!(handle.freq_hz(), 1_400_000_000);
+    device.set_freq_explicit(2_600_000_000, 3_000_000_000,
+                             RFPathFilter::LowPass).unwrap();
+    assert_eq!(handle.freq_hz(), 400_000_000);
+    assert_eq!(device.set_freq_explicit(2_000_000_000, 1_000_000_000,
+                                        RFPathFilter::HighPass),
+               Err(HackRFError::InvalidParam));
+    assert_eq!(device.set_freq_explicit(2_400_000_000, 10_000_000,
+                                        RFPathFilter::LowPass),
+               Err(HackRFError::InvalidParam));
+    device.set_freq_explicit(2_400_000_000, 0, RFPathFilter::Bypass)
+          .unwrap();
+    assert_eq!(handle.freq_hz(), 2_400_000_000);
+}
+
+#[test]
+fn validates_gains_and_filters() {
+    let (mut device, handle) = simulated(board());
+    device.set_lna_gain(30).unwrap();
+    device.set_vga_gain(21).unwrap();
+    device.set_txvga_gain(47).unwrap();
+    assert_eq!((handle.lna_gain(), handle.vga_gain(), handle.txvga_gain()),
+               (24, 20, 47));
+    assert_eq!(device.set_lna_gain(48), Err(HackRFError::InvalidParam));
+    assert_eq!(device.set_txvga_gain(48), Err(HackRFError::InvalidParam));
+
+    device.set_baseband_filter_bandwidth(4_000_000).unwrap();
+    assert_eq!(handle.baseband_filter_bandwidth(), 5_000_000);
+    assert_eq!(device.set_baseband_filter_bandwidth(30_000_000),
+               Err(HackRFError::Libusb));
+
+    device.set_sample_rate_manual(20_000_000, 2).unwrap();
+    assert_eq!(handle.sample_rate(), 10e6);
+    assert_eq!(device.set_sample_rate_manual(20_000_000, 0),
+               Err(HackRFError::Libusb));
+    device.set_amp_enable(true).unwrap();
+    device.set_antenna_enable(true).unwrap();
+    assert!(handle.amp_enable() && handle.antenna_enable());
+}
+
+#[test]
+fn is_streaming_fails_before_a_stream_starts() {
+    let (mut device, _) = simulated(board());
+    assert_eq!(device.is_streaming(), Err(HackRFError::StreamingThreadErr));
+}
+
+#[test]
+fn tone_appears_at_its_offset_from_the_tuned_frequency() {
+    let sim = board().signal(Signal::tone(101e6, 0.5));
+    let (mut device, handle) = simulated(sim);
+    device.set_sample_rate(8e6).unwrap();
+    device.set_freq(100_000_000).unwrap();
+    let samples = receive(&mut device);
+    let expected = 2.0 * PI * 1e6 / 8e6;
+    assert!((phase_step(&samples) - expected).abs() < 0.01);
+    assert!(samples.iter().all(|s| {
+        let m = s.to_complex_f32().norm_sqr().sqrt();
+        (m - 0.5).abs() < 0.02
+    }));
+
+    // Retuning moves the tone to the other side of the centre.
+    device.set_freq(102_000_000).unwrap();
+    let samples = receive(&mut device);
+    assert!((phase_step(&samples) + expected).abs() < 0.01);
+
+    // Replacing the signal moves it back without retuning.
+    handle.set_signals(vec![Signal::tone(103e6, 0.5)]);
+    let samples = receive(&mut device);
+    assert!((phase_step(&samples) - expected).abs() < 0.01);
+
+    // Outside the sampled bandwidth it is not received at all.
+    device.set_freq(200_000_000).unwrap();
+    let samples = receive(&mut device);
+    assert!(samples.iter().all(|&s| s == IQ8::default()));
+}
+
+#[test]
+fn noise_has_the_requested_power() {
+    let sim = board().signal(Signal::noise(0.1));
+    let (mut device, _) = simulated(sim);
+    let samples = receive(&mut device);
+    let n = samples.len() as f64;
+    let mean: f64 = samples.iter().map(|s| s.i as f64).sum::<f64>() / n;
+    let power: f64 = samples.iter()
+        .map(|s| (s.i as f64).powi(2) + (s.q as f64).powi(2))
+        .sum::<f64>() / n;
+    let rms = (power / 2.0).sqrt() / 128.0;
+    assert!(mean.abs() < 1.0);
+    assert!((rms - 0.1).abs() < 0.01, "rms {}", rms);
+}
+
+#[test]
+fn recording_is_played_back_in_a_loop() {
+    let recording: Vec<IQ8> = (0..100).map(|i| IQ8::new(i, -i)).collect();
+    let sim = board()
+        .signal(Signal::recording(recording.clone()))
+        .transfer_samples(64);
+    let handle = sim.handle();
+    let mut device = HackRFDevice::from_backend(sim.realtime(false));
+    let (tx, rx) = mpsc::channel();
+    let stream = device.rx(move |buffer: &[IQ8]| {
+        tx.send(buffer.to_vec()).is_ok()
+    }).unwrap();
+    let received: Vec<IQ8> = rx.iter().take(4).flatten().collect();
+    stream.stop().unwrap();
+    let expected: Vec<IQ8> = recording.iter().cycle().take(256)
+                                      .cloned().collect();
+    assert_eq!(received, expected);
+    assert!(handle.is_open());
+}
+
+#[test]
+fn recording_is_loaded_from_a_file() {
+    let path = std::env::temp_dir().join(
+        format!("hackrf-sim-test-{}.iq", std::process::id()));
+    std::fs::write(&path, [1u8, 2, 0xff, 0xfe, 7]).unwrap();
+    let signal = Signal::from_file(&path);
+    std::fs::remove_file(&path).unwrap();
+    match signal.unwrap() {
+        Signal::Recording(samples) => {
+            assert_eq!(&samples[..], &[IQ8::new(1, 2), IQ8::new(-1, -2)]);
+        },
+        other => panic!("unexpected {:?}", other)
+    }
+}
+
+#[test]
+fn transmitted_samples_are_captured() {
+    let (mut device, handle) = simulated(board());
+    let mut sent = 0;
+    let mut stream = device.tx(move |buffer: &mut [IQ8]| {
+        sent += 1;
+        buffer.fill(IQ8::new(sent, -sent));
+        sent < 3
+    }).unwrap();
+    while stream.is_streaming().unwrap() {
+        thread::sleep(Duration::from_millis(1));
+    }
+    stream.stop().unwrap();
+    let transmitted = handle.take_transmitted();
+    assert_eq!(transmitted.len(), 2 * 4096);
+    assert!(transmitted[..4096].iter().all(|&s| s == IQ8::new(1, -1)));
+    assert!(transmitted[4096..].iter().all(|&s| s == IQ8::new(2, -2)));
+    assert!(handle.transmitted().is_empty());
+}
+
+#[test]
+fn panicking_callback_stops_the_stream() {
+    let (mut device, _) = simulated(board());
+    let mut stream = device.rx(|_: &[IQ8]| panic!("callback failed"))
+                           .unwrap();
+    while stream.is_streaming().unwrap() {
+        thread::sleep(Duration::from_millis(1));
+    }
+}
+
+#[test]
+fn closing_stops_the_simulated_device() {
+    let (device, handle) = simulated(board());
+    device.close().unwrap();
+    assert!(!handle.is_open());
+
+    let (mut device, handle) = simulated(board());
+    let stream = device.rx(|_: &[IQ8]| true).unwrap();
+    drop(stream);
+    drop(device);
+    assert!(!handle.is_open());
+}
@@ -0,0 +1,121 @@
+// Tests for hackrf_sweep-style power spectra.
+#![cfg(feature = "spectrum")]
+
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, UNIX_EPOCH};
+
+use hackrf::{HackRFDevice, HackRFError, PowerSpectrum, Signal,
+             SimulatedHackRF, SpectrumRow, SweepBlock, SweepConfig, IQ8,
+             SWEEP_BLOCK_SAMPLES};
+
+#[test]
+fn fft_size_is_rounded_like_hackrf_sweep() {
+    let spectrum = PowerSpectrum::new(20_000_000, 1_000_000).unwrap();
+    assert_eq!(spectrum.fft_size(), 20);
+    assert_eq!(spectrum.bin_width(), 1e6);
+    let spectrum = PowerSpectrum::new(20_000_000, 500_000).unwrap();
+    assert_eq!(spectrum.fft_size(), 44);
+    assert!(PowerSpectrum::new(20_000_000, 2_000).is_err());
+}
+
+#[test]
+fn rows_format_as_hackrf_sweep_csv() {
+    let row = SpectrumRow {
+        time: UNIX_EPOCH + Duration::new(1_700_000_000, 123_456_000),
+        hz_low: 2_400_000_000,
+        hz_high: 2_405_000_000,
+        hz_bin_width: 1_000_000.0,
+        num_samples: 20,
+        db: vec![-70.5, -65.25, f32::NEG_INFINITY]
+    };
+    assert_eq!(row.to_csv_with_offset(0),
+               "2023-11-14, 22:13:20.123456, 2400000000, 2405000000, \
+                1000000.00, 20, -70.50, -65.25, -inf");
+    assert!(row.to_csv_with_offset(2 * 3600)
+               .starts_with("2023-11-15, 00:13:20.123456, "));
+    assert!(row.to_csv_with_offset(-23 * 3600)
+               .starts_with("2023-11-13, 23:13:20.123456, "));
+    // Local time differs from UTC only in the date and time fields.
+    let local = row.to_string();
+    assert_eq!(local.len(), row.to_csv_with_offset(0).len());
+    assert!(local.ends_with(", 2400000000, 2405000000, 1000000.00, 20, \
+                             -70.50, -65.25, -inf"));
+}
+
+#[test]
+fn each_step_becomes_two_rows_once_the_next_step_starts() {
+    let mut spectrum = PowerSpectrum::new(20_000_000, 1_000_000).unwrap();
+    let mut transfer = vec![IQ8::new(10, 0); SWEEP_BLOCK_SAMPLES];
+    SweepBlock::write_header(&mut transfer, 100_000_000);
+    let block = SweepBlock::parse(&transfer).unwrap();
+    assert!(spectrum.push(&block, UNIX_EPOCH).is_empty());
+    assert!(spectrum.push(&block, UNIX_EPOCH).is_empty());
+
+    SweepBlock::write_header(&mut transfer, 105_000_000);
+    let block = SweepBlock::parse(&transfer).unwrap();
+    let rows = spectrum.push(&block, UNIX_EPOCH);
+    assert_eq!(rows.len(), 2);
+    assert_eq!((rows[0].hz_low, rows[0].hz_high), (100_000_000, 105_000_000));
+    assert_eq!((rows[1].hz_low, rows[1].hz_high), (110_000_000, 115_000_000));
+    assert_eq!(rows[0].db.len(), 5);
+
+    let rows = spectrum.flush();
+    assert_eq!(rows[0].hz_low, 105_000_000);
+    assert!(spectrum.flush().is_empty());
+}
+
+#[test]
+fn simulated_tone_shows_up_in_the_right_bin() {
+    let sim = SimulatedHackRF::new()
+        .signal(Signal::tone(112.5e6, 0.5))
+        .transfer_samples(SWEEP_BLOCK_SAMPLES)
+        .realtime(false);
+    let mut device = HackRFDevice::from_backend(sim);
+    let (tx, rx) = mpsc::channel();
+    let stream = device.sweep_spectrum(
+        &SweepConfig::new(100, 140), 1_000_000,
+        move |row: &SpectrumRow| tx.send(row.clone()).is_ok()).unwrap();
+    let rows: Vec<SpectrumRow> = rx.iter().take(8).collect();
+    stream.stop().unwrap();
+
+    let lows: Vec<u64> = rows.iter().map(|r| r.hz_low / 1_000_000).collect();
+    assert_eq!(lows, [100, 110, 105, 115, 120, 130, 125, 135]);
+    let peak = |row: &SpectrumRow| row.db.iter().cloned()
+                                      .fold(f32::MIN, f32::max);
+    let tone = rows.iter().find(|r| r.hz_low == 110_000_000).unwrap();
+    // 112.5MHz falls in the third 1MHz bin.
+    assert_eq!(tone.db[2], peak(tone));
+    assert!(peak(tone) > -15.0);
+    for row in rows.iter().filter(|r| r.hz_low != 110_000_000) {
+        assert!(peak(row) < peak(tone) - 30.0, "{}", row);
+    }
+}
+
+#[test]
+fn last_step_is_passed_on_when_the_stream_stops() {
+    let sim = SimulatedHackRF::new()
+        .transfer_samples(SWEEP_BLOCK_SAMPLES)
+        .realtime(false);
+    let mut device = HackRFDevice::from_backend(sim);
+    let (tx, rx) = mpsc::channel();
+    // A single step never moves on to another, so only stopping ends it.
+    let stream = device.sweep_spectrum(
+        &SweepConfig::new(100, 105), 1_000_000,
+        move |row: &SpectrumRow| tx.send(row.clone()).is_ok()).unwrap();
+    thread::sleep(Duration::from_millis(50));
+    assert!(rx.try_recv().is_err());
+    stream.stop().unwrap();
+
+    let lows: Vec<u64> = rx.try_iter().map(|r| r.hz_low).collect();
+    assert_eq!(lows, [100_000_000, 110_000_000]);
+}
+
+#[test]
+fn sweep_spectrum_needs_hackrf_sweep_steps() {
+    let sim = SimulatedHackRF::new().realtime(false);
+    let mut device = HackRFDevice::from_backend(sim);
+    let config = SweepConfig::new(100, 140).offset(0);
+    assert!(matches!(device.sweep_spectrum(&config, 1_000_000, |_| true),
+                     Err(HackRFError::InvalidParam)));
+}
@@ -0,0 +1,91 @@
+// Tests for SPI flash access and firmware updates, against the simulator.
+
+use hackrf::{check_firmware_image, FirmwareError, FlashProgress, FlashStage,
+             HackRFError, SimulatedHackRF, SPIFLASH_SIZE};
+
+/// A plausible image: stack in local SRAM, Thumb reset vector, then filler.
+fn image(len: usize) -> Vec<u8> {
+    let mut image = vec![0x00, 0x80, 0x00, 0x10, 0x01, 0x01, 0x00, 0x14];
+    image.extend((8..len).map(|i| (i * 7) as u8));
+    image
+}
+
+mod common;
+use common::simulated;
+
+#[test]
+fn firmware_is_flashed_and_verified() {
+    let old = image(5000);
+    let mut new = image(3000);
+    for b in &mut new[8..] {
+        *b = !*b;
+    }
+    let (mut device, handle) = simulated(SimulatedHackRF::new()
+                                         .firmware(&old));
+    let mut stages = Vec::new();
+    let mut last = None;
+    device.flash_firmware(&new, |p: FlashProgress| {
+        if stages.last() != Some(&p.stage) {
+            stages.push(p.stage);
+        }
+        last = Some(p);
+    }).unwrap();
+    assert_eq!(stages, [FlashStage::Erasing, FlashStage::Writing,
+                        FlashStage::Verifying]);
+    assert_eq!(last, Some(FlashProgress { stage: FlashStage::Verifying,
+                                          done: 3000, total: 3000 }));
+    let flash = handle.spiflash();
+    assert_eq!(&flash[..3000], &new[..]);
+    assert!(flash[3000..].iter().all(|&b| b == 0xff));
+}
+
+#[test]
+fn current_firmware_can_be_read_back() {
+    let old = image(1000);
+    let (mut device, _) = simulated(SimulatedHackRF::new().firmware(&old));
+    let mut pages = 0;
+    let flash = device.read_firmware(|_| pages += 1).unwrap();
+    assert_eq!(flash.len(), SPIFLASH_SIZE);
+    assert_eq!(&flash[..1000], &old[..]);
+    assert_eq!(pages, SPIFLASH_SIZE / 256 + 1);
+}
+
+#[test]
+fn writing_without_erasing_fails_verification() {
+    let (mut device, _) = simulated(SimulatedHackRF::new()
+                                    .firmware(&[0; 512]));
+    let data = [0x5a; 300];
+    device.spiflash_write(0, &data).unwrap();
+    let mut readback = [0; 300];
+    device.spiflash_read(0, &mut readback).unwrap();
+    assert_eq!(readback, [0; 300]);
+    device.spiflash_erase().unwrap();
+    device.spiflash_write(0, &data).unwrap();
+    device.spiflash_read(0, &mut readback).unwrap();
+    assert_eq!(readback, data);
+
+    assert_eq!(device.spiflash_read(SPIFLASH_SIZE as u32 - 4, &mut [0; 8]),
+               Err(HackRFError::InvalidParam));
+}
+
+#[test]
+fn bad_images_are_refused_before_erasing() {
+    let old = image(1000);
+    let (mut device, handle) = simulated(SimulatedHackRF::new()
+                                         .firmware(&old));
+    let mut dfu = image(100);
+    dfu.extend_from_slice(b"\x00\x00\x00\x00\x00\x00\x00\x00\
+                            \x00\x01UFD\x10\x00\x00\x00\x00");
+    let mut bad_stack = image(100);
+    bad_stack[3] = 0x40;
+    let mut arm_reset = image(100);
+    arm_reset[4] = 0x00;
+    for bad in [vec![], vec![0xff; 4096], vec![1; SPIFLASH_SIZE + 1], dfu,
+                bad_stack, arm_reset] {
+        let result = device.flash_firmware(&bad, |_| ());
+        assert!(matches!(result, Err(FirmwareError::Invalid(_))),
+                "{:?}", result);
+    }
+    assert_eq!(&handle.spiflash()[..1000], &old[..]);
+    assert!(check_firmware_image(&image(100)).is_ok());
+}
@@ -0,0 +1,64 @@
+// Tests for receiving into a buffer pool with rx_buffers, against the
+// simulator.
+
+use std::thread;
+use std::time::Duration;
+
+use hackrf::{HackRFError, SimulatedHackRF};
+
+mod common;
+use common::simulated;
+
+/// A simulated board with transfers short enough to queue many of.
+fn board() -> SimulatedHackRF {
+    SimulatedHackRF::new().transfer_samples(1024)
+}
+
+#[test]
+fn lagging_consumer_sees_dropped_transfers() {
+    let (mut device, _) = simulated(board());
+    let mut stream = device.rx_buffers(2).unwrap();
+    thread::sleep(Duration::from_millis(50));
+    let first = stream.recv().unwrap();
+    let second = stream.recv().unwrap();
+    assert_eq!((first.sequence(), second.sequence()), (0, 1));
+    assert_eq!(first.dropped_before(), 0);
+    assert_eq!(second.dropped_before(), 0);
+    drop((first, second));
+
+    // Everything received while both buffers were queued or held was lost.
+    let third = stream.recv().unwrap();
+    assert!(third.dropped_before() > 0);
+    assert_eq!(third.sequence(), 2 + third.dropped_before());
+    assert!(stream.dropped() >= third.dropped_before());
+}
+
+#[test]
+fn buffers_go_back_to_the_pool() {
+    let (mut device, _) = simulated(board());
+    let mut stream = device.rx_buffers(1).unwrap();
+    let first = stream.recv().unwrap();
+    let data = first.as_bytes().as_ptr();
+    // While the only buffer is held nothing more can be queued.
+    thread::sleep(Duration::from_millis(20));
+    assert!(stream.try_recv().is_none());
+    drop(first);
+
+    let second = stream.recv().unwrap();
+    assert_eq!(second.as_bytes().as_ptr(), data);
+    assert!(second.dropped_before() > 0);
+}
+
+#[test]
+fn recv_ends_once_the_device_stops() {
+    let (mut device, handle) = simulated(board());
+    let mut stream = device.rx_buffers(4).unwrap();
+    thread::sleep(Duration::from_millis(20));
+    handle.unplug();
+    // Buffers already queued, and the transfer under way when unplugged,
+    // are still received before the end.
+    let queued = stream.by_ref().count();
+    assert!((1..=5).contains(&queued), "{}", queued);
+    assert_eq!(stream.recv().err(), Some(HackRFError::StreamingStopped));
+    assert_eq!(stream.is_streaming(), Ok(false));
+}
@@ -0,0 +1,111 @@
+// Tests for sweep mode: step planning, block parsing and a simulated sweep.
+
+use std::sync::mpsc;
+
+use hackrf::{HackRFDevice, HackRFError, Signal, SimulatedHackRF, SweepBlock,
+             SweepConfig, SweepStyle, IQ8, MAX_SWEEP_DWELL_SAMPLES,
+             SWEEP_BLOCK_SAMPLES};
+
+#[test]
+fn linear_steps_cover_each_range() {
+    let config = SweepConfig::new(100, 160)
+        .range(2400, 2440)
+        .style(SweepStyle::Linear);
+    let mhz: Vec<u64> = config.steps().iter().map(|f| f / 1_000_000)
+                              .collect();
+    assert_eq!(mhz, [100, 120, 140, 2400, 2420]);
+}
+
+#[test]
+fn interleaved_steps_alternate_quarter_and_three_quarter_widths() {
+    let config = SweepConfig::new(100, 140);
+    let mhz: Vec<u64> = config.steps().iter().map(|f| f / 1_000_000)
+                              .collect();
+    assert_eq!(mhz, [100, 105, 120, 125]);
+}
+
+#[test]
+fn invalid_configs_are_rejected() {
+    assert!(SweepConfig::new(100, 200).validate().is_ok());
+    assert!(SweepConfig::new(200, 100).validate().is_err());
+    assert!(SweepConfig::new(100, 7300).validate().is_err());
+    assert!(SweepConfig::new(100, 200).dwell_samples(1000).validate()
+                                      .is_err());
+    assert!(SweepConfig::new(100, 200)
+                .dwell_samples(MAX_SWEEP_DWELL_SAMPLES).validate().is_ok());
+    let too_long = MAX_SWEEP_DWELL_SAMPLES + SWEEP_BLOCK_SAMPLES as u32;
+    assert!(SweepConfig::new(100, 200).dwell_samples(too_long).validate()
+                                      .is_err());
+    let mut config = SweepConfig::new(0, 10);
+    for i in 1..10 {
+        config = config.range(i * 10, i * 10 + 10);
+    }
+    assert!(config.validate().is_ok());
+    assert_eq!(config.range(100, 110).validate(),
+               Err(HackRFError::InvalidParam));
+}
+
+#[test]
+fn blocks_are_split_and_tagged_by_header() {
+    let mut transfer = vec![IQ8::new(3, 4); 3 * SWEEP_BLOCK_SAMPLES];
+    SweepBlock::write_header(&mut transfer, 2_400_000_000);
+    SweepBlock::write_header(&mut transfer[2 * SWEEP_BLOCK_SAMPLES..],
+                             915_000_000);
+    let blocks: Vec<SweepBlock> = SweepBlock::split(&transfer).collect();
+    assert_eq!(blocks.len(), 2);
+    assert_eq!(blocks[0].freq_hz, 2_400_000_000);
+    assert_eq!(blocks[1].freq_hz, 915_000_000);
+    assert_eq!(blocks[0].samples.len(), SWEEP_BLOCK_SAMPLES - 5);
+    assert!(blocks[1].samples.iter().all(|&s| s == IQ8::new(3, 4)));
+}
+
+#[test]
+fn simulated_sweep_visits_every_step_with_its_signal() {
+    // A tone 2MHz above the tuned frequency of the 120MHz step only.
+    let sim = SimulatedHackRF::new()
+        .signal(Signal::tone(129.5e6, 0.5))
+        .transfer_samples(2 * SWEEP_BLOCK_SAMPLES)
+        .realtime(false);
+    let mut device = HackRFDevice::from_backend(sim);
+    device.set_sample_rate(20e6).unwrap();
+    let config = SweepConfig::new(100, 160).style(SweepStyle::Linear);
+    let (tx, rx) = mpsc::channel();
+    let stream = device.sweep(&config, move |block: SweepBlock| {
+        let power = block.samples.iter()
+            .map(|s| s.to_complex_f32().norm_sqr()).sum::<f32>()
+            / block.samples.len() as f32;
+        tx.send((block.freq_hz, power)).is_ok()
+    }).unwrap();
+    let blocks: Vec<(u64, f32)> = rx.iter().take(6).collect();
+    stream.stop().unwrap();
+
+    let freqs: Vec<u64> = blocks.iter().map(|b| b.0 / 1_000_000).collect();
+    assert_eq!(freqs, [100, 120, 140, 100, 120, 140]);
+    for &(freq, power) in &blocks {
+        if freq == 120_000_000 {
+            assert!((power - 0.25).abs() < 0.01);
+        } else {
+            assert_eq!(power, 0.0);
+        }
+    }
+}
+
+#[test]
+fn unplugging_ends_a_simulated_sweep() {
+    let sim = SimulatedHackRF::new()
+        .transfer_samples(SWEEP_BLOCK_SAMPLES)
+        .realtime(false);
+    let handle = sim.handle();
+    let mut device = HackRFDevice::from_backend(sim);
+    let (tx, rx) = mpsc::channel();
+    let stream = device.sweep(&SweepConfig::new(100, 160),
+                              move |block: SweepBlock| {
+        tx.send(block.freq_hz).is_ok()
+    }).unwrap();
+    rx.recv().unwrap();
+    handle.unplug();
+    // The transfer thread exits, dropping the callback and its sender, so
+    // the blocks still to come are finite.
+    rx.iter().count();
+    stream.stop().unwrap();
+}
@@ -0,0 +1,147 @@
+// Tests for half-duplex switching with Transceiver, against the simulator.
+
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use hackrf::{HackRFDevice, HackRFError, RadioConfig, SimHandle,
+             SimulatedHackRF, StreamDirection, Transceiver, IQ8};
+
+/// A transceiver whose every open creates a new simulated board, returning
+/// the handles of all the boards opened so far.
+fn transceiver(config: RadioConfig)
+               -> (Transceiver, Arc<Mutex<Vec<SimHandle>>>) {
+    let handles = Arc::new(Mutex::new(Vec::new()));
+    let opened = handles.clone();
+    let transceiver = Transceiver::new(move || {
+        let sim = SimulatedHackRF::new().transfer_samples(1024)
+            .realtime(false);
+        opened.lock().unwrap().push(sim.handle());
+        Ok(HackRFDevice::from_backend(sim))
+    }, config).unwrap();
+    (transceiver, handles)
+}
+
+fn config() -> RadioConfig {
+    let mut config = RadioConfig::new(433_920_000, 8e6);
+    config.lna_gain = 16;
+    config.vga_gain = 20;
+    config.txvga_gain = 30;
+    config.amp_enable = true;
+    config
+}
+
+#[test]
+fn config_is_applied_on_open() {
+    let (transceiver, handles) = transceiver(config());
+    let handles = handles.lock().unwrap();
+    assert_eq!(handles.len(), 1);
+    assert_eq!(handles[0].freq_hz(), 433_920_000);
+    assert_eq!(handles[0].txvga_gain(), 30);
+    assert_eq!(transceiver.direction(), None);
+    assert_eq!(transceiver.config(), config());
+}
+
+#[test]
+fn changing_direction_reopens_and_reapplies() {
+    let (mut transceiver, handles) = transceiver(config());
+
+    let mut stream = transceiver.rx_buffers(4).unwrap();
+    assert_eq!(stream.recv().unwrap().len(), 1024);
+    stream.stop().unwrap();
+    let mut stream = transceiver.rx_buffers(4).unwrap();
+    stream.recv().unwrap();
+    stream.stop().unwrap();
+    assert_eq!(transceiver.reopens(), 0);
+    assert_eq!(transceiver.direction(), Some(StreamDirection::Rx));
+
+    let mut sent = 0;
+    let mut stream = transceiver.tx(move |buffer: &mut [IQ8]| {
+        buffer.fill(IQ8::new(10, -10));
+        sent += 1;
+        sent < 3
+    }).unwrap();
+    while stream.is_streaming().unwrap() {
+        thread::sleep(Duration::from_millis(1));
+    }
+    stream.stop().unwrap();
+    assert_eq!(transceiver.reopens(), 1);
+    assert_eq!(transceiver.direction(), Some(StreamDirection::Tx));
+
+    transceiver.rx_buffers(4).unwrap().stop().unwrap();
+    assert_eq!(transceiver.reopens(), 2);
+
+    let handles = handles.lock().unwrap();
+    assert_eq!(handles.len(), 3);
+    assert!(!handles[0].is_open() && !handles[1].is_open());
+    assert!(handles[2].is_open());
+    assert!(!handles[1].transmitted().is_empty());
+    for handle in handles.iter() {
+        assert_eq!(handle.freq_hz(), 433_920_000);
+        assert_eq!(handle.sample_rate(), 8e6);
+        assert_eq!((handle.lna_gain(), handle.vga_gain()), (16, 20));
+        assert!(handle.amp_enable());
+    }
+}
+
+#[test]
+fn set_config_is_kept_across_reopens() {
+    let (mut transceiver, handles) = transceiver(config());
+    let mut retuned = config();
+    retuned.freq_hz = 915_000_000;
+    transceiver.set_config(retuned).unwrap();
+    assert_eq!(handles.lock().unwrap()[0].freq_hz(), 915_000_000);
+
+    let mut bad = retuned;
+    bad.lna_gain = 48;
+    assert_eq!(transceiver.set_config(bad), Err(HackRFError::InvalidParam));
+    assert_eq!(transceiver.config(), retuned);
+
+    transceiver.reopen().unwrap();
+    let handles = handles.lock().unwrap();
+    assert_eq!(handles.len(), 2);
+    assert_eq!(handles[1].freq_hz(), 915_000_000);
+    assert_eq!(handles[1].lna_gain(), 16);
+}
+
+#[test]
+fn direct_settings_are_kept_across_reopens() {
+    let (mut transceiver, handles) = transceiver(config());
+    transceiver.device().unwrap().set_freq(868_000_000).unwrap();
+    transceiver.device().unwrap().set_vga_gain(40).unwrap();
+    assert_eq!(transceiver.config().freq_hz, 868_000_000);
+
+    transceiver.reopen().unwrap();
+    let handles = handles.lock().unwrap();
+    assert_eq!(handles[1].freq_hz(), 868_000_000);
+    assert_eq!(handles[1].vga_gain(), 40);
+    assert_eq!(handles[1].txvga_gain(), 30);
+}
+
+#[test]
+fn failed_start_leaves_direction_alone() {
+    let (mut transceiver, _) = transceiver(config());
+    assert_eq!(transceiver.rx_buffers(0).err(),
+               Some(HackRFError::InvalidParam));
+    assert_eq!(transceiver.direction(), None);
+    transceiver.rx_buffers(4).unwrap().stop().unwrap();
+    assert_eq!(transceiver.direction(), Some(StreamDirection::Rx));
+}
+
+#[test]
+fn failed_open_is_retried_on_next_use() {
+    let attempts = Arc::new(Mutex::new(0));
+    let counted = attempts.clone();
+    let mut transceiver = Transceiver::new(move || {
+        let mut attempts = counted.lock().unwrap();
+        *attempts += 1;
+        match *attempts {
+            2 => Err(HackRFError::NotFound),
+            _ => Ok(HackRFDevice::from_backend(SimulatedHackRF::new()))
+        }
+    }, config()).unwrap();
+    assert_eq!(transceiver.reopen(), Err(HackRFError::NotFound));
+    assert!(transceiver.device().is_ok());
+    assert_eq!(*attempts.lock().unwrap(), 3);
+    transceiver.close().unwrap();
+}
@@ -0,0 +1,62 @@
+// Tests for the XSVF parser and CPLD programming, against a sample file and
+// the simulator.
+
+use hackrf::{FirmwareError, FlashProgress, HackRFDevice, SimulatedHackRF,
+             Xsvf, XsvfCommand, XsvfError};
+
+const SAMPLE: &[u8] = include_bytes!("fixtures/sample.xsvf");
+
+#[test]
+fn sample_file_parses() {
+    let xsvf = Xsvf::parse(SAMPLE).unwrap();
+    let commands: Vec<&XsvfCommand> = xsvf.commands.iter().map(|c| &c.1)
+                                          .collect();
+    assert_eq!(commands.len(), 21);
+    assert_eq!(commands[0], &XsvfCommand::Comment("hackrf test cpld".into()));
+    assert_eq!(xsvf.commands[1].0, 18);
+    assert_eq!(commands[7], &XsvfCommand::Sir { bits: 8, tdi: vec![0x01] });
+    assert_eq!(commands[10], &XsvfCommand::SdrTdo {
+        tdi: vec![0; 4], tdo: vec![0x06, 0xe5, 0x80, 0x93] });
+    assert_eq!(commands[11], &XsvfCommand::Sir { bits: 8, tdi: vec![0xe8] });
+    assert_eq!(commands[12], &XsvfCommand::Wait {
+        wait_state: 1, end_state: 1, microseconds: 800 });
+    assert_eq!(commands[14], &XsvfCommand::SdrInc {
+        start: vec![0, 0, 0, 0x10],
+        data: vec![vec![0xaa, 0xbb], vec![0xcc, 0xdd]] });
+    assert_eq!(commands[20], &XsvfCommand::Complete);
+}
+
+#[test]
+fn malformed_files_are_rejected() {
+    assert_eq!(Xsvf::parse(&SAMPLE[..SAMPLE.len() - 1]),
+               Err(XsvfError::MissingComplete));
+    // Cut off part way through the XSDRTDO at offset 46.
+    assert_eq!(Xsvf::parse(&SAMPLE[..49]),
+               Err(XsvfError::Truncated { offset: 46 }));
+    assert_eq!(Xsvf::parse(&[0x12, 0x01, 0x05, 0x00]),
+               Err(XsvfError::UnknownCommand { offset: 2, opcode: 0x05 }));
+    assert_eq!(Xsvf::parse(&[0x12, 0x10, 0x00]),
+               Err(XsvfError::InvalidState { offset: 1, state: 0x10 }));
+    assert_eq!(Xsvf::parse(b"\x16unterminated"),
+               Err(XsvfError::Truncated { offset: 0 }));
+    // Anything after XCOMPLETE is ignored.
+    assert_eq!(Xsvf::parse(&[0x00, 0xff]).unwrap().commands.len(), 1);
+}
+
+#[test]
+fn cpld_is_programmed_with_progress() {
+    let sim = SimulatedHackRF::new();
+    let handle = sim.handle();
+    let mut device = HackRFDevice::from_backend(sim.realtime(false));
+    let mut xsvf = vec![0x16];
+    xsvf.extend_from_slice(&[b'x'; 1200]);
+    xsvf.extend_from_slice(&[0x00, 0x00]);
+    let mut done = Vec::new();
+    device.cpld_write(&xsvf, |p: FlashProgress| done.push(p.done)).unwrap();
+    assert_eq!(done, [0, 512, 1024, 1203]);
+    assert_eq!(handle.cpld(), xsvf);
+
+    let result = device.cpld_write(&SAMPLE[..49], |_| ());
+    assert!(matches!(result, Err(FirmwareError::Invalid(_))));
+    assert_eq!(handle.cpld(), xsvf);
+}